@@ -8,7 +8,7 @@ extern crate network_manager;
 use clap::{App, Arg};
 use std::io::Write;
 
-use network_manager::{Device, DeviceType, NetworkManager};
+use network_manager::{Device, DeviceType, HotspotConfig, NetworkManager};
 
 mod errors {
     use network_manager;
@@ -73,11 +73,13 @@ fn run() -> Result<()> {
     let device = find_device(&manager, matches.value_of("INTERFACE"))?;
     let wifi_device = device.as_wifi_device().unwrap();
 
-    wifi_device.create_hotspot(
-        matches.value_of("SSID").unwrap(),
-        matches.value_of("PASSWORD"),
-        None,
-    )?;
+    let mut config = HotspotConfig::new();
+
+    if let Some(password) = matches.value_of("PASSWORD") {
+        config = config.password(password);
+    }
+
+    wifi_device.create_hotspot(matches.value_of("SSID").unwrap(), &config)?;
 
     Ok(())
 }
@@ -0,0 +1,436 @@
+use futures::future::{self, Future};
+use futures::stream::{self, Stream};
+use futures::task::{self, Task};
+use futures::{Async, Poll};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use errors::*;
+
+use connection::{
+    request_activation, request_deactivation, Connection, ConnectionState, ConnectionStateWatch,
+    StateRequest,
+};
+use device::{connect_result, request_connect, request_disconnect, Device, DeviceState};
+use events::NetworkManagerEvent;
+use hotspot::HotspotConfig;
+use manager::{method_timeout, NetworkManager};
+use ssid::AsSsidSlice;
+use wifi::{
+    request_scan_watch, request_wifi_connect, request_wifi_hotspot, AccessPoint,
+    AccessPointCredentials, WiFiDevice,
+};
+
+/// How long a pending future waits before checking for new signals again.
+const POLL_INTERVAL_MS: u64 = 50;
+
+/// A future resolved on the thread that polls it.
+pub type NetworkManagerFuture<T> = Box<dyn Future<Item = T, Error = Error>>;
+
+/// A stream of Network Manager events. It does not end.
+pub type NetworkManagerEventStream = Box<dyn Stream<Item = NetworkManagerEvent, Error = Error>>;
+
+/// Access to Network Manager from futures.
+///
+/// Requests are sent when a method is called, and the futures resolve once
+/// Network Manager completes them, e.g. once a connection is activated.
+/// Waiting for connections and devices to change state and for scans to
+/// complete does not block: the futures handle the signals received so far
+/// each time they are polled, and are woken up every 50 ms to check again.
+/// D-Bus method calls, including the property reads of the handles returned,
+/// still block the calling thread for a round trip.
+///
+/// The futures resolve to the `Device`, `Connection` and `AccessPoint` handles
+/// of the blocking API. Like those, they stay on the thread they are created
+/// on, e.g. in a single-threaded executor. Anything that returns right away
+/// is done through `manager()` and the handles it returns.
+pub struct AsyncNetworkManager {
+    manager: NetworkManager,
+    waker: Waker,
+}
+
+impl AsyncNetworkManager {
+    pub fn new() -> Self {
+        Self::with_manager(NetworkManager::new())
+    }
+
+    pub fn with_method_timeout(timeout: u64) -> Self {
+        Self::with_manager(NetworkManager::with_method_timeout(timeout))
+    }
+
+    /// Makes the requests through `manager`, e.g. one on top of a custom
+    /// backend. Activations and device state changes are waited for up to
+    /// its method timeout.
+    pub fn with_manager(manager: NetworkManager) -> Self {
+        AsyncNetworkManager {
+            manager,
+            waker: Waker::new(),
+        }
+    }
+
+    pub fn manager(&self) -> &NetworkManager {
+        &self.manager
+    }
+
+    /// Subscribes to Network Manager events.
+    pub fn subscribe(&self) -> NetworkManagerEventStream {
+        let mut events = match self.manager.subscribe() {
+            Ok(events) => events,
+            Err(e) => return Box::new(stream::once(Err(e))),
+        };
+
+        let waker = self.waker.clone();
+
+        Box::new(stream::poll_fn(move || {
+            match events.next_timeout(Duration::from_secs(0))? {
+                Some(event) => Ok(Async::Ready(Some(event))),
+                None => {
+                    waker.wake_later(task::current());
+
+                    Ok(Async::NotReady)
+                }
+            }
+        }))
+    }
+
+    /// Activates a connection, resolving like `Connection::activate`.
+    pub fn activate(&self, connection: &Connection) -> NetworkManagerFuture<ConnectionState> {
+        match request_activation(connection) {
+            Ok(request) => self.connection_state(connection, request),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+
+    /// Deactivates a connection, resolving like `Connection::deactivate`.
+    pub fn deactivate(&self, connection: &Connection) -> NetworkManagerFuture<ConnectionState> {
+        match request_deactivation(connection) {
+            Ok(request) => self.connection_state(connection, request),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+
+    /// Connects a device, resolving like `Device::connect`.
+    pub fn connect_device(&self, device: &Device) -> NetworkManagerFuture<DeviceState> {
+        let mut watch = match request_connect(device) {
+            Ok(Some(watch)) => watch,
+            Ok(None) => return Box::new(future::ok(DeviceState::Activated)),
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let device = device.clone();
+
+        self.wait(
+            method_timeout(&self.manager),
+            move || match watch.next(Duration::from_secs(0))? {
+                Some((state, reason)) => connect_result(state, reason).map(Some),
+                None => Ok(None),
+            },
+            move || connect_result(device.get_state()?, device.get_state_reason()?),
+        )
+    }
+
+    /// Disconnects a device, resolving like `Device::disconnect`.
+    pub fn disconnect_device(&self, device: &Device) -> NetworkManagerFuture<DeviceState> {
+        let mut watch = match request_disconnect(device) {
+            Ok(Some(watch)) => watch,
+            Ok(None) => return Box::new(future::ok(DeviceState::Disconnected)),
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let device = device.clone();
+
+        self.wait(
+            method_timeout(&self.manager),
+            move || Ok(watch.next(Duration::from_secs(0))?.map(|(state, _)| state)),
+            move || device.get_state(),
+        )
+    }
+
+    /// Scans with a Wi-Fi device, resolving like `WiFiDevice::scan_and_wait`.
+    pub fn scan(
+        &self,
+        device: &Device,
+        timeout: Duration,
+    ) -> NetworkManagerFuture<Vec<AccessPoint>> {
+        let mut watch = match as_wifi_device(device).and_then(|wifi| request_scan_watch(&wifi, &[]))
+        {
+            Ok(watch) => watch,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let device = device.clone();
+
+        self.wait(
+            timeout,
+            move || {
+                if watch.next(Duration::from_secs(0))? {
+                    Ok(Some(as_wifi_device(&device)?.get_access_points()?))
+                } else {
+                    Ok(None)
+                }
+            },
+            move || {
+                bail!(ErrorKind::NetworkManager(format!(
+                    "Scan did not complete within {:?}",
+                    timeout
+                )))
+            },
+        )
+    }
+
+    /// Connects a Wi-Fi device to an access point, resolving like
+    /// `WiFiDevice::connect`.
+    pub fn connect(
+        &self,
+        device: &Device,
+        access_point: &AccessPoint,
+        credentials: &AccessPointCredentials,
+    ) -> NetworkManagerFuture<(Connection, ConnectionState)> {
+        let result = as_wifi_device(device)
+            .and_then(|wifi| request_wifi_connect(&wifi, access_point, credentials));
+
+        self.activation(result)
+    }
+
+    /// Creates and activates an access point connection on a Wi-Fi device,
+    /// resolving like `WiFiDevice::create_hotspot`.
+    pub fn create_hotspot<T>(
+        &self,
+        device: &Device,
+        ssid: &T,
+        config: &HotspotConfig,
+    ) -> NetworkManagerFuture<(Connection, ConnectionState)>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        let result =
+            as_wifi_device(device).and_then(|wifi| request_wifi_hotspot(&wifi, ssid, config));
+
+        self.activation(result)
+    }
+
+    fn activation(
+        &self,
+        result: Result<(Connection, ConnectionStateWatch)>,
+    ) -> NetworkManagerFuture<(Connection, ConnectionState)> {
+        match result {
+            Ok((connection, watch)) => Box::new(
+                self.connection_state(&connection, StateRequest::Waiting(watch))
+                    .map(move |state| (connection, state)),
+            ),
+            Err(e) => Box::new(future::err(e)),
+        }
+    }
+
+    fn connection_state(
+        &self,
+        connection: &Connection,
+        request: StateRequest,
+    ) -> NetworkManagerFuture<ConnectionState> {
+        let mut watch = match request {
+            StateRequest::Reached(state) => return Box::new(future::ok(state)),
+            StateRequest::Waiting(watch) => watch,
+        };
+
+        let connection = connection.clone();
+
+        self.wait(
+            method_timeout(&self.manager),
+            move || watch.next(Duration::from_secs(0)),
+            move || connection.get_state(),
+        )
+    }
+
+    /// Resolves to the first value of `poll`, which is called whenever the
+    /// future is polled, or to the value of `timed_out` after `timeout`.
+    fn wait<T, P, D>(&self, timeout: Duration, mut poll: P, timed_out: D) -> NetworkManagerFuture<T>
+    where
+        T: 'static,
+        P: FnMut() -> Result<Option<T>> + 'static,
+        D: FnOnce() -> Result<T> + 'static,
+    {
+        let deadline = Instant::now() + timeout;
+
+        let mut timed_out = Some(timed_out);
+
+        let waker = self.waker.clone();
+
+        Box::new(future::poll_fn(move || -> Poll<T, Error> {
+            if let Some(value) = poll()? {
+                return Ok(Async::Ready(value));
+            }
+
+            if Instant::now() >= deadline {
+                if let Some(timed_out) = timed_out.take() {
+                    return timed_out().map(Async::Ready);
+                }
+            }
+
+            waker.wake_later(task::current());
+
+            Ok(Async::NotReady)
+        }))
+    }
+}
+
+impl Default for AsyncNetworkManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wakes up pending futures after the poll interval, from a thread shared by
+/// the futures of a manager. The thread stops once they are all dropped.
+#[derive(Clone)]
+struct Waker {
+    tasks: mpsc::Sender<Task>,
+}
+
+impl Waker {
+    fn new() -> Self {
+        let (tasks, receiver) = mpsc::channel::<Task>();
+
+        thread::spawn(move || {
+            while let Ok(task) = receiver.recv() {
+                thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+
+                task.notify();
+
+                for task in receiver.try_iter() {
+                    task.notify();
+                }
+            }
+        });
+
+        Waker { tasks }
+    }
+
+    fn wake_later(&self, task: Task) {
+        // The thread only stops once all senders are dropped
+        let _ = self.tasks.send(task);
+    }
+}
+
+fn as_wifi_device(device: &Device) -> Result<WiFiDevice<'_>> {
+    device.as_wifi_device().ok_or_else(|| {
+        ErrorKind::NetworkManager(format!("{} is not a Wi-Fi device", device.interface())).into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use device::DeviceStateReason;
+    use fake::fixtures;
+    use futures::future::Either;
+
+    fn find_access_point(device: &Device, ssid: &str) -> AccessPoint {
+        device
+            .as_wifi_device()
+            .unwrap()
+            .get_access_points()
+            .unwrap()
+            .into_iter()
+            .find(|access_point| access_point.ssid().as_str().unwrap() == ssid)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_connect() {
+        let manager = AsyncNetworkManager::with_manager(fixtures::wifi().1);
+
+        let device = manager.manager().get_device_by_interface("wlan0").unwrap();
+
+        let access_point = find_access_point(&device, "office");
+
+        let wrong = AccessPointCredentials::Wpa {
+            passphrase: "wrong password".into(),
+        };
+
+        match manager.connect(&device, &access_point, &wrong).wait() {
+            Err(Error(ErrorKind::ActivationFailed(_, reason), _)) => {
+                assert_eq!(reason, DeviceStateReason::NoSecrets);
+            }
+            result => panic!("Unexpected connect result: {:?}", result),
+        }
+
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "password".into(),
+        };
+
+        let (connection, state) = manager
+            .connect(&device, &access_point, &credentials)
+            .wait()
+            .unwrap();
+
+        assert_eq!(connection.settings().id(), "office");
+        assert_eq!(state, ConnectionState::Activated);
+        assert_eq!(device.get_state().unwrap(), DeviceState::Activated);
+
+        assert_eq!(
+            manager.deactivate(&connection).wait().unwrap(),
+            ConnectionState::Deactivated
+        );
+        assert_eq!(
+            manager.activate(&connection).wait().unwrap(),
+            ConnectionState::Activated
+        );
+        assert_eq!(
+            manager.disconnect_device(&device).wait().unwrap(),
+            DeviceState::Disconnected
+        );
+        assert_eq!(
+            manager.connect_device(&device).wait().unwrap(),
+            DeviceState::Activated
+        );
+    }
+
+    #[test]
+    fn test_events_while_scanning() {
+        let (fake, manager, device_path) = fixtures::wifi();
+        let manager = AsyncNetworkManager::with_manager(manager);
+
+        let device = manager.manager().get_device_by_interface("wlan0").unwrap();
+
+        let activated = manager
+            .subscribe()
+            .filter(|event| {
+                matches!(
+                    *event,
+                    NetworkManagerEvent::DeviceStateChanged {
+                        new_state: DeviceState::Activated,
+                        ..
+                    }
+                )
+            })
+            .into_future()
+            .map_err(|(e, _)| e);
+
+        // The refused request is only repeated after a second
+        fake.refuse_scans(&device_path, 1);
+
+        let scan = manager.scan(&device, Duration::from_secs(5));
+
+        manager
+            .connect(
+                &device,
+                &find_access_point(&device, "open"),
+                &AccessPointCredentials::None,
+            )
+            .wait()
+            .unwrap();
+
+        let scan = match scan.select2(activated).wait() {
+            Ok(Either::B(((event, _), scan))) => {
+                assert!(event.is_some());
+                scan
+            }
+            Ok(Either::A(_)) => panic!("Scan completed before the events were delivered"),
+            Err(_) => panic!("Scanning or subscribing failed"),
+        };
+
+        assert_eq!(scan.wait().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,259 @@
+use errors::*;
+
+use std::collections::BTreeMap;
+
+use checkpoint::{CheckpointFlags, RollbackResult};
+use connection::ConnectionState;
+use device::{DeviceCapabilities, DeviceState, DeviceStateReason, DeviceType};
+use dhcp_config::{Dhcp4Config, Dhcp6Config};
+use events::EventStream;
+use ip_config::{Ip4Config, Ip6Config};
+use manager::{Connectivity, NetworkManagerState};
+use modem::ModemCapabilities;
+use settings::ConnectionSettings;
+use ssid::{Ssid, SsidSlice};
+use wifi::{
+    AccessPoint, AccessPointCredentials, NM80211ApFlags, NM80211ApSecurityFlags, NM80211Mode,
+};
+
+/// Keeps `Backend` from being implemented outside of the crate.
+pub(crate) mod sealed {
+    pub trait Sealed {}
+}
+
+/// The operations `NetworkManager`, `Device`, `WiFiDevice` and `Connection`
+/// are built on.
+///
+/// Objects are identified by their Network Manager object paths. The default
+/// implementation talks to Network Manager over the system D-Bus, while
+/// `FakeNetworkManager`, available with the `fake` feature, keeps everything
+/// in memory for testing.
+///
+/// The trait is sealed, so that operations can be added to it without
+/// breaking changes.
+pub trait Backend: sealed::Sealed {
+    /// Timeout in seconds for method calls and for waiting on state changes.
+    fn method_timeout(&self) -> u64;
+
+    /// Creates a new subscription to Network Manager events.
+    fn subscribe(&self) -> Result<EventStream>;
+
+    fn get_state(&self) -> Result<NetworkManagerState>;
+
+    fn check_connectivity(&self) -> Result<Connectivity>;
+
+    fn is_wireless_enabled(&self) -> Result<bool>;
+
+    fn is_networking_enabled(&self) -> Result<bool>;
+
+    /// Whether the Wi-Fi radio is enabled by a hardware switch.
+    fn is_wireless_hardware_enabled(&self) -> Result<bool>;
+
+    fn is_wwan_enabled(&self) -> Result<bool>;
+
+    /// Whether the mobile broadband radio is enabled by a hardware switch.
+    fn is_wwan_hardware_enabled(&self) -> Result<bool>;
+
+    /// Fails if networking is already enabled or disabled respectively.
+    fn set_networking_enabled(&self, enabled: bool) -> Result<()>;
+
+    fn set_wireless_enabled(&self, enabled: bool) -> Result<()>;
+
+    fn set_wwan_enabled(&self, enabled: bool) -> Result<()>;
+
+    /// Puts networking to sleep or wakes it up. Fails if already asleep or
+    /// awake respectively.
+    fn sleep(&self, sleep: bool) -> Result<()>;
+
+    /// Snapshots the given devices, or all devices if none are given, and
+    /// returns the checkpoint path. A `rollback_timeout` of 0 disables the
+    /// automatic rollback.
+    fn create_checkpoint(
+        &self,
+        devices: &[String],
+        rollback_timeout: u32,
+        flags: CheckpointFlags,
+    ) -> Result<String>;
+
+    fn destroy_checkpoint(&self, path: &str) -> Result<()>;
+
+    /// Restores and destroys the checkpoint. Returns the result per device.
+    fn rollback_checkpoint(&self, path: &str) -> Result<BTreeMap<String, RollbackResult>>;
+
+    /// Resets the rollback timeout to `add_timeout` seconds from now.
+    fn adjust_checkpoint_rollback_timeout(&self, path: &str, add_timeout: u32) -> Result<()>;
+
+    /// Lists the paths of all saved connections.
+    fn list_connections(&self) -> Result<Vec<String>>;
+
+    /// Lists the paths of all active connections.
+    fn get_active_connections(&self) -> Result<Vec<String>>;
+
+    /// Gets the saved connection path of an active connection.
+    fn get_active_connection_path(&self, path: &str) -> Option<String>;
+
+    fn get_connection_state(&self, path: &str) -> Result<ConnectionState>;
+
+    fn get_connection_settings(&self, path: &str) -> Result<ConnectionSettings>;
+
+    fn get_active_connection_devices(&self, path: &str) -> Result<Vec<String>>;
+
+    /// Gets the IPv4 configuration of an active connection, if it has one.
+    fn get_active_connection_ip4_config(&self, path: &str) -> Result<Option<Ip4Config>>;
+
+    /// Gets the IPv6 configuration of an active connection, if it has one.
+    fn get_active_connection_ip6_config(&self, path: &str) -> Result<Option<Ip6Config>>;
+
+    fn delete_connection(&self, path: &str) -> Result<()>;
+
+    /// Adds a connection profile without activating it and returns its path.
+    /// Unless `save` is set, the profile is kept in memory only.
+    fn add_connection(&self, settings: &ConnectionSettings, save: bool) -> Result<String>;
+
+    /// Replaces the settings of a connection profile. Unless `save` is set,
+    /// the changes are kept in memory only.
+    fn update_connection(
+        &self,
+        path: &str,
+        settings: &ConnectionSettings,
+        save: bool,
+    ) -> Result<()>;
+
+    /// Writes a connection profile with unsaved changes to disk.
+    fn save_connection(&self, path: &str) -> Result<()>;
+
+    /// Activates a saved connection and returns the active connection path.
+    fn activate_connection(&self, path: &str) -> Result<String>;
+
+    fn deactivate_connection(&self, path: &str) -> Result<()>;
+
+    /// Creates and activates a connection to an access point. Returns the
+    /// saved and the active connection paths.
+    fn connect_to_access_point(
+        &self,
+        device_path: &str,
+        access_point: &AccessPoint,
+        credentials: &AccessPointCredentials,
+    ) -> Result<(String, String)>;
+
+    /// Adds a connection and activates it on a device. Returns the saved and
+    /// the active connection paths.
+    fn add_and_activate_connection(
+        &self,
+        settings: &ConnectionSettings,
+        device_path: &str,
+    ) -> Result<(String, String)>;
+
+    fn get_devices(&self) -> Result<Vec<String>>;
+
+    fn get_device_by_interface(&self, interface: &str) -> Result<String>;
+
+    fn get_device_interface(&self, path: &str) -> Result<String>;
+
+    fn get_device_type(&self, path: &str) -> Result<DeviceType>;
+
+    fn get_device_state(&self, path: &str) -> Result<DeviceState>;
+
+    /// Gets the reason of the last state change of a device.
+    fn get_device_state_reason(&self, path: &str) -> Result<DeviceStateReason>;
+
+    fn get_device_hw_address(&self, path: &str) -> Result<String>;
+
+    /// Gets the permanent hardware address of a device. Returns `None` for
+    /// device types that do not have one.
+    fn get_device_perm_hw_address(&self, path: &str) -> Result<Option<String>>;
+
+    fn get_device_driver(&self, path: &str) -> Result<String>;
+
+    fn get_device_driver_version(&self, path: &str) -> Result<String>;
+
+    fn get_device_firmware_version(&self, path: &str) -> Result<String>;
+
+    fn get_device_mtu(&self, path: &str) -> Result<u32>;
+
+    fn get_device_capabilities(&self, path: &str) -> Result<DeviceCapabilities>;
+
+    fn is_device_managed(&self, path: &str) -> Result<bool>;
+
+    fn get_device_autoconnect(&self, path: &str) -> Result<bool>;
+
+    fn get_device_ip4_connectivity(&self, path: &str) -> Result<Connectivity>;
+
+    fn get_device_udi(&self, path: &str) -> Result<String>;
+
+    /// Whether a device exists, as opposed to being a placeholder for a
+    /// software device that has not been created yet.
+    fn is_device_real(&self, path: &str) -> Result<bool>;
+
+    /// Lists the paths of the saved connections that can be activated on a
+    /// device.
+    fn get_device_available_connections(&self, path: &str) -> Result<Vec<String>>;
+
+    /// Gets the IPv4 configuration of a device, if it has one.
+    fn get_device_ip4_config(&self, path: &str) -> Result<Option<Ip4Config>>;
+
+    /// Gets the IPv6 configuration of a device, if it has one.
+    fn get_device_ip6_config(&self, path: &str) -> Result<Option<Ip6Config>>;
+
+    /// Gets the DHCPv4 lease options of a device, if it has a lease.
+    fn get_device_dhcp4_config(&self, path: &str) -> Result<Option<Dhcp4Config>>;
+
+    /// Gets the DHCPv6 lease options of a device, if it has a lease.
+    fn get_device_dhcp6_config(&self, path: &str) -> Result<Option<Dhcp6Config>>;
+
+    fn connect_device(&self, path: &str) -> Result<()>;
+
+    fn disconnect_device(&self, path: &str) -> Result<()>;
+
+    fn get_wired_carrier(&self, path: &str) -> Result<bool>;
+
+    /// Gets the link speed in Mb/s.
+    fn get_wired_speed(&self, path: &str) -> Result<u32>;
+
+    fn get_wired_s390_subchannels(&self, path: &str) -> Result<Vec<String>>;
+
+    fn get_modem_capabilities(&self, path: &str) -> Result<ModemCapabilities>;
+
+    fn get_modem_current_capabilities(&self, path: &str) -> Result<ModemCapabilities>;
+
+    fn get_modem_device_id(&self, path: &str) -> Result<String>;
+
+    fn get_modem_operator_code(&self, path: &str) -> Result<String>;
+
+    /// Requests a scan. Probe requests are sent for `ssids`, which is needed
+    /// for finding networks that do not broadcast their SSID.
+    ///
+    /// Fails with `ErrorKind::ScanNotAllowed` if a scan is already running or
+    /// the previous one finished too recently.
+    fn request_access_point_scan(&self, path: &str, ssids: &[&SsidSlice]) -> Result<()>;
+
+    /// Gets the time of the last completed scan in milliseconds since boot,
+    /// or -1 if the device has never scanned.
+    fn get_device_last_scan(&self, path: &str) -> Result<i64>;
+
+    fn get_device_access_points(&self, path: &str) -> Result<Vec<String>>;
+
+    fn get_access_point_ssid(&self, path: &str) -> Option<Ssid>;
+
+    fn get_access_point_strength(&self, path: &str) -> Result<u32>;
+
+    fn get_access_point_flags(&self, path: &str) -> Result<NM80211ApFlags>;
+
+    fn get_access_point_wpa_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags>;
+
+    fn get_access_point_rsn_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags>;
+
+    fn get_access_point_bssid(&self, path: &str) -> Result<String>;
+
+    /// Gets the radio channel frequency of an access point in MHz.
+    fn get_access_point_frequency(&self, path: &str) -> Result<u32>;
+
+    /// Gets the maximum bitrate of an access point in kbit/s.
+    fn get_access_point_max_bitrate(&self, path: &str) -> Result<u32>;
+
+    fn get_access_point_mode(&self, path: &str) -> Result<NM80211Mode>;
+
+    /// Gets when an access point was last found in a scan, in seconds since
+    /// boot, or -1 if it has never been found.
+    fn get_access_point_last_seen(&self, path: &str) -> Result<i32>;
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+use backend::Backend;
+use errors::*;
+
+bitflags! {
+    pub struct CheckpointFlags: u32 {
+        const CHECKPOINT_NONE                   = 0x0000_0000;
+        // destroy all existing checkpoints before creating the new one
+        const CHECKPOINT_DESTROY_ALL            = 0x0000_0001;
+        // delete connections added after the checkpoint on rollback
+        const CHECKPOINT_DELETE_NEW_CONNECTIONS = 0x0000_0002;
+        // disconnect devices that appeared after the checkpoint on rollback
+        const CHECKPOINT_DISCONNECT_NEW_DEVICES = 0x0000_0004;
+        // allow creating checkpoints covering devices of existing ones
+        const CHECKPOINT_ALLOW_OVERLAPPING      = 0x0000_0008;
+    }
+}
+
+/// The outcome of rolling back a single device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RollbackResult {
+    Ok,
+    NoDevice,
+    DeviceUnmanaged,
+    Failed,
+}
+
+impl From<u32> for RollbackResult {
+    fn from(result: u32) -> Self {
+        match result {
+            0 => RollbackResult::Ok,
+            1 => RollbackResult::NoDevice,
+            2 => RollbackResult::DeviceUnmanaged,
+            3 => RollbackResult::Failed,
+            _ => {
+                warn!("Undefined rollback result: {}", result);
+                RollbackResult::Failed
+            }
+        }
+    }
+}
+
+/// A snapshot of the device and connection configuration, created with
+/// `NetworkManager::create_checkpoint`.
+///
+/// Network Manager rolls back to the snapshot by itself once the rollback
+/// timeout expires, unless the checkpoint is destroyed before.
+pub struct Checkpoint {
+    backend: Rc<dyn Backend>,
+    path: String,
+}
+
+impl Checkpoint {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Restores the snapshot and destroys the checkpoint. Returns the result
+    /// for each covered device, by device path.
+    pub fn rollback(self) -> Result<BTreeMap<String, RollbackResult>> {
+        self.backend.rollback_checkpoint(&self.path)
+    }
+
+    /// Keeps the current configuration and destroys the checkpoint.
+    pub fn destroy(self) -> Result<()> {
+        self.backend.destroy_checkpoint(&self.path)
+    }
+
+    /// Resets the rollback timeout to `timeout` from now. A zero timeout
+    /// disables the automatic rollback.
+    pub fn adjust_rollback_timeout(&self, timeout: Duration) -> Result<()> {
+        self.backend
+            .adjust_checkpoint_rollback_timeout(&self.path, timeout_secs(timeout))
+    }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Checkpoint {{ path: {:?} }}", self.path)
+    }
+}
+
+/// Rolls a checkpoint back unless the changes made while holding the guard
+/// are committed.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use network_manager::{CheckpointFlags, CheckpointGuard, Connectivity, NetworkManager};
+///
+/// let manager = NetworkManager::new();
+/// let checkpoint = manager
+///     .create_checkpoint(&[], Duration::from_secs(60), CheckpointFlags::CHECKPOINT_NONE)
+///     .unwrap();
+/// let guard = CheckpointGuard::new(checkpoint);
+///
+/// // Apply the network changes here
+///
+/// guard
+///     .commit_if(|| manager.get_connectivity().ok() == Some(Connectivity::Full))
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct CheckpointGuard {
+    checkpoint: Option<Checkpoint>,
+}
+
+impl CheckpointGuard {
+    pub fn new(checkpoint: Checkpoint) -> Self {
+        CheckpointGuard {
+            checkpoint: Some(checkpoint),
+        }
+    }
+
+    /// Keeps the changes if `check` passes and rolls them back otherwise.
+    /// Returns whether the changes were kept.
+    ///
+    /// Fails if the rollback timeout expired before `check` passed, as the
+    /// changes are rolled back by then.
+    pub fn commit_if<F>(mut self, check: F) -> Result<bool>
+    where
+        F: FnOnce() -> bool,
+    {
+        let checkpoint = self.take();
+
+        if check() {
+            checkpoint.destroy()?;
+
+            Ok(true)
+        } else {
+            checkpoint.rollback()?;
+
+            Ok(false)
+        }
+    }
+
+    /// Rolls the changes back right away.
+    pub fn rollback(mut self) -> Result<BTreeMap<String, RollbackResult>> {
+        self.take().rollback()
+    }
+
+    fn take(&mut self) -> Checkpoint {
+        self.checkpoint
+            .take()
+            .expect("Checkpoint taken from guard twice")
+    }
+}
+
+impl Drop for CheckpointGuard {
+    fn drop(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.take() {
+            if let Err(error) = checkpoint.rollback() {
+                error!("Checkpoint rollback failed: {}", error);
+            }
+        }
+    }
+}
+
+pub fn create_checkpoint(
+    backend: &Rc<dyn Backend>,
+    devices: &[String],
+    rollback_timeout: Duration,
+    flags: CheckpointFlags,
+) -> Result<Checkpoint> {
+    let path = backend.create_checkpoint(devices, timeout_secs(rollback_timeout), flags)?;
+
+    Ok(Checkpoint {
+        backend: Rc::clone(backend),
+        path,
+    })
+}
+
+/// Rounds up to whole seconds, so that a short timeout does not become zero
+/// and disable the rollback.
+fn timeout_secs(timeout: Duration) -> u32 {
+    let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+
+    secs.min(u64::from(u32::MAX)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use connection::ConnectionState;
+    use fake::fixtures;
+    use manager::{Connectivity, NetworkManager};
+    use wifi::{AccessPointCredentials, Security};
+
+    const ROLLBACK_TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn setup() -> NetworkManager {
+        let (fake, manager, device) = fixtures::wifi();
+
+        fake.add_access_point(&device, "home", 60, Security::WPA2, Some("home password"));
+
+        manager
+    }
+
+    fn connect(manager: &NetworkManager, ssid: &str, passphrase: &str) -> Result<ConnectionState> {
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+
+        let access_point = wifi_device
+            .get_access_points()
+            .unwrap()
+            .into_iter()
+            .find(|access_point| access_point.ssid().as_str().unwrap() == ssid)
+            .unwrap();
+
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: passphrase.into(),
+        };
+
+        wifi_device
+            .connect(&access_point, &credentials)
+            .map(|(_, state)| state)
+    }
+
+    fn active_ids(manager: &NetworkManager) -> Vec<String> {
+        manager
+            .get_active_connections()
+            .unwrap()
+            .iter()
+            .map(|connection| connection.settings().id().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rollback_on_failed_check() {
+        let manager = setup();
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+
+        connect(&manager, "home", "home password").unwrap();
+
+        let checkpoint = manager
+            .create_checkpoint(
+                &[&device],
+                ROLLBACK_TIMEOUT,
+                CheckpointFlags::CHECKPOINT_DELETE_NEW_CONNECTIONS,
+            )
+            .unwrap();
+        let guard = CheckpointGuard::new(checkpoint);
+
+        assert!(connect(&manager, "office", "wrong password").is_err());
+
+        let committed = guard
+            .commit_if(|| manager.get_connectivity().unwrap() == Connectivity::Full)
+            .unwrap();
+
+        assert!(!committed);
+        assert_eq!(active_ids(&manager), vec!["home".to_string()]);
+        assert_eq!(manager.get_connections().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_commit_and_drop() {
+        let manager = setup();
+
+        let checkpoint = manager
+            .create_checkpoint(&[], ROLLBACK_TIMEOUT, CheckpointFlags::CHECKPOINT_NONE)
+            .unwrap();
+        let guard = CheckpointGuard::new(checkpoint);
+
+        connect(&manager, "office", "password").unwrap();
+
+        assert!(guard
+            .commit_if(|| manager.get_connectivity().unwrap() == Connectivity::Full)
+            .unwrap());
+        assert_eq!(active_ids(&manager), vec!["office".to_string()]);
+
+        {
+            let checkpoint = manager
+                .create_checkpoint(&[], ROLLBACK_TIMEOUT, CheckpointFlags::CHECKPOINT_NONE)
+                .unwrap();
+            let _guard = CheckpointGuard::new(checkpoint);
+
+            assert!(manager
+                .create_checkpoint(&[], ROLLBACK_TIMEOUT, CheckpointFlags::CHECKPOINT_NONE)
+                .is_err());
+
+            connect(&manager, "home", "home password").unwrap();
+        }
+
+        assert_eq!(active_ids(&manager), vec!["office".to_string()]);
+    }
+}
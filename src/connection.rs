@@ -1,27 +1,32 @@
 use std::fmt;
-use std::net::Ipv4Addr;
+use std::path::Path;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use dbus_nm::DBusNetworkManager;
+use backend::Backend;
 use errors::*;
 
-use device::{get_active_connection_devices, Device};
-use ssid::{AsSsidSlice, Ssid};
-use wifi::{AccessPoint, AccessPointCredentials};
+use device::{get_active_connection_devices, Device, DeviceState, DeviceStateReason};
+use events::{EventStream, NetworkManagerEvent};
+use hotspot::{lease_file_path, read_hotspot_clients, HotspotClient, HotspotConfig};
+use ip_config::{Ip4Config, Ip6Config};
+use settings::ConnectionSettings;
+use ssid::AsSsidSlice;
+use wifi::{hidden_network_settings, AccessPoint, AccessPointCredentials};
 
 #[derive(Clone)]
 pub struct Connection {
-    dbus_manager: Rc<DBusNetworkManager>,
+    backend: Rc<dyn Backend>,
     path: String,
     settings: ConnectionSettings,
 }
 
 impl Connection {
-    fn init(dbus_manager: &Rc<DBusNetworkManager>, path: &str) -> Result<Self> {
-        let settings = dbus_manager.get_connection_settings(path)?;
+    fn init(backend: &Rc<dyn Backend>, path: &str) -> Result<Self> {
+        let settings = backend.get_connection_settings(path)?;
 
         Ok(Connection {
-            dbus_manager: Rc::clone(dbus_manager),
+            backend: Rc::clone(backend),
             path: path.to_string(),
             settings,
         })
@@ -32,10 +37,10 @@ impl Connection {
     }
 
     pub fn get_state(&self) -> Result<ConnectionState> {
-        let active_path_option = get_connection_active_path(&self.dbus_manager, &self.path)?;
+        let active_path_option = get_connection_active_path(&*self.backend, &self.path)?;
 
         if let Some(active_path) = active_path_option {
-            let state = self.dbus_manager.get_connection_state(&active_path)?;
+            let state = self.backend.get_connection_state(&active_path)?;
 
             Ok(state)
         } else {
@@ -43,78 +48,133 @@ impl Connection {
         }
     }
 
+    /// Gets the IPv4 configuration of the connection. Returns `None` unless
+    /// the connection is active.
+    pub fn ip4_config(&self) -> Result<Option<Ip4Config>> {
+        match get_connection_active_path(&*self.backend, &self.path)? {
+            Some(active_path) => self.backend.get_active_connection_ip4_config(&active_path),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the IPv6 configuration of the connection. Returns `None` unless
+    /// the connection is active.
+    pub fn ip6_config(&self) -> Result<Option<Ip6Config>> {
+        match get_connection_active_path(&*self.backend, &self.path)? {
+            Some(active_path) => self.backend.get_active_connection_ip6_config(&active_path),
+            None => Ok(None),
+        }
+    }
+
     pub fn delete(&self) -> Result<()> {
-        self.dbus_manager.delete_connection(&self.path)
+        self.backend.delete_connection(&self.path)
+    }
+
+    /// Replaces the settings of the connection profile and writes it to disk.
+    ///
+    /// Secrets are not part of `settings()`, so they have to be set again when
+    /// updating a profile that has any.
+    pub fn update(&mut self, settings: &ConnectionSettings) -> Result<()> {
+        self.backend.update_connection(&self.path, settings, true)?;
+
+        self.settings = self.backend.get_connection_settings(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Replaces the settings of the connection profile without writing it to
+    /// disk. The changes are lost on restart unless `save` is called.
+    pub fn update_unsaved(&mut self, settings: &ConnectionSettings) -> Result<()> {
+        self.backend
+            .update_connection(&self.path, settings, false)?;
+
+        self.settings = self.backend.get_connection_settings(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Writes a profile with unsaved changes to disk.
+    pub fn save(&self) -> Result<()> {
+        self.backend.save_connection(&self.path)
     }
 
     /// Activate a Network Manager connection.
+    ///
+    /// Fails with `ErrorKind::ActivationFailed` if the activation attempt
+    /// ends with the connection deactivated, with the reason the device failed
+    /// for, e.g. `DeviceStateReason::NoSecrets` for a wrong passphrase.
     pub fn activate(&self) -> Result<ConnectionState> {
-        let state = self.get_state()?;
-
-        match state {
-            ConnectionState::Activated => Ok(ConnectionState::Activated),
-            ConnectionState::Activating => wait(
-                self,
-                &ConnectionState::Activated,
-                self.dbus_manager.method_timeout(),
-            ),
-            ConnectionState::Unknown => bail!(ErrorKind::NetworkManager(
-                "Unable to get connection state".into()
-            )),
-            _ => {
-                self.dbus_manager.activate_connection(&self.path)?;
+        self.activate_with_timeout(method_timeout(&*self.backend))
+    }
 
-                wait(
-                    self,
-                    &ConnectionState::Activated,
-                    self.dbus_manager.method_timeout(),
-                )
-            }
+    /// Like `activate`, but waits up to `timeout` for the activation to
+    /// complete instead of the method timeout. A zero timeout does not wait.
+    pub fn activate_with_timeout(&self, timeout: Duration) -> Result<ConnectionState> {
+        match request_activation(self)? {
+            StateRequest::Reached(state) => Ok(state),
+            StateRequest::Waiting(watch) => wait(self, watch, timeout),
         }
     }
 
     /// Deactivates a Network Manager connection.
     pub fn deactivate(&self) -> Result<ConnectionState> {
-        let state = self.get_state()?;
+        self.deactivate_with_timeout(method_timeout(&*self.backend))
+    }
 
-        match state {
-            ConnectionState::Deactivated => Ok(ConnectionState::Deactivated),
-            ConnectionState::Deactivating => wait(
-                self,
-                &ConnectionState::Deactivated,
-                self.dbus_manager.method_timeout(),
-            ),
-            ConnectionState::Unknown => bail!(ErrorKind::NetworkManager(
-                "Unable to get connection state".into()
-            )),
-            _ => {
-                let active_path_option =
-                    get_connection_active_path(&self.dbus_manager, &self.path)?;
-
-                if let Some(active_path) = active_path_option {
-                    self.dbus_manager.deactivate_connection(&active_path)?;
-
-                    wait(
-                        self,
-                        &ConnectionState::Deactivated,
-                        self.dbus_manager.method_timeout(),
-                    )
-                } else {
-                    Ok(ConnectionState::Deactivated)
-                }
-            }
+    /// Like `deactivate`, but waits up to `timeout` for the deactivation to
+    /// complete instead of the method timeout. A zero timeout does not wait.
+    pub fn deactivate_with_timeout(&self, timeout: Duration) -> Result<ConnectionState> {
+        match request_deactivation(self)? {
+            StateRequest::Reached(state) => Ok(state),
+            StateRequest::Waiting(watch) => wait(self, watch, timeout),
         }
     }
 
     pub fn get_devices(&self) -> Result<Vec<Device>> {
-        let active_path_option = get_connection_active_path(&self.dbus_manager, &self.path)?;
+        let active_path_option = get_connection_active_path(&*self.backend, &self.path)?;
 
         if let Some(active_path) = active_path_option {
-            get_active_connection_devices(&self.dbus_manager, &active_path)
+            get_active_connection_devices(&self.backend, &active_path)
         } else {
             Ok(vec![])
         }
     }
+
+    /// Lists the clients of an access point connection, as found in the
+    /// dnsmasq lease file Network Manager writes for shared connections.
+    /// Returns no clients unless the connection is active.
+    pub fn hotspot_clients(&self) -> Result<Vec<HotspotClient>> {
+        match self.hotspot_interface()? {
+            Some(interface) => read_hotspot_clients(lease_file_path(&interface)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Like `hotspot_clients`, but reads the clients from `lease_file`.
+    pub fn hotspot_clients_from<P>(&self, lease_file: P) -> Result<Vec<HotspotClient>>
+    where
+        P: AsRef<Path>,
+    {
+        match self.hotspot_interface()? {
+            Some(_) => read_hotspot_clients(lease_file),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The interface an active access point connection runs on.
+    fn hotspot_interface(&self) -> Result<Option<String>> {
+        if self.settings.mode() != Some("ap") {
+            bail!(ErrorKind::NetworkManager(format!(
+                "{} is not a hotspot connection",
+                self.settings.id()
+            )));
+        }
+
+        Ok(self
+            .get_devices()?
+            .first()
+            .map(|device| device.interface().to_string()))
+    }
 }
 
 impl Ord for Connection {
@@ -147,7 +207,7 @@ impl fmt::Debug for Connection {
     }
 }
 
-impl<'a> From<&'a Connection> for i32 {
+impl From<&Connection> for i32 {
     fn from(val: &Connection) -> i32 {
         val.clone()
             .path
@@ -159,15 +219,6 @@ impl<'a> From<&'a Connection> for i32 {
     }
 }
 
-#[derive(Default, Debug, Clone, Eq, PartialEq)]
-pub struct ConnectionSettings {
-    pub kind: String, // `type` is a reserved word, so we are using `kind` instead
-    pub id: String,
-    pub uuid: String,
-    pub ssid: Ssid,
-    pub mode: String,
-}
-
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ConnectionState {
     Unknown = 0,
@@ -193,13 +244,60 @@ impl From<i64> for ConnectionState {
     }
 }
 
-pub fn get_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Connection>> {
-    let paths = dbus_manager.list_connections()?;
+/// The reason of an active connection state change, reported by the
+/// `StateChanged` signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActiveConnectionStateReason {
+    Unknown,
+    None,
+    UserDisconnected,
+    DeviceDisconnected,
+    ServiceStopped,
+    IpConfigInvalid,
+    ConnectTimeout,
+    ServiceStartTimeout,
+    ServiceStartFailed,
+    NoSecrets,
+    LoginFailed,
+    ConnectionRemoved,
+    DependencyFailed,
+    DeviceRealizeFailed,
+    DeviceRemoved,
+}
+
+impl From<u32> for ActiveConnectionStateReason {
+    fn from(reason: u32) -> Self {
+        match reason {
+            0 => ActiveConnectionStateReason::Unknown,
+            1 => ActiveConnectionStateReason::None,
+            2 => ActiveConnectionStateReason::UserDisconnected,
+            3 => ActiveConnectionStateReason::DeviceDisconnected,
+            4 => ActiveConnectionStateReason::ServiceStopped,
+            5 => ActiveConnectionStateReason::IpConfigInvalid,
+            6 => ActiveConnectionStateReason::ConnectTimeout,
+            7 => ActiveConnectionStateReason::ServiceStartTimeout,
+            8 => ActiveConnectionStateReason::ServiceStartFailed,
+            9 => ActiveConnectionStateReason::NoSecrets,
+            10 => ActiveConnectionStateReason::LoginFailed,
+            11 => ActiveConnectionStateReason::ConnectionRemoved,
+            12 => ActiveConnectionStateReason::DependencyFailed,
+            13 => ActiveConnectionStateReason::DeviceRealizeFailed,
+            14 => ActiveConnectionStateReason::DeviceRemoved,
+            _ => {
+                warn!("Undefined active connection state reason: {}", reason);
+                ActiveConnectionStateReason::Unknown
+            }
+        }
+    }
+}
+
+pub fn get_connections(backend: &Rc<dyn Backend>) -> Result<Vec<Connection>> {
+    let paths = backend.list_connections()?;
 
     let mut connections = Vec::with_capacity(paths.len());
 
     for path in &paths {
-        connections.push(Connection::init(dbus_manager, path)?)
+        connections.push(Connection::init(backend, path)?)
     }
 
     connections.sort();
@@ -207,14 +305,14 @@ pub fn get_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Conn
     Ok(connections)
 }
 
-pub fn get_active_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Connection>> {
-    let active_paths = dbus_manager.get_active_connections()?;
+pub fn get_active_connections(backend: &Rc<dyn Backend>) -> Result<Vec<Connection>> {
+    let active_paths = backend.get_active_connections()?;
 
     let mut connections = Vec::with_capacity(active_paths.len());
 
     for active_path in active_paths {
-        if let Some(path) = dbus_manager.get_active_connection_path(&active_path) {
-            connections.push(Connection::init(dbus_manager, &path)?)
+        if let Some(path) = backend.get_active_connection_path(&active_path) {
+            connections.push(Connection::init(backend, &path)?)
         }
     }
 
@@ -223,57 +321,278 @@ pub fn get_active_connections(dbus_manager: &Rc<DBusNetworkManager>) -> Result<V
     Ok(connections)
 }
 
+pub fn get_device_available_connections(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+) -> Result<Vec<Connection>> {
+    let paths = backend.get_device_available_connections(device_path)?;
+
+    let mut connections = Vec::with_capacity(paths.len());
+
+    for path in &paths {
+        connections.push(Connection::init(backend, path)?)
+    }
+
+    connections.sort();
+
+    Ok(connections)
+}
+
+pub fn add_connection(
+    backend: &Rc<dyn Backend>,
+    settings: &ConnectionSettings,
+    save: bool,
+) -> Result<Connection> {
+    let path = backend.add_connection(settings, save)?;
+
+    Connection::init(backend, &path)
+}
+
 pub fn connect_to_access_point(
-    dbus_manager: &Rc<DBusNetworkManager>,
+    backend: &Rc<dyn Backend>,
     device_path: &str,
     access_point: &AccessPoint,
     credentials: &AccessPointCredentials,
 ) -> Result<(Connection, ConnectionState)> {
-    let (path, _) = dbus_manager.connect_to_access_point(device_path, access_point, credentials)?;
+    let (connection, watch) =
+        request_access_point_connection(backend, device_path, access_point, credentials)?;
 
-    let connection = Connection::init(dbus_manager, &path)?;
+    wait_activated(connection, watch)
+}
 
-    let state = wait(
-        &connection,
-        &ConnectionState::Activated,
-        dbus_manager.method_timeout(),
-    )?;
+/// Like `connect_to_access_point`, but returns as soon as the activation is
+/// started.
+pub fn request_access_point_connection(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+    access_point: &AccessPoint,
+    credentials: &AccessPointCredentials,
+) -> Result<(Connection, ConnectionStateWatch)> {
+    let events = backend.subscribe()?;
 
-    Ok((connection, state))
+    let (path, active_path) =
+        backend.connect_to_access_point(device_path, access_point, credentials)?;
+
+    let connection = Connection::init(backend, &path)?;
+
+    let watch = ConnectionStateWatch::new(
+        events,
+        &active_path,
+        vec![device_path.to_string()],
+        ConnectionState::Activated,
+    );
+
+    Ok((connection, watch))
+}
+
+pub fn connect_to_hidden_network<S>(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+    interface: &str,
+    ssid: &S,
+    credentials: &AccessPointCredentials,
+) -> Result<(Connection, ConnectionState)>
+where
+    S: AsSsidSlice + ?Sized,
+{
+    let (connection, watch) =
+        request_hidden_network_connection(backend, device_path, interface, ssid, credentials)?;
+
+    wait_activated(connection, watch)
+}
+
+/// Like `connect_to_hidden_network`, but returns as soon as the activation
+/// is started.
+pub fn request_hidden_network_connection<S>(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+    interface: &str,
+    ssid: &S,
+    credentials: &AccessPointCredentials,
+) -> Result<(Connection, ConnectionStateWatch)>
+where
+    S: AsSsidSlice + ?Sized,
+{
+    let ssid = ssid.as_ssid_slice()?;
+
+    let settings = hidden_network_settings(ssid, interface, credentials)?;
+
+    let connection = add_connection(backend, &settings, true)?;
+
+    // The activation probes for the network as well, so a failed scan
+    // request, e.g. while another scan is running, is not fatal
+    if let Err(error) = backend.request_access_point_scan(device_path, &[ssid]) {
+        warn!("Scanning for hidden network failed: {}", error);
+    }
+
+    let events = backend.subscribe()?;
+
+    let active_path = backend.activate_connection(&connection.path)?;
+
+    let watch = ConnectionStateWatch::new(
+        events,
+        &active_path,
+        vec![device_path.to_string()],
+        ConnectionState::Activated,
+    );
+
+    Ok((connection, watch))
 }
 
 pub fn create_hotspot<S>(
-    dbus_manager: &Rc<DBusNetworkManager>,
+    backend: &Rc<dyn Backend>,
     device_path: &str,
     interface: &str,
     ssid: &S,
-    password: Option<&str>,
-    address: Option<Ipv4Addr>,
+    config: &HotspotConfig,
 ) -> Result<(Connection, ConnectionState)>
 where
     S: AsSsidSlice + ?Sized,
 {
-    let (path, _) = dbus_manager.create_hotspot(device_path, interface, ssid, password, address)?;
+    let (connection, watch) = request_hotspot(backend, device_path, interface, ssid, config)?;
 
-    let connection = Connection::init(dbus_manager, &path)?;
+    wait_activated(connection, watch)
+}
 
-    let state = wait(
-        &connection,
-        &ConnectionState::Activated,
-        dbus_manager.method_timeout(),
-    )?;
+/// Like `create_hotspot`, but returns as soon as the activation is started.
+pub fn request_hotspot<S>(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+    interface: &str,
+    ssid: &S,
+    config: &HotspotConfig,
+) -> Result<(Connection, ConnectionStateWatch)>
+where
+    S: AsSsidSlice + ?Sized,
+{
+    let settings = config.to_settings(ssid.as_ssid_slice()?, interface)?;
 
-    Ok((connection, state))
+    request_add_and_activate_connection(backend, device_path, &settings)
+}
+
+/// Adds a connection profile and activates it on the device, waiting for the
+/// activation to complete.
+pub fn add_and_activate_connection(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+    settings: &ConnectionSettings,
+) -> Result<(Connection, ConnectionState)> {
+    let (connection, watch) = request_add_and_activate_connection(backend, device_path, settings)?;
+
+    wait_activated(connection, watch)
+}
+
+/// Like `add_and_activate_connection`, but returns as soon as the activation
+/// is started.
+pub fn request_add_and_activate_connection(
+    backend: &Rc<dyn Backend>,
+    device_path: &str,
+    settings: &ConnectionSettings,
+) -> Result<(Connection, ConnectionStateWatch)> {
+    let events = backend.subscribe()?;
+
+    let (path, active_path) = backend.add_and_activate_connection(settings, device_path)?;
+
+    let connection = Connection::init(backend, &path)?;
+
+    let watch = ConnectionStateWatch::new(
+        events,
+        &active_path,
+        vec![device_path.to_string()],
+        ConnectionState::Activated,
+    );
+
+    Ok((connection, watch))
+}
+
+/// The outcome of requesting a connection to be activated or deactivated.
+pub enum StateRequest {
+    /// Nothing to wait for, e.g. the connection was already activated.
+    Reached(ConnectionState),
+    /// The state change is in progress.
+    Waiting(ConnectionStateWatch),
+}
+
+/// Starts activating `connection`, unless it is already active or being
+/// activated.
+pub fn request_activation(connection: &Connection) -> Result<StateRequest> {
+    let backend = &connection.backend;
+
+    match connection.get_state()? {
+        ConnectionState::Activated => Ok(StateRequest::Reached(ConnectionState::Activated)),
+        ConnectionState::Activating => {
+            let events = backend.subscribe()?;
+
+            match get_connection_active_path(&**backend, &connection.path)? {
+                Some(active_path) => Ok(StateRequest::Waiting(ConnectionStateWatch::new(
+                    events,
+                    &active_path,
+                    active_devices(&**backend, &active_path),
+                    ConnectionState::Activated,
+                ))),
+                None => Ok(StateRequest::Reached(connection.get_state()?)),
+            }
+        }
+        ConnectionState::Unknown => bail!(ErrorKind::NetworkManager(
+            "Unable to get connection state".into()
+        )),
+        _ => {
+            let events = backend.subscribe()?;
+
+            let active_path = backend.activate_connection(&connection.path)?;
+
+            Ok(StateRequest::Waiting(ConnectionStateWatch::new(
+                events,
+                &active_path,
+                active_devices(&**backend, &active_path),
+                ConnectionState::Activated,
+            )))
+        }
+    }
+}
+
+/// Starts deactivating `connection`, unless it is already inactive.
+pub fn request_deactivation(connection: &Connection) -> Result<StateRequest> {
+    let backend = &connection.backend;
+
+    let state = connection.get_state()?;
+
+    match state {
+        ConnectionState::Deactivated => Ok(StateRequest::Reached(ConnectionState::Deactivated)),
+        ConnectionState::Unknown => bail!(ErrorKind::NetworkManager(
+            "Unable to get connection state".into()
+        )),
+        _ => {
+            let events = backend.subscribe()?;
+
+            let active_path_option = get_connection_active_path(&**backend, &connection.path)?;
+
+            if let Some(active_path) = active_path_option {
+                if state != ConnectionState::Deactivating {
+                    backend.deactivate_connection(&active_path)?;
+                }
+
+                Ok(StateRequest::Waiting(ConnectionStateWatch::new(
+                    events,
+                    &active_path,
+                    vec![],
+                    ConnectionState::Deactivated,
+                )))
+            } else {
+                Ok(StateRequest::Reached(ConnectionState::Deactivated))
+            }
+        }
+    }
 }
 
 fn get_connection_active_path(
-    dbus_manager: &DBusNetworkManager,
+    backend: &dyn Backend,
     connection_path: &str,
 ) -> Result<Option<String>> {
-    let active_paths = dbus_manager.get_active_connections()?;
+    let active_paths = backend.get_active_connections()?;
 
     for active_path in active_paths {
-        if let Some(settings_path) = dbus_manager.get_active_connection_path(&active_path) {
+        if let Some(settings_path) = backend.get_active_connection_path(&active_path) {
             if connection_path == settings_path {
                 return Ok(Some(active_path));
             }
@@ -283,45 +602,231 @@ fn get_connection_active_path(
     Ok(None)
 }
 
+/// The paths of the devices of the active connection at `active_path`.
+/// Empty if the connection is not active anymore.
+fn active_devices(backend: &dyn Backend, active_path: &str) -> Vec<String> {
+    backend
+        .get_active_connection_devices(active_path)
+        .unwrap_or_default()
+}
+
+fn method_timeout(backend: &dyn Backend) -> Duration {
+    Duration::from_secs(backend.method_timeout())
+}
+
+/// Follows the state change signals of an active connection until it reaches
+/// its target state or gets deactivated.
+pub struct ConnectionStateWatch {
+    events: EventStream,
+    active_path: String,
+    devices: Vec<String>,
+    target_state: ConnectionState,
+    last_reason: ActiveConnectionStateReason,
+    device_reason: DeviceStateReason,
+}
+
+impl ConnectionStateWatch {
+    /// Watches the active connection at `active_path`. `devices` are the
+    /// paths of the devices the connection is activated on.
+    fn new(
+        events: EventStream,
+        active_path: &str,
+        devices: Vec<String>,
+        target_state: ConnectionState,
+    ) -> Self {
+        ConnectionStateWatch {
+            events,
+            active_path: active_path.to_string(),
+            devices,
+            target_state,
+            last_reason: ActiveConnectionStateReason::Unknown,
+            device_reason: DeviceStateReason::None,
+        }
+    }
+
+    /// Handles the events arriving within `timeout`. Returns the state of the
+    /// connection once it reaches the target state or gets deactivated, and
+    /// `None` if it is still changing after `timeout`. A zero timeout only
+    /// handles the events already received.
+    ///
+    /// Fails with `ErrorKind::ActivationFailed` if the connection gets
+    /// deactivated while waiting for it to be activated. The device state
+    /// reason of the error is taken from the failure of one of the devices.
+    pub fn next(&mut self, timeout: Duration) -> Result<Option<ConnectionState>> {
+        let start = Instant::now();
+
+        loop {
+            let remaining = timeout
+                .checked_sub(start.elapsed())
+                .unwrap_or_else(|| Duration::from_secs(0));
+
+            let event = match self.events.next_timeout(remaining)? {
+                Some(event) => event,
+                None => return Ok(None),
+            };
+
+            if let Some(state) = self.handle(&event)? {
+                return Ok(Some(state));
+            }
+        }
+    }
+
+    fn handle(&mut self, event: &NetworkManagerEvent) -> Result<Option<ConnectionState>> {
+        let (state, reason) = match *event {
+            NetworkManagerEvent::ActiveConnectionStateChanged {
+                ref path,
+                ref state,
+                reason,
+            } if *path == self.active_path => (state.clone(), reason),
+            NetworkManagerEvent::ActiveConnectionRemoved(ref path) if *path == self.active_path => {
+                (ConnectionState::Deactivated, self.last_reason)
+            }
+            NetworkManagerEvent::DeviceStateChanged {
+                ref path,
+                new_state: DeviceState::Failed,
+                reason,
+                ..
+            } if self.devices.contains(path) => {
+                self.device_reason = reason;
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+
+        self.last_reason = reason;
+
+        if state == self.target_state {
+            debug!("Connection target state reached: {:?}", state);
+
+            return Ok(Some(state));
+        } else if state == ConnectionState::Deactivated {
+            debug!(
+                "Connection deactivated while waiting for state ({:?}): {:?}",
+                self.target_state, reason
+            );
+
+            if self.target_state == ConnectionState::Activated {
+                bail!(ErrorKind::ActivationFailed(reason, self.device_reason));
+            }
+
+            return Ok(Some(state));
+        }
+
+        debug!(
+            "Still waiting for connection state ({:?}): {:?}",
+            self.target_state, state
+        );
+
+        Ok(None)
+    }
+}
+
+/// Waits for a connection just requested on a device to be activated.
+fn wait_activated(
+    connection: Connection,
+    watch: ConnectionStateWatch,
+) -> Result<(Connection, ConnectionState)> {
+    let state = wait(&connection, watch, method_timeout(&*connection.backend))?;
+
+    Ok((connection, state))
+}
+
+/// Waits up to `timeout` for `watch` to end, returning the current state of
+/// the connection if it is still changing after that.
 fn wait(
     connection: &Connection,
-    target_state: &ConnectionState,
-    timeout: u64,
+    mut watch: ConnectionStateWatch,
+    timeout: Duration,
 ) -> Result<ConnectionState> {
-    if timeout == 0 {
+    if timeout == Duration::from_secs(0) {
         return connection.get_state();
     }
 
-    debug!("Waiting for connection state: {:?}", target_state);
+    debug!("Waiting for connection state: {:?}", watch.target_state);
 
-    let mut total_time = 0;
+    let start = Instant::now();
 
-    loop {
-        ::std::thread::sleep(::std::time::Duration::from_secs(1));
+    let state = connection.get_state()?;
 
-        let state = connection.get_state()?;
+    if state == watch.target_state {
+        debug!("Connection target state already reached: {:?}", state);
 
-        total_time += 1;
+        return Ok(state);
+    }
 
-        if state == *target_state {
-            debug!(
-                "Connection target state reached: {:?} / {}s elapsed",
-                state, total_time
-            );
+    match watch.next(timeout)? {
+        Some(state) => Ok(state),
+        None => {
+            let state = connection.get_state()?;
 
-            return Ok(state);
-        } else if total_time >= timeout {
             debug!(
-                "Timeout reached in waiting for connection state ({:?}): {:?} / {}s elapsed",
-                target_state, state, total_time
+                "Timeout reached in waiting for connection state ({:?}): {:?} / {:?} elapsed",
+                watch.target_state,
+                state,
+                start.elapsed()
             );
 
-            return Ok(state);
+            Ok(state)
         }
+    }
+}
 
-        debug!(
-            "Still waiting for connection state ({:?}): {:?} / {}s elapsed",
-            target_state, state, total_time
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use device::DeviceState;
+    use fake::fixtures;
+    use wifi::Security;
+
+    #[test]
+    fn test_add_connection_before_in_range() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        let connection = manager
+            .add_connection(&fixtures::wpa_settings("factory", "secret"))
+            .unwrap();
+
+        assert_eq!(
+            connection.get_state().unwrap(),
+            ConnectionState::Deactivated
+        );
+        assert_eq!(manager.get_connections().unwrap(), vec![connection.clone()]);
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        assert!(device.connect().is_err());
+
+        fake.add_access_point(&device_path, "factory", 60, Security::WPA2, Some("secret"));
+
+        assert_eq!(device.connect().unwrap(), DeviceState::Activated);
+        assert_eq!(connection.get_state().unwrap(), ConnectionState::Activated);
+    }
+
+    #[test]
+    fn test_update_connection() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let mut connection = manager
+            .add_connection_unsaved(&fixtures::wpa_settings("office", "wrong password"))
+            .unwrap();
+
+        assert!(connection.activate().is_err());
+        assert_eq!(
+            connection.get_state().unwrap(),
+            ConnectionState::Deactivated
+        );
+
+        let mut settings = connection.settings().clone();
+        settings.connection.autoconnect_priority = Some(5);
+        settings.wireless_security.as_mut().unwrap().psk = Some("password".into());
+
+        connection.update(&settings).unwrap();
+        connection.save().unwrap();
+
+        assert_eq!(
+            connection.settings().connection.autoconnect_priority,
+            Some(5)
         );
+        assert_eq!(connection.activate().unwrap(), ConnectionState::Activated);
     }
 }
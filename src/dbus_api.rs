@@ -1,9 +1,12 @@
-use dbus::arg::{Array, Get, Iter, RefArg, Variant};
+use dbus::arg::{ArgType, Get, RefArg, Variant};
 use dbus::stdintf::OrgFreedesktopDBusProperties;
 use dbus::Connection as DBusConnection;
-use dbus::{BusType, ConnPath, Message, Path};
+use dbus::{BusType, ConnPath, ConnectionItem, Message, MessageItem, Path};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
 
 use errors::*;
+use settings::{SettingGroup, SettingType, SettingValue};
 
 const DEFAULT_TIMEOUT: u64 = 15;
 const RETRIES_ALLOWED: usize = 10;
@@ -164,6 +167,27 @@ impl DBusApi {
         }
     }
 
+    pub fn set_property<T>(&self, path: &str, interface: &str, name: &str, value: T) -> Result<()>
+    where
+        T: RefArg + 'static,
+    {
+        let path_with_base = self.with_path(path);
+
+        path_with_base
+            .set(interface, name, Variant(Box::new(value) as Box<dyn RefArg>))
+            .map_err(|e| {
+                let message = format!(
+                    "Set {}::{} property failed on {}: {}",
+                    interface,
+                    name,
+                    path,
+                    e.message().unwrap_or("no details")
+                );
+                error!("{}", message);
+                Error::from(e).chain_err(|| ErrorKind::DBusAPI(message))
+            })
+    }
+
     pub fn extract<'a, T>(&self, response: &'a Message) -> Result<T>
     where
         T: Get<'a>,
@@ -189,7 +213,42 @@ impl DBusApi {
         bail!(ErrorKind::DBusAPI("Wrong response type".into()))
     }
 
-    fn with_path<'a, P: Into<Path<'a>>>(&'a self, path: P) -> ConnPath<&'a DBusConnection> {
+    pub fn add_match(&self, rule: &str) -> Result<()> {
+        self.connection.add_match(rule).map_err(|e| {
+            let message = format!("Adding match rule failed: {}", rule);
+            error!("{}", message);
+            Error::from(e).chain_err(|| ErrorKind::DBusAPI(message))
+        })
+    }
+
+    pub fn remove_match(&self, rule: &str) -> Result<()> {
+        self.connection.remove_match(rule).map_err(|e| {
+            let message = format!("Removing match rule failed: {}", rule);
+            error!("{}", message);
+            Error::from(e).chain_err(|| ErrorKind::DBusAPI(message))
+        })
+    }
+
+    /// Waits for the next incoming signal. Returns `None` if no signal arrives
+    /// within `timeout`.
+    pub fn receive_signal(&self, timeout: Duration) -> Option<Message> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout_ms = remaining.as_millis().min(i32::MAX as u128) as i32;
+
+            if let Some(ConnectionItem::Signal(message)) = self.connection.iter(timeout_ms).next() {
+                return Some(message);
+            }
+
+            if Instant::now() >= deadline {
+                return None;
+            }
+        }
+    }
+
+    fn with_path<'a, P: Into<Path<'a>>>(&'a self, path: P) -> ConnPath<'a, &'a DBusConnection> {
         self.connection
             .with_path(self.base, path, self.method_timeout as i32 * 1000)
     }
@@ -213,13 +272,41 @@ impl VariantTo<i64> for DBusApi {
 
 impl VariantTo<u32> for DBusApi {
     fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<u32> {
-        value.0.as_i64().map(|v| v as u32)
+        value
+            .0
+            .as_i64()
+            .filter(|v| *v >= 0 && *v <= i64::from(u32::MAX))
+            .map(|v| v as u32)
+    }
+}
+
+/// `as_i64` is not implemented for `u64` values, which are downcast instead.
+impl VariantTo<u64> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<u64> {
+        match value.0.as_any().downcast_ref::<u64>() {
+            Some(v) => Some(*v),
+            None => value.0.as_i64().filter(|v| *v >= 0).map(|v| v as u64),
+        }
+    }
+}
+
+impl VariantTo<u8> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<u8> {
+        value
+            .0
+            .as_i64()
+            .filter(|v| *v >= 0 && *v <= i64::from(u8::MAX))
+            .map(|v| v as u8)
     }
 }
 
 impl VariantTo<bool> for DBusApi {
     fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<bool> {
-        value.0.as_i64().map(|v| v == 0)
+        if value.0.arg_type() != ArgType::Boolean {
+            return None;
+        }
+
+        value.0.as_i64().map(|v| v != 0)
     }
 }
 
@@ -263,24 +350,276 @@ impl VariantTo<Vec<u8>> for DBusApi {
     }
 }
 
-pub fn extract<'a, T>(var: &mut Variant<Iter<'a>>) -> Result<T>
-where
-    T: Get<'a>,
-{
-    var.0
-        .get::<T>()
-        .ok_or_else(|| ErrorKind::DBusAPI(format!("Variant type does not match: {:?}", var)).into())
+impl VariantTo<Vec<Path<'static>>> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<Vec<Path<'static>>> {
+        value
+            .0
+            .as_iter()?
+            .map(|element| Path::new(element.as_str()?.to_string()).ok())
+            .collect()
+    }
+}
+
+/// Decodes string keyed dictionaries, e.g. `a{sv}` or `a{ss}`.
+impl VariantTo<SettingGroup> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<SettingGroup> {
+        variant_to_dict(&variant_to_setting_value(value)?)
+    }
+}
+
+/// Decodes arrays of dictionaries, e.g. the `aa{sv}` `AddressData` property.
+impl VariantTo<Vec<SettingGroup>> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<Vec<SettingGroup>> {
+        array_items(&variant_to_setting_value(value)?)?
+            .iter()
+            .map(variant_to_dict)
+            .collect()
+    }
+}
+
+/// Decodes IPv4 addresses sent as `u32` values in network byte order.
+impl VariantTo<Vec<Ipv4Addr>> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<Vec<Ipv4Addr>> {
+        Vec::<Ipv4Addr>::from_setting(&variant_to_setting_value(value)?)
+    }
+}
+
+/// Decodes IPv6 addresses sent as byte arrays.
+impl VariantTo<Vec<Ipv6Addr>> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<Vec<Ipv6Addr>> {
+        let items = array_items(&variant_to_setting_value(value)?)?.to_vec();
+
+        Vec::<Ipv6Addr>::from_setting(&SettingValue::Array(items, "ay".into()))
+    }
+}
+
+/// Converts a property value with its exact D-Bus types, by appending it to a
+/// scratch message and reading it back.
+///
+/// Values nested in arrays and dictionaries are only exposed by `RefArg` as
+/// borrowed trait objects, which cannot be downcast, e.g. to `u64`.
+fn variant_to_setting_value(value: &Variant<Box<dyn RefArg>>) -> Option<SettingValue> {
+    // Arrays of arrays or structs are decoded as structs, which are invalid
+    // when empty
+    if value.0.arg_type() == ArgType::Struct && value.0.as_iter()?.next().is_none() {
+        return Some(SettingValue::Struct(Vec::new()));
+    }
+
+    let message = Message::new_signal("/", "org.freedesktop.DBus", "Decode")
+        .ok()?
+        .append_ref(&[&*value.0]);
+
+    setting_value_from_message_item(message.get_items().first()?).ok()
+}
+
+/// Returns the elements of an array. Arrays of arrays or structs arrive as
+/// structs and are accepted as well.
+fn array_items(value: &SettingValue) -> Option<&[SettingValue]> {
+    match *value {
+        SettingValue::Array(ref items, _) | SettingValue::Struct(ref items) => Some(items),
+        _ => None,
+    }
+}
+
+/// Decodes a dictionary, unwrapping all variant layers of its values, as
+/// dictionaries nested in arrays arrive with their values wrapped twice.
+fn variant_to_dict(value: &SettingValue) -> Option<SettingGroup> {
+    let mut group = value.as_dict()?;
+
+    for value in group.values_mut() {
+        while let SettingValue::Variant(inner) = value.clone() {
+            *value = *inner;
+        }
+    }
+
+    Some(group)
+}
+
+pub fn setting_value_from_message_item(item: &MessageItem) -> Result<SettingValue> {
+    let value = match *item {
+        MessageItem::Bool(value) => SettingValue::Bool(value),
+        MessageItem::Byte(value) => SettingValue::Byte(value),
+        MessageItem::Int16(value) => SettingValue::Int16(value),
+        MessageItem::UInt16(value) => SettingValue::UInt16(value),
+        MessageItem::Int32(value) => SettingValue::Int32(value),
+        MessageItem::UInt32(value) => SettingValue::UInt32(value),
+        MessageItem::Int64(value) => SettingValue::Int64(value),
+        MessageItem::UInt64(value) => SettingValue::UInt64(value),
+        MessageItem::Double(value) => SettingValue::Double(value),
+        MessageItem::Str(ref value) => SettingValue::String(value.clone()),
+        MessageItem::ObjectPath(ref value) => SettingValue::ObjectPath(value.to_string()),
+        MessageItem::Array(ref items, ref signature) => SettingValue::Array(
+            items
+                .iter()
+                .map(setting_value_from_message_item)
+                .collect::<Result<_>>()?,
+            signature.to_string(),
+        ),
+        MessageItem::Struct(ref items) => SettingValue::Struct(
+            items
+                .iter()
+                .map(setting_value_from_message_item)
+                .collect::<Result<_>>()?,
+        ),
+        MessageItem::DictEntry(ref key, ref value) => SettingValue::DictEntry(
+            Box::new(setting_value_from_message_item(key)?),
+            Box::new(setting_value_from_message_item(value)?),
+        ),
+        MessageItem::Variant(ref value) => {
+            SettingValue::Variant(Box::new(setting_value_from_message_item(value)?))
+        }
+        MessageItem::UnixFd(_) => bail!(ErrorKind::DBusAPI(
+            "File descriptors are not supported in settings".into()
+        )),
+    };
+
+    Ok(value)
 }
 
-pub fn variant_iter_to_vec_u8(var: &mut Variant<Iter>) -> Result<Vec<u8>> {
-    let array_option = &var.0.get::<Array<u8, _>>();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    type VariantMap = HashMap<String, Variant<Box<dyn RefArg>>>;
+
+    fn variant<T: RefArg + 'static>(value: T) -> Variant<Box<dyn RefArg>> {
+        Variant(Box::new(value))
+    }
+
+    fn decode<T>(value: &Variant<Box<dyn RefArg>>) -> Option<T>
+    where
+        DBusApi: VariantTo<T>,
+    {
+        DBusApi::variant_to(value)
+    }
+
+    fn address_data(address: &str, prefix: u32) -> VariantMap {
+        let mut map = VariantMap::new();
+        map.insert("address".into(), variant(address.to_string()));
+        map.insert("prefix".into(), variant(prefix));
+        map
+    }
+
+    #[test]
+    fn test_basic_types() {
+        assert_eq!(decode::<bool>(&variant(true)), Some(true));
+        assert_eq!(decode::<bool>(&variant(false)), Some(false));
+        assert_eq!(decode::<bool>(&variant(1u32)), None);
+        assert_eq!(decode::<u64>(&variant(u64::MAX)), Some(u64::MAX));
+        assert_eq!(decode::<u64>(&variant(7u32)), Some(7));
+        assert_eq!(decode::<u64>(&variant(-1i32)), None);
+        assert_eq!(decode::<u8>(&variant(3u8)), Some(3));
+        assert_eq!(decode::<u8>(&variant(300u32)), None);
+        assert_eq!(decode::<u32>(&variant(42u32)), Some(42));
+        assert_eq!(decode::<u32>(&variant(-1i32)), None);
+        assert_eq!(decode::<u32>(&variant(1i64 << 32)), None);
+        assert_eq!(
+            decode::<String>(&variant("wlan0".to_string())),
+            Some("wlan0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let paths = vec![
+            Path::new("/org/freedesktop/NetworkManager/Devices/1").unwrap(),
+            Path::new("/org/freedesktop/NetworkManager/Devices/2").unwrap(),
+        ];
+
+        assert_eq!(decode::<Vec<Path>>(&variant(paths.clone())), Some(paths));
+        assert_eq!(decode::<Vec<Path>>(&variant(1u32)), None);
+    }
+
+    #[test]
+    fn test_dicts() {
+        let mut map = VariantMap::new();
+        map.insert("lease".into(), variant(u64::MAX));
+        map.insert("dns".into(), variant(variant("192.168.1.1".to_string())));
+
+        let group = decode::<SettingGroup>(&variant(map)).unwrap();
+
+        assert_eq!(group["lease"], SettingValue::UInt64(u64::MAX));
+        assert_eq!(group["dns"], SettingValue::String("192.168.1.1".into()));
+
+        let mut map = HashMap::new();
+        map.insert("domain_name".to_string(), "lan".to_string());
+
+        let group = decode::<SettingGroup>(&variant(map)).unwrap();
+
+        assert_eq!(group["domain_name"], SettingValue::String("lan".into()));
+        assert_eq!(decode::<SettingGroup>(&variant(1u32)), None);
+    }
+
+    #[test]
+    fn test_arrays_of_dicts() {
+        let expected = [("192.168.1.10", 24u32), ("10.0.0.2", 8u32)];
+
+        let array = expected
+            .iter()
+            .map(|&(address, prefix)| address_data(address, prefix))
+            .collect::<Vec<_>>();
+
+        // Arrays of dictionaries are received as structs
+        let received = expected
+            .iter()
+            .map(|&(address, prefix)| Box::new(address_data(address, prefix)) as Box<dyn RefArg>)
+            .collect::<Vec<_>>();
+
+        for value in &[variant(array), variant(received)] {
+            let groups = decode::<Vec<SettingGroup>>(value).unwrap();
+
+            let decoded = groups
+                .iter()
+                .map(|group| {
+                    (
+                        group["address"].as_str().unwrap().to_string(),
+                        group["prefix"].as_i64().unwrap() as u32,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                decoded,
+                expected
+                    .iter()
+                    .map(|&(address, prefix)| (address.to_string(), prefix))
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let empty: Vec<Box<dyn RefArg>> = Vec::new();
+
+        assert_eq!(
+            decode::<Vec<SettingGroup>>(&variant(empty)),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_ip_addresses() {
+        let ipv4 = vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(8, 8, 8, 8)];
+        let encoded = ipv4
+            .iter()
+            .map(|address| u32::from_ne_bytes(address.octets()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(decode::<Vec<Ipv4Addr>>(&variant(encoded)), Some(ipv4));
+
+        let ipv6 = vec![
+            "2001:db8::1".parse::<Ipv6Addr>().unwrap(),
+            "fe80::1".parse::<Ipv6Addr>().unwrap(),
+        ];
+        let received = ipv6
+            .iter()
+            .map(|address| Box::new(address.octets().to_vec()) as Box<dyn RefArg>)
+            .collect::<Vec<_>>();
+
+        assert_eq!(decode::<Vec<Ipv6Addr>>(&variant(received)), Some(ipv6));
+
+        let truncated: Vec<Box<dyn RefArg>> = vec![Box::new(vec![0u8; 4])];
 
-    if let Some(array) = *array_option {
-        Ok(array.collect())
-    } else {
-        bail!(ErrorKind::DBusAPI(format!(
-            "Variant not an array: {:?}",
-            var
-        )))
+        assert_eq!(decode::<Vec<Ipv6Addr>>(&variant(truncated)), None);
     }
 }
@@ -1,56 +1,196 @@
-use std::collections::HashMap;
-use std::net::Ipv4Addr;
-
-use dbus::arg::{Array, Dict, Iter, RefArg, Variant};
-use dbus::Path;
-
-use ascii::AsciiStr;
-
-use connection::{ConnectionSettings, ConnectionState};
-use dbus_api::{extract, variant_iter_to_vec_u8, DBusApi, VariantTo};
-use device::{DeviceState, DeviceType};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use dbus::arg::{Array, RefArg, Variant};
+use dbus::{MessageItem, Path};
+
+use backend::{sealed, Backend};
+use checkpoint::{CheckpointFlags, RollbackResult};
+use connection::ConnectionState;
+use dbus_api::{setting_value_from_message_item, DBusApi, VariantTo};
+use device::{DeviceCapabilities, DeviceState, DeviceStateReason, DeviceType};
+use dhcp_config::{dhcp_options, Dhcp4Config, Dhcp6Config};
 use errors::*;
+use events::{new_event_stream, EventStream, SignalDispatcher};
+use ip_config::{AddressData, Ip4Config, Ip6Config, IpConfig, RouteData};
 use manager::{Connectivity, NetworkManagerState};
-use ssid::{AsSsidSlice, Ssid};
-use wifi::{AccessPoint, AccessPointCredentials, NM80211ApFlags, NM80211ApSecurityFlags};
+use modem::ModemCapabilities;
+use settings::{ConnectionSettings, SettingGroup, SettingValue};
+use ssid::{Ssid, SsidSlice};
+use wifi::{
+    key_mgmt, verify_ascii_password, AccessPoint, AccessPointCredentials, NM80211ApFlags,
+    NM80211ApSecurityFlags, NM80211Mode, WEP_KEY_TYPE_PASSPHRASE,
+};
 
 type VariantMap = HashMap<String, Variant<Box<dyn RefArg>>>;
 
 const NM_SERVICE_MANAGER: &str = "org.freedesktop.NetworkManager";
 
-const NM_SERVICE_PATH: &str = "/org/freedesktop/NetworkManager";
+pub const NM_SERVICE_PATH: &str = "/org/freedesktop/NetworkManager";
 const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
 
-const NM_SERVICE_INTERFACE: &str = "org.freedesktop.NetworkManager";
+pub const NM_SERVICE_INTERFACE: &str = "org.freedesktop.NetworkManager";
 const NM_SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
 const NM_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.\
                                        Connection";
-const NM_ACTIVE_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
-const NM_DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
-const NM_WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+pub const NM_ACTIVE_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+pub const NM_DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
+pub const NM_WIRED_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wired";
+const NM_MODEM_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Modem";
+pub const NM_WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
 const NM_ACCESS_POINT_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const NM_IP4_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP4Config";
+const NM_IP6_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP6Config";
+const NM_DHCP4_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.DHCP4Config";
+const NM_DHCP6_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.DHCP6Config";
 
-const NM_WEP_KEY_TYPE_PASSPHRASE: u32 = 2;
+pub const DBUS_PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+const NM_DEVICE_NOT_ALLOWED: &str = "org.freedesktop.NetworkManager.Device.NotAllowed";
+const SCAN_RETRY_MESSAGES: &[&str] = &["already scanning", "previous scan", "too frequent"];
 
 const UNKNOWN_CONNECTION: &str = "org.freedesktop.NetworkManager.UnknownConnection";
 const METHOD_RETRY_ERROR_NAMES: &[&str; 1] = &[UNKNOWN_CONNECTION];
 
 pub struct DBusNetworkManager {
-    dbus: DBusApi,
+    dbus: Rc<DBusApi>,
+    dispatcher: Rc<RefCell<SignalDispatcher>>,
 }
 
 impl DBusNetworkManager {
     pub fn new(method_timeout: Option<u64>) -> Self {
+        let dbus = Rc::new(DBusApi::new(
+            NM_SERVICE_MANAGER,
+            METHOD_RETRY_ERROR_NAMES,
+            method_timeout,
+        ));
+
         DBusNetworkManager {
-            dbus: DBusApi::new(NM_SERVICE_MANAGER, METHOD_RETRY_ERROR_NAMES, method_timeout),
+            dispatcher: Rc::new(RefCell::new(SignalDispatcher::new(Rc::clone(&dbus)))),
+            dbus,
         }
     }
 
-    pub fn method_timeout(&self) -> u64 {
+    /// Reads the configuration object, e.g. `IP4Config` or `DHCP4Config`,
+    /// referenced by `property` of the object at `path` with `read`. Returns
+    /// `None` if the property is unset.
+    fn config_object<C, F>(
+        &self,
+        path: &str,
+        interface: &str,
+        property: &str,
+        read: F,
+    ) -> Result<Option<C>>
+    where
+        F: FnOnce(&str) -> Result<C>,
+    {
+        let config_path: String = self.dbus.property(path, interface, property)?;
+
+        if config_path == "/" {
+            return Ok(None);
+        }
+
+        read(&config_path).map(Some)
+    }
+
+    fn ip_config<A>(&self, path: &str, interface: &str) -> Result<IpConfig<A>>
+    where
+        A: FromStr,
+        DBusApi: VariantTo<Vec<A>>,
+    {
+        let error = || ErrorKind::DBusAPI(format!("Wrong {} properties on {}", interface, path));
+
+        let address_data =
+            self.dbus
+                .property::<Vec<SettingGroup>>(path, interface, "AddressData")?;
+        let route_data = self
+            .dbus
+            .property::<Vec<SettingGroup>>(path, interface, "RouteData")?;
+        let gateway = self.dbus.property::<String>(path, interface, "Gateway")?;
+
+        // An empty string is reported when there is no gateway
+        let gateway = if gateway.is_empty() {
+            None
+        } else {
+            Some(gateway.parse().map_err(|_| error())?)
+        };
+
+        Ok(IpConfig {
+            addresses: address_data
+                .iter()
+                .map(AddressData::from_attributes)
+                .collect::<Option<_>>()
+                .ok_or_else(error)?,
+            gateway,
+            routes: route_data
+                .iter()
+                .map(RouteData::from_attributes)
+                .collect::<Option<_>>()
+                .ok_or_else(error)?,
+            nameservers: self.dbus.property(path, interface, "Nameservers")?,
+            domains: self
+                .dbus
+                .property::<Vec<String>>(path, interface, "Domains")?,
+            searches: self
+                .dbus
+                .property::<Vec<String>>(path, interface, "Searches")?,
+        })
+    }
+
+    fn dhcp_options(&self, path: &str, interface: &str) -> Result<BTreeMap<String, String>> {
+        let options: SettingGroup = self.dbus.property(path, interface, "Options")?;
+
+        dhcp_options(&options).ok_or_else(|| {
+            ErrorKind::DBusAPI(format!("Wrong {} options on {}", interface, path)).into()
+        })
+    }
+
+    /// Reads an `ao` typed property.
+    fn object_paths(&self, path: &str, interface: &str, property: &str) -> Result<Vec<String>> {
+        let paths: Vec<Path> = self.dbus.property(path, interface, property)?;
+
+        Ok(paths.iter().map(|path| path.to_string()).collect())
+    }
+}
+
+/// The rules matching the Network Manager signals that are translated into
+/// events.
+pub fn signal_match_rules() -> Vec<String> {
+    vec![
+        format!(
+            "type='signal',sender='{}',interface='{}'",
+            NM_SERVICE_MANAGER, NM_SERVICE_INTERFACE
+        ),
+        format!(
+            "type='signal',sender='{}',interface='{}',member='PropertiesChanged'",
+            NM_SERVICE_MANAGER, DBUS_PROPERTIES_INTERFACE
+        ),
+        format!(
+            "type='signal',sender='{}',interface='{}',member='StateChanged'",
+            NM_SERVICE_MANAGER, NM_DEVICE_INTERFACE
+        ),
+        format!(
+            "type='signal',sender='{}',interface='{}',member='StateChanged'",
+            NM_SERVICE_MANAGER, NM_ACTIVE_INTERFACE
+        ),
+    ]
+}
+
+impl sealed::Sealed for DBusNetworkManager {}
+
+impl Backend for DBusNetworkManager {
+    fn method_timeout(&self) -> u64 {
         self.dbus.method_timeout()
     }
 
-    pub fn get_state(&self) -> Result<NetworkManagerState> {
+    fn subscribe(&self) -> Result<EventStream> {
+        new_event_stream(&self.dispatcher)
+    }
+
+    fn get_state(&self) -> Result<NetworkManagerState> {
         let response = self
             .dbus
             .call(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "state")?;
@@ -60,7 +200,7 @@ impl DBusNetworkManager {
         Ok(NetworkManagerState::from(state))
     }
 
-    pub fn check_connectivity(&self) -> Result<Connectivity> {
+    fn check_connectivity(&self) -> Result<Connectivity> {
         let response =
             self.dbus
                 .call(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "CheckConnectivity")?;
@@ -70,17 +210,136 @@ impl DBusNetworkManager {
         Ok(Connectivity::from(connectivity))
     }
 
-    pub fn is_wireless_enabled(&self) -> Result<bool> {
+    fn is_wireless_enabled(&self) -> Result<bool> {
         self.dbus
             .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "WirelessEnabled")
     }
 
-    pub fn is_networking_enabled(&self) -> Result<bool> {
+    fn is_networking_enabled(&self) -> Result<bool> {
         self.dbus
             .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "NetworkingEnabled")
     }
 
-    pub fn list_connections(&self) -> Result<Vec<String>> {
+    fn is_wireless_hardware_enabled(&self) -> Result<bool> {
+        self.dbus.property(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "WirelessHardwareEnabled",
+        )
+    }
+
+    fn is_wwan_enabled(&self) -> Result<bool> {
+        self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "WwanEnabled")
+    }
+
+    fn is_wwan_hardware_enabled(&self) -> Result<bool> {
+        self.dbus
+            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "WwanHardwareEnabled")
+    }
+
+    fn set_networking_enabled(&self, enabled: bool) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "Enable",
+            &[&enabled as &dyn RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        self.dbus.set_property(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "WirelessEnabled",
+            enabled,
+        )
+    }
+
+    fn set_wwan_enabled(&self, enabled: bool) -> Result<()> {
+        self.dbus.set_property(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "WwanEnabled",
+            enabled,
+        )
+    }
+
+    fn sleep(&self, sleep: bool) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "Sleep",
+            &[&sleep as &dyn RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    fn create_checkpoint(
+        &self,
+        devices: &[String],
+        rollback_timeout: u32,
+        flags: CheckpointFlags,
+    ) -> Result<String> {
+        let devices = devices
+            .iter()
+            .map(|device| Path::new(device.as_str()))
+            .collect::<::std::result::Result<Vec<_>, _>>()?;
+
+        let response = self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointCreate",
+            &[&devices as &dyn RefArg, &rollback_timeout, &flags.bits()],
+        )?;
+
+        let path: Path = self.dbus.extract(&response)?;
+
+        Ok(path.to_string())
+    }
+
+    fn destroy_checkpoint(&self, path: &str) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointDestroy",
+            &[&Path::new(path)? as &dyn RefArg],
+        )?;
+
+        Ok(())
+    }
+
+    fn rollback_checkpoint(&self, path: &str) -> Result<BTreeMap<String, RollbackResult>> {
+        let response = self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointRollback",
+            &[&Path::new(path)? as &dyn RefArg],
+        )?;
+
+        let results: HashMap<String, u32> = self.dbus.extract(&response)?;
+
+        Ok(results
+            .into_iter()
+            .map(|(device, result)| (device, RollbackResult::from(result)))
+            .collect())
+    }
+
+    fn adjust_checkpoint_rollback_timeout(&self, path: &str, add_timeout: u32) -> Result<()> {
+        self.dbus.call_with_args(
+            NM_SERVICE_PATH,
+            NM_SERVICE_INTERFACE,
+            "CheckpointAdjustRollbackTimeout",
+            &[&Path::new(path)? as &dyn RefArg, &add_timeout],
+        )?;
+
+        Ok(())
+    }
+
+    fn list_connections(&self) -> Result<Vec<String>> {
         let response =
             self.dbus
                 .call(NM_SETTINGS_PATH, NM_SETTINGS_INTERFACE, "ListConnections")?;
@@ -90,18 +349,17 @@ impl DBusNetworkManager {
         Ok(array.map(|e| e.to_string()).collect())
     }
 
-    pub fn get_active_connections(&self) -> Result<Vec<String>> {
-        self.dbus
-            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "ActiveConnections")
+    fn get_active_connections(&self) -> Result<Vec<String>> {
+        self.object_paths(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "ActiveConnections")
     }
 
-    pub fn get_active_connection_path(&self, path: &str) -> Option<String> {
+    fn get_active_connection_path(&self, path: &str) -> Option<String> {
         self.dbus
             .property(path, NM_ACTIVE_INTERFACE, "Connection")
             .ok()
     }
 
-    pub fn get_connection_state(&self, path: &str) -> Result<ConnectionState> {
+    fn get_connection_state(&self, path: &str) -> Result<ConnectionState> {
         let state: i64 = match self.dbus.property(path, NM_ACTIVE_INTERFACE, "State") {
             Ok(state) => state,
             Err(_) => return Ok(ConnectionState::Unknown),
@@ -110,63 +368,91 @@ impl DBusNetworkManager {
         Ok(ConnectionState::from(state))
     }
 
-    pub fn get_connection_settings(&self, path: &str) -> Result<ConnectionSettings> {
+    fn get_connection_settings(&self, path: &str) -> Result<ConnectionSettings> {
         let response = self
             .dbus
             .call(path, NM_CONNECTION_INTERFACE, "GetSettings")?;
 
-        let dict: Dict<&str, Dict<&str, Variant<Iter>, _>, _> = self.dbus.extract(&response)?;
-
-        let mut kind = String::new();
-        let mut id = String::new();
-        let mut uuid = String::new();
-        let mut ssid = Ssid::new();
-        let mut mode = String::new();
-
-        for (_, v1) in dict {
-            for (k2, mut v2) in v1 {
-                match k2 {
-                    "id" => {
-                        id = extract::<String>(&mut v2)?;
-                    }
-                    "uuid" => {
-                        uuid = extract::<String>(&mut v2)?;
-                    }
-                    "type" => {
-                        kind = extract::<String>(&mut v2)?;
-                    }
-                    "ssid" => {
-                        ssid = Ssid::from_bytes(variant_iter_to_vec_u8(&mut v2)?)?;
-                    }
-                    "mode" => {
-                        mode = extract::<String>(&mut v2)?;
-                    }
-                    _ => {}
-                }
-            }
+        match response.get_items().first() {
+            Some(item) => settings_from_message_item(item),
+            None => bail!(ErrorKind::DBusAPI("Wrong response type".into())),
         }
+    }
+
+    fn get_active_connection_devices(&self, path: &str) -> Result<Vec<String>> {
+        self.object_paths(path, NM_ACTIVE_INTERFACE, "Devices")
+    }
 
-        Ok(ConnectionSettings {
-            kind,
-            id,
-            uuid,
-            ssid,
-            mode,
+    fn get_active_connection_ip4_config(&self, path: &str) -> Result<Option<Ip4Config>> {
+        self.config_object(path, NM_ACTIVE_INTERFACE, "Ip4Config", |config| {
+            self.ip_config(config, NM_IP4_CONFIG_INTERFACE)
         })
     }
 
-    pub fn get_active_connection_devices(&self, path: &str) -> Result<Vec<String>> {
-        self.dbus.property(path, NM_ACTIVE_INTERFACE, "Devices")
+    fn get_active_connection_ip6_config(&self, path: &str) -> Result<Option<Ip6Config>> {
+        self.config_object(path, NM_ACTIVE_INTERFACE, "Ip6Config", |config| {
+            self.ip_config(config, NM_IP6_CONFIG_INTERFACE)
+        })
     }
 
-    pub fn delete_connection(&self, path: &str) -> Result<()> {
+    fn delete_connection(&self, path: &str) -> Result<()> {
         self.dbus.call(path, NM_CONNECTION_INTERFACE, "Delete")?;
 
         Ok(())
     }
 
-    pub fn activate_connection(&self, path: &str) -> Result<()> {
+    fn add_connection(&self, settings: &ConnectionSettings, save: bool) -> Result<String> {
+        // Profiles are rejected without an UUID
+        let mut settings = settings.clone();
+
+        if settings.uuid().is_empty() {
+            settings.connection.uuid = Some(random_uuid()?);
+        }
+
+        let method = if save {
+            "AddConnection"
+        } else {
+            "AddConnectionUnsaved"
+        };
+
+        let response = self.dbus.call_with_args(
+            NM_SETTINGS_PATH,
+            NM_SETTINGS_INTERFACE,
+            method,
+            &[&settings_to_message_item(&settings)?],
+        )?;
+
+        let path: Path = self.dbus.extract(&response)?;
+
+        Ok(path.to_string())
+    }
+
+    fn update_connection(
+        &self,
+        path: &str,
+        settings: &ConnectionSettings,
+        save: bool,
+    ) -> Result<()> {
+        let method = if save { "Update" } else { "UpdateUnsaved" };
+
         self.dbus.call_with_args(
+            path,
+            NM_CONNECTION_INTERFACE,
+            method,
+            &[&settings_to_message_item(settings)?],
+        )?;
+
+        Ok(())
+    }
+
+    fn save_connection(&self, path: &str) -> Result<()> {
+        self.dbus.call(path, NM_CONNECTION_INTERFACE, "Save")?;
+
+        Ok(())
+    }
+
+    fn activate_connection(&self, path: &str) -> Result<String> {
+        let response = self.dbus.call_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
             "ActivateConnection",
@@ -177,10 +463,12 @@ impl DBusNetworkManager {
             ],
         )?;
 
-        Ok(())
+        let active_connection: Path = self.dbus.extract(&response)?;
+
+        Ok(active_connection.to_string())
     }
 
-    pub fn deactivate_connection(&self, path: &str) -> Result<()> {
+    fn deactivate_connection(&self, path: &str) -> Result<()> {
         self.dbus.call_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
@@ -191,7 +479,7 @@ impl DBusNetworkManager {
         Ok(())
     }
 
-    pub fn connect_to_access_point(
+    fn connect_to_access_point(
         &self,
         device_path: &str,
         access_point: &AccessPoint,
@@ -214,7 +502,7 @@ impl DBusNetworkManager {
                 add_val(
                     &mut security_settings,
                     "wep-key-type",
-                    NM_WEP_KEY_TYPE_PASSPHRASE,
+                    WEP_KEY_TYPE_PASSPHRASE,
                 );
                 add_str(
                     &mut security_settings,
@@ -227,7 +515,11 @@ impl DBusNetworkManager {
             AccessPointCredentials::Wpa { ref passphrase } => {
                 let mut security_settings: VariantMap = HashMap::new();
 
-                add_str(&mut security_settings, "key-mgmt", "wpa-psk");
+                add_str(
+                    &mut security_settings,
+                    "key-mgmt",
+                    key_mgmt(access_point, credentials).unwrap_or("wpa-psk"),
+                );
                 add_str(
                     &mut security_settings,
                     "psk",
@@ -236,24 +528,43 @@ impl DBusNetworkManager {
 
                 settings.insert("802-11-wireless-security".to_string(), security_settings);
             }
-            AccessPointCredentials::Enterprise {
-                ref identity,
-                ref passphrase,
-            } => {
+            AccessPointCredentials::Sae { ref passphrase } => {
                 let mut security_settings: VariantMap = HashMap::new();
 
-                add_str(&mut security_settings, "key-mgmt", "wpa-eap");
+                // SAE passwords are not limited to the WPA-PSK length
+                if passphrase.is_empty() {
+                    bail!(ErrorKind::PreSharedKey("Empty password".into()));
+                }
+
+                add_str(&mut security_settings, "key-mgmt", "sae");
+                add_str(&mut security_settings, "psk", passphrase as &str);
+
+                settings.insert("802-11-wireless-security".to_string(), security_settings);
+            }
+            AccessPointCredentials::Enterprise(ref ieee8021x)
+            | AccessPointCredentials::EnterpriseSuiteB192(ref ieee8021x) => {
+                let mut security_settings: VariantMap = HashMap::new();
+
+                add_str(
+                    &mut security_settings,
+                    "key-mgmt",
+                    key_mgmt(access_point, credentials).unwrap_or("wpa-eap"),
+                );
 
-                let mut eap: VariantMap = HashMap::new();
-                add_val(&mut eap, "eap", vec!["peap".to_string()]);
-                add_str(&mut eap, "identity", identity as &str);
-                add_str(&mut eap, "password", passphrase as &str);
-                add_str(&mut eap, "phase2-auth", "mschapv2");
+                let eap = group_to_variant_map(&ieee8021x.to_setting()?.to_group())?;
 
                 settings.insert("802-11-wireless-security".to_string(), security_settings);
                 settings.insert("802-1x".to_string(), eap);
             }
-            AccessPointCredentials::None => {}
+            AccessPointCredentials::Owe | AccessPointCredentials::None => {
+                if let Some(key_mgmt) = key_mgmt(access_point, credentials) {
+                    let mut security_settings: VariantMap = HashMap::new();
+
+                    add_str(&mut security_settings, "key-mgmt", key_mgmt);
+
+                    settings.insert("802-11-wireless-security".to_string(), security_settings);
+                }
+            }
         };
 
         let response = self.dbus.call_with_args(
@@ -272,69 +583,17 @@ impl DBusNetworkManager {
         Ok((conn_path.to_string(), active_connection.to_string()))
     }
 
-    pub fn create_hotspot<T>(
+    fn add_and_activate_connection(
         &self,
+        settings: &ConnectionSettings,
         device_path: &str,
-        interface: &str,
-        ssid: &T,
-        password: Option<&str>,
-        address: Option<Ipv4Addr>,
-    ) -> Result<(String, String)>
-    where
-        T: AsSsidSlice + ?Sized,
-    {
-        let ssid = ssid.as_ssid_slice()?;
-        let ssid_vec = ssid.as_bytes().to_vec();
-
-        let mut wireless: VariantMap = HashMap::new();
-        add_val(&mut wireless, "ssid", ssid_vec);
-        add_str(&mut wireless, "band", "bg");
-        add_val(&mut wireless, "hidden", false);
-        add_str(&mut wireless, "mode", "ap");
-
-        let mut connection: VariantMap = HashMap::new();
-        add_val(&mut connection, "autoconnect", false);
-        if let Ok(ssid_str) = ssid.as_str() {
-            add_str(&mut connection, "id", ssid_str);
-        }
-        add_str(&mut connection, "interface-name", interface);
-        add_str(&mut connection, "type", "802-11-wireless");
-
-        let mut ipv4: VariantMap = HashMap::new();
-        if let Some(address) = address {
-            add_str(&mut ipv4, "method", "manual");
-
-            let mut addr_map: VariantMap = HashMap::new();
-            add_str(&mut addr_map, "address", format!("{}", address));
-            add_val(&mut addr_map, "prefix", 24_u32);
-
-            add_val(&mut ipv4, "address-data", vec![addr_map]);
-        } else {
-            add_str(&mut ipv4, "method", "shared");
-        }
-
-        let mut settings: HashMap<String, VariantMap> = HashMap::new();
-
-        if let Some(password) = password {
-            add_str(&mut wireless, "security", "802-11-wireless-security");
-
-            let mut security: VariantMap = HashMap::new();
-            add_str(&mut security, "key-mgmt", "wpa-psk");
-            add_str(&mut security, "psk", verify_ascii_password(password)?);
-
-            settings.insert("802-11-wireless-security".to_string(), security);
-        }
-
-        settings.insert("802-11-wireless".to_string(), wireless);
-        settings.insert("connection".to_string(), connection);
-        settings.insert("ipv4".to_string(), ipv4);
-
+    ) -> Result<(String, String)> {
         let response = self.dbus.call_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
             "AddAndActivateConnection",
             &[
-                &settings as &dyn RefArg,
+                &settings_to_message_item(settings)?,
                 &Path::new(device_path)? as &dyn RefArg,
                 &Path::new("/")? as &dyn RefArg,
             ],
@@ -345,12 +604,11 @@ impl DBusNetworkManager {
         Ok((conn_path.to_string(), active_connection.to_string()))
     }
 
-    pub fn get_devices(&self) -> Result<Vec<String>> {
-        self.dbus
-            .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "Devices")
+    fn get_devices(&self) -> Result<Vec<String>> {
+        self.object_paths(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "Devices")
     }
 
-    pub fn get_device_by_interface(&self, interface: &str) -> Result<String> {
+    fn get_device_by_interface(&self, interface: &str) -> Result<String> {
         let response = self.dbus.call_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
@@ -363,19 +621,116 @@ impl DBusNetworkManager {
         Ok(path.to_string())
     }
 
-    pub fn get_device_interface(&self, path: &str) -> Result<String> {
+    fn get_device_interface(&self, path: &str) -> Result<String> {
         self.dbus.property(path, NM_DEVICE_INTERFACE, "Interface")
     }
 
-    pub fn get_device_type(&self, path: &str) -> Result<DeviceType> {
+    fn get_device_type(&self, path: &str) -> Result<DeviceType> {
         self.dbus.property(path, NM_DEVICE_INTERFACE, "DeviceType")
     }
 
-    pub fn get_device_state(&self, path: &str) -> Result<DeviceState> {
+    fn get_device_state(&self, path: &str) -> Result<DeviceState> {
         self.dbus.property(path, NM_DEVICE_INTERFACE, "State")
     }
 
-    pub fn connect_device(&self, path: &str) -> Result<()> {
+    fn get_device_state_reason(&self, path: &str) -> Result<DeviceStateReason> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "StateReason")
+    }
+
+    fn get_device_hw_address(&self, path: &str) -> Result<String> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "HwAddress")
+    }
+
+    fn get_device_perm_hw_address(&self, path: &str) -> Result<Option<String>> {
+        let interface = match self.get_device_type(path)? {
+            DeviceType::Ethernet => NM_WIRED_INTERFACE,
+            DeviceType::WiFi => NM_WIRELESS_INTERFACE,
+            _ => return Ok(None),
+        };
+
+        self.dbus
+            .property(path, interface, "PermHwAddress")
+            .map(Some)
+    }
+
+    fn get_device_driver(&self, path: &str) -> Result<String> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "Driver")
+    }
+
+    fn get_device_driver_version(&self, path: &str) -> Result<String> {
+        self.dbus
+            .property(path, NM_DEVICE_INTERFACE, "DriverVersion")
+    }
+
+    fn get_device_firmware_version(&self, path: &str) -> Result<String> {
+        self.dbus
+            .property(path, NM_DEVICE_INTERFACE, "FirmwareVersion")
+    }
+
+    fn get_device_mtu(&self, path: &str) -> Result<u32> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "Mtu")
+    }
+
+    fn get_device_capabilities(&self, path: &str) -> Result<DeviceCapabilities> {
+        self.dbus
+            .property(path, NM_DEVICE_INTERFACE, "Capabilities")
+    }
+
+    fn is_device_managed(&self, path: &str) -> Result<bool> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "Managed")
+    }
+
+    fn get_device_autoconnect(&self, path: &str) -> Result<bool> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "Autoconnect")
+    }
+
+    fn get_device_ip4_connectivity(&self, path: &str) -> Result<Connectivity> {
+        let connectivity: u32 = self
+            .dbus
+            .property(path, NM_DEVICE_INTERFACE, "Ip4Connectivity")?;
+
+        Ok(Connectivity::from(connectivity))
+    }
+
+    fn get_device_udi(&self, path: &str) -> Result<String> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "Udi")
+    }
+
+    fn is_device_real(&self, path: &str) -> Result<bool> {
+        self.dbus.property(path, NM_DEVICE_INTERFACE, "Real")
+    }
+
+    fn get_device_available_connections(&self, path: &str) -> Result<Vec<String>> {
+        self.object_paths(path, NM_DEVICE_INTERFACE, "AvailableConnections")
+    }
+
+    fn get_device_ip4_config(&self, path: &str) -> Result<Option<Ip4Config>> {
+        self.config_object(path, NM_DEVICE_INTERFACE, "Ip4Config", |config| {
+            self.ip_config(config, NM_IP4_CONFIG_INTERFACE)
+        })
+    }
+
+    fn get_device_ip6_config(&self, path: &str) -> Result<Option<Ip6Config>> {
+        self.config_object(path, NM_DEVICE_INTERFACE, "Ip6Config", |config| {
+            self.ip_config(config, NM_IP6_CONFIG_INTERFACE)
+        })
+    }
+
+    fn get_device_dhcp4_config(&self, path: &str) -> Result<Option<Dhcp4Config>> {
+        self.config_object(path, NM_DEVICE_INTERFACE, "Dhcp4Config", |config| {
+            self.dhcp_options(config, NM_DHCP4_CONFIG_INTERFACE)
+                .map(Dhcp4Config::from_options)
+        })
+    }
+
+    fn get_device_dhcp6_config(&self, path: &str) -> Result<Option<Dhcp6Config>> {
+        self.config_object(path, NM_DEVICE_INTERFACE, "Dhcp6Config", |config| {
+            self.dhcp_options(config, NM_DHCP6_CONFIG_INTERFACE)
+                .map(Dhcp6Config::from_options)
+        })
+    }
+
+    fn connect_device(&self, path: &str) -> Result<()> {
         self.dbus.call_with_args(
             NM_SERVICE_PATH,
             NM_SERVICE_INTERFACE,
@@ -390,30 +745,82 @@ impl DBusNetworkManager {
         Ok(())
     }
 
-    pub fn disconnect_device(&self, path: &str) -> Result<()> {
+    fn disconnect_device(&self, path: &str) -> Result<()> {
         self.dbus.call(path, NM_DEVICE_INTERFACE, "Disconnect")?;
 
         Ok(())
     }
 
-    pub fn request_access_point_scan(&self, path: &str) -> Result<()> {
-        let options: VariantMap = HashMap::new();
-        self.dbus.call_with_args(
+    fn get_wired_carrier(&self, path: &str) -> Result<bool> {
+        self.dbus.property(path, NM_WIRED_INTERFACE, "Carrier")
+    }
+
+    fn get_wired_speed(&self, path: &str) -> Result<u32> {
+        self.dbus.property(path, NM_WIRED_INTERFACE, "Speed")
+    }
+
+    fn get_wired_s390_subchannels(&self, path: &str) -> Result<Vec<String>> {
+        self.dbus
+            .property(path, NM_WIRED_INTERFACE, "S390Subchannels")
+    }
+
+    fn get_modem_capabilities(&self, path: &str) -> Result<ModemCapabilities> {
+        self.dbus
+            .property(path, NM_MODEM_INTERFACE, "ModemCapabilities")
+    }
+
+    fn get_modem_current_capabilities(&self, path: &str) -> Result<ModemCapabilities> {
+        self.dbus
+            .property(path, NM_MODEM_INTERFACE, "CurrentCapabilities")
+    }
+
+    fn get_modem_device_id(&self, path: &str) -> Result<String> {
+        self.dbus.property(path, NM_MODEM_INTERFACE, "DeviceId")
+    }
+
+    fn get_modem_operator_code(&self, path: &str) -> Result<String> {
+        self.dbus.property(path, NM_MODEM_INTERFACE, "OperatorCode")
+    }
+
+    fn request_access_point_scan(&self, path: &str, ssids: &[&SsidSlice]) -> Result<()> {
+        let mut options: VariantMap = HashMap::new();
+
+        if !ssids.is_empty() {
+            let ssids = ssids
+                .iter()
+                .map(|ssid| ssid.as_bytes().to_vec())
+                .collect::<Vec<_>>();
+
+            add_val(&mut options, "ssids", ssids);
+        }
+
+        let result = self.dbus.call_with_args(
             path,
             NM_WIRELESS_INTERFACE,
             "RequestScan",
             &[&options as &dyn RefArg],
-        )?;
+        );
+
+        if let Err(error) = result {
+            if let Some(message) = scan_not_allowed_message(&error) {
+                bail!(ErrorKind::ScanNotAllowed(message));
+            }
+
+            return Err(error);
+        }
 
         Ok(())
     }
 
-    pub fn get_device_access_points(&self, path: &str) -> Result<Vec<String>> {
-        self.dbus
-            .property(path, NM_WIRELESS_INTERFACE, "AccessPoints")
+    fn get_device_last_scan(&self, path: &str) -> Result<i64> {
+        self.dbus.property(path, NM_WIRELESS_INTERFACE, "LastScan")
+    }
+
+    fn get_device_access_points(&self, path: &str) -> Result<Vec<String>> {
+        self.object_paths(path, NM_WIRELESS_INTERFACE, "AccessPoints")
     }
 
-    pub fn get_access_point_ssid(&self, path: &str) -> Option<Ssid> {
+    fn get_access_point_ssid(&self, path: &str) -> Option<Ssid> {
         if let Ok(ssid_vec) = self
             .dbus
             .property::<Vec<u8>>(path, NM_ACCESS_POINT_INTERFACE, "Ssid")
@@ -424,24 +831,54 @@ impl DBusNetworkManager {
         }
     }
 
-    pub fn get_access_point_strength(&self, path: &str) -> Result<u32> {
-        self.dbus
-            .property(path, NM_ACCESS_POINT_INTERFACE, "Strength")
+    fn get_access_point_strength(&self, path: &str) -> Result<u32> {
+        let strength: u8 = self
+            .dbus
+            .property(path, NM_ACCESS_POINT_INTERFACE, "Strength")?;
+
+        Ok(u32::from(strength))
     }
 
-    pub fn get_access_point_flags(&self, path: &str) -> Result<NM80211ApFlags> {
+    fn get_access_point_flags(&self, path: &str) -> Result<NM80211ApFlags> {
         self.dbus.property(path, NM_ACCESS_POINT_INTERFACE, "Flags")
     }
 
-    pub fn get_access_point_wpa_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
+    fn get_access_point_wpa_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
         self.dbus
             .property(path, NM_ACCESS_POINT_INTERFACE, "WpaFlags")
     }
 
-    pub fn get_access_point_rsn_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
+    fn get_access_point_rsn_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
         self.dbus
             .property(path, NM_ACCESS_POINT_INTERFACE, "RsnFlags")
     }
+
+    fn get_access_point_bssid(&self, path: &str) -> Result<String> {
+        self.dbus
+            .property(path, NM_ACCESS_POINT_INTERFACE, "HwAddress")
+    }
+
+    fn get_access_point_frequency(&self, path: &str) -> Result<u32> {
+        self.dbus
+            .property(path, NM_ACCESS_POINT_INTERFACE, "Frequency")
+    }
+
+    fn get_access_point_max_bitrate(&self, path: &str) -> Result<u32> {
+        self.dbus
+            .property(path, NM_ACCESS_POINT_INTERFACE, "MaxBitrate")
+    }
+
+    fn get_access_point_mode(&self, path: &str) -> Result<NM80211Mode> {
+        self.dbus.property(path, NM_ACCESS_POINT_INTERFACE, "Mode")
+    }
+
+    fn get_access_point_last_seen(&self, path: &str) -> Result<i32> {
+        let last_seen: i64 = self
+            .dbus
+            .property(path, NM_ACCESS_POINT_INTERFACE, "LastSeen")?;
+
+        Ok(last_seen as i32)
+    }
 }
 
 impl VariantTo<DeviceType> for DBusApi {
@@ -456,6 +893,44 @@ impl VariantTo<DeviceState> for DBusApi {
     }
 }
 
+/// `StateReason` is a `(uu)` struct of the state and its reason.
+impl VariantTo<DeviceStateReason> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<DeviceStateReason> {
+        let mut fields = value.0.as_iter()?;
+
+        fields.next()?;
+
+        fields
+            .next()?
+            .as_i64()
+            .map(|v| DeviceStateReason::from(v as u32))
+    }
+}
+
+impl VariantTo<DeviceCapabilities> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<DeviceCapabilities> {
+        value
+            .0
+            .as_i64()
+            .map(|v| DeviceCapabilities::from_bits_truncate(v as u32))
+    }
+}
+
+impl VariantTo<ModemCapabilities> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<ModemCapabilities> {
+        value
+            .0
+            .as_i64()
+            .map(|v| ModemCapabilities::from_bits_truncate(v as u32))
+    }
+}
+
+impl VariantTo<NM80211Mode> for DBusApi {
+    fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<NM80211Mode> {
+        value.0.as_i64().map(NM80211Mode::from)
+    }
+}
+
 impl VariantTo<NM80211ApFlags> for DBusApi {
     fn variant_to(value: &Variant<Box<dyn RefArg>>) -> Option<NM80211ApFlags> {
         value
@@ -490,23 +965,271 @@ where
     map.insert(key.into(), Variant(Box::new(value.into())));
 }
 
-fn verify_ascii_password(password: &str) -> Result<&str> {
-    match AsciiStr::from_ascii(password) {
-        Err(e) => Err(e).chain_err(|| ErrorKind::PreSharedKey("Not an ASCII password".into())),
-        Ok(p) => {
-            if p.len() < 8 {
-                bail!(ErrorKind::PreSharedKey(format!(
-                    "Password length should be at least 8 characters: {} len",
-                    p.len()
-                )))
-            } else if p.len() > 64 {
-                bail!(ErrorKind::PreSharedKey(format!(
-                    "Password length should not exceed 64: {} len",
-                    p.len()
-                )))
-            } else {
-                Ok(password)
+/// Returns the message of a `RequestScan` failure that goes away by retrying
+/// later, i.e. a scan already running or the rate limit being hit.
+fn scan_not_allowed_message(error: &Error) -> Option<String> {
+    let mut current = Some(error);
+    let mut dbus_error = None;
+
+    while let Some(error) = current {
+        if let ErrorKind::DBus(ref e) = *error.kind() {
+            dbus_error = Some(e);
+            break;
+        }
+
+        current = error
+            .1
+            .next_error
+            .as_ref()
+            .and_then(|next| next.downcast_ref::<Error>());
+    }
+
+    let dbus_error = dbus_error?;
+
+    let message = dbus_error.message()?;
+
+    if dbus_error.name() == Some(NM_DEVICE_NOT_ALLOWED)
+        && SCAN_RETRY_MESSAGES
+            .iter()
+            .any(|retry_message| message.contains(retry_message))
+    {
+        Some(message.to_string())
+    } else {
+        None
+    }
+}
+
+/// Generates a version 4 UUID using the kernel random number generator.
+fn random_uuid() -> Result<String> {
+    let uuid = fs::read_to_string("/proc/sys/kernel/random/uuid")
+        .chain_err(|| ErrorKind::NetworkManager("Unable to generate connection UUID".into()))?;
+
+    Ok(uuid.trim().to_string())
+}
+
+/// Converts a setting group to the `a{sv}` dictionary built by the calls
+/// that take settings as nested variant maps.
+fn group_to_variant_map(group: &SettingGroup) -> Result<VariantMap> {
+    let mut map: VariantMap = HashMap::new();
+
+    for (key, value) in group {
+        add_val(&mut map, key.clone(), setting_value_to_message_item(value)?);
+    }
+
+    Ok(map)
+}
+
+/// Converts the `a{sa{sv}}` settings dictionary returned by `GetSettings`.
+fn settings_from_message_item(item: &MessageItem) -> Result<ConnectionSettings> {
+    let mut groups = BTreeMap::new();
+
+    for (name, settings) in dict_entries(item)? {
+        let mut group = SettingGroup::new();
+
+        for (key, value) in dict_entries(settings)? {
+            let value = match *value {
+                MessageItem::Variant(ref inner) => &**inner,
+                ref value => value,
+            };
+
+            group.insert(key, setting_value_from_message_item(value)?);
+        }
+
+        groups.insert(name, group);
+    }
+
+    Ok(ConnectionSettings::from_groups(groups))
+}
+
+/// Converts settings to the `a{sa{sv}}` dictionary expected by `AddConnection`
+/// and `Update`.
+fn settings_to_message_item(settings: &ConnectionSettings) -> Result<MessageItem> {
+    let mut groups = Vec::new();
+
+    for (name, group) in settings.to_groups() {
+        let mut entries = Vec::new();
+
+        for (key, value) in &group {
+            entries.push(MessageItem::DictEntry(
+                Box::new(MessageItem::Str(key.clone())),
+                Box::new(MessageItem::Variant(Box::new(
+                    setting_value_to_message_item(value)?,
+                ))),
+            ));
+        }
+
+        groups.push(MessageItem::DictEntry(
+            Box::new(MessageItem::Str(name)),
+            Box::new(MessageItem::Array(entries, "{sv}".into())),
+        ));
+    }
+
+    Ok(MessageItem::Array(groups, "{sa{sv}}".into()))
+}
+
+fn dict_entries(item: &MessageItem) -> Result<Vec<(String, &MessageItem)>> {
+    let mut entries = Vec::new();
+
+    if let MessageItem::Array(ref items, _) = *item {
+        for entry in items {
+            if let MessageItem::DictEntry(ref key, ref value) = *entry {
+                if let MessageItem::Str(ref key) = **key {
+                    entries.push((key.clone(), &**value));
+                    continue;
+                }
             }
+
+            bail!(ErrorKind::DBusAPI(format!(
+                "Dictionary entry expected: {:?}",
+                entry
+            )));
         }
+
+        Ok(entries)
+    } else {
+        bail!(ErrorKind::DBusAPI(format!(
+            "Dictionary expected: {:?}",
+            item
+        )))
+    }
+}
+
+fn setting_value_to_message_item(value: &SettingValue) -> Result<MessageItem> {
+    let item = match *value {
+        SettingValue::Bool(value) => MessageItem::Bool(value),
+        SettingValue::Byte(value) => MessageItem::Byte(value),
+        SettingValue::Int16(value) => MessageItem::Int16(value),
+        SettingValue::UInt16(value) => MessageItem::UInt16(value),
+        SettingValue::Int32(value) => MessageItem::Int32(value),
+        SettingValue::UInt32(value) => MessageItem::UInt32(value),
+        SettingValue::Int64(value) => MessageItem::Int64(value),
+        SettingValue::UInt64(value) => MessageItem::UInt64(value),
+        SettingValue::Double(value) => MessageItem::Double(value),
+        SettingValue::String(ref value) => MessageItem::Str(value.clone()),
+        SettingValue::ObjectPath(ref value) => {
+            MessageItem::ObjectPath(Path::new(value.clone()).map_err(ErrorKind::DBusAPI)?)
+        }
+        SettingValue::Array(ref items, ref signature) => MessageItem::Array(
+            items
+                .iter()
+                .map(setting_value_to_message_item)
+                .collect::<Result<_>>()?,
+            signature.clone().into(),
+        ),
+        SettingValue::Struct(ref items) => MessageItem::Struct(
+            items
+                .iter()
+                .map(setting_value_to_message_item)
+                .collect::<Result<_>>()?,
+        ),
+        SettingValue::DictEntry(ref key, ref value) => MessageItem::DictEntry(
+            Box::new(setting_value_to_message_item(key)?),
+            Box::new(setting_value_to_message_item(value)?),
+        ),
+        SettingValue::Variant(ref value) => {
+            MessageItem::Variant(Box::new(setting_value_to_message_item(value)?))
+        }
+    };
+
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dbus::Message;
+
+    use std::net::Ipv4Addr;
+
+    use settings::{ConnectionSetting, Ipv4Setting, SettingValue};
+
+    fn entry(key: &str, value: MessageItem) -> MessageItem {
+        MessageItem::DictEntry(
+            Box::new(MessageItem::Str(key.into())),
+            Box::new(MessageItem::Variant(Box::new(value))),
+        )
+    }
+
+    fn group(name: &str, entries: Vec<MessageItem>) -> MessageItem {
+        MessageItem::DictEntry(
+            Box::new(MessageItem::Str(name.into())),
+            Box::new(MessageItem::Array(entries, "{sv}".into())),
+        )
+    }
+
+    #[test]
+    fn test_get_settings_round_trip() {
+        let ssid = b"home".iter().map(|b| MessageItem::Byte(*b)).collect();
+
+        let settings = MessageItem::Array(
+            vec![
+                group(
+                    "connection",
+                    vec![
+                        entry("id", MessageItem::Str("home".into())),
+                        entry("uuid", MessageItem::Str("a0b1".into())),
+                        entry("type", MessageItem::Str("802-11-wireless".into())),
+                    ],
+                ),
+                group(
+                    "802-11-wireless",
+                    vec![entry("ssid", MessageItem::Array(ssid, "y".into()))],
+                ),
+            ],
+            "{sa{sv}}".into(),
+        );
+
+        let message = Message::new_method_call(
+            NM_SERVICE_MANAGER,
+            NM_SETTINGS_PATH,
+            NM_CONNECTION_INTERFACE,
+            "GetSettings",
+        )
+        .unwrap()
+        .append1(settings);
+
+        let decoded = settings_from_message_item(&message.get_items()[0]).unwrap();
+
+        assert_eq!(decoded.id(), "home");
+        assert_eq!(decoded.uuid(), "a0b1");
+        assert_eq!(decoded.kind(), "802-11-wireless");
+        assert_eq!(decoded.ssid().unwrap().as_bytes(), b"home");
+        assert!(decoded.connection.other.is_empty());
+    }
+
+    #[test]
+    fn test_settings_message_round_trip() {
+        let mut settings = ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some("wired".into()),
+                kind: Some("802-3-ethernet".into()),
+                permissions: Some(vec![]),
+                ..Default::default()
+            },
+            ipv4: Some(Ipv4Setting {
+                method: Some("manual".into()),
+                dns: Some(vec![Ipv4Addr::new(1, 1, 1, 1)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        settings
+            .connection
+            .other
+            .insert("mptcp-flags".into(), SettingValue::UInt32(0));
+
+        let message = Message::new_method_call(
+            NM_SERVICE_MANAGER,
+            NM_SETTINGS_PATH,
+            NM_SETTINGS_INTERFACE,
+            "AddConnection",
+        )
+        .unwrap()
+        .append1(settings_to_message_item(&settings).unwrap());
+
+        let decoded = settings_from_message_item(&message.get_items()[0]).unwrap();
+
+        assert_eq!(decoded, settings);
     }
 }
@@ -1,27 +1,37 @@
 use std::fmt;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use dbus_nm::DBusNetworkManager;
+use backend::Backend;
 use errors::*;
 
+use connection::{get_device_available_connections, Connection};
+
+use dhcp_config::{Dhcp4Config, Dhcp6Config};
+use ethernet::{new_ethernet_device, EthernetDevice};
+use events::{EventStream, NetworkManagerEvent};
+use ip_config::{Ip4Config, Ip6Config};
+use manager::Connectivity;
+use modem::{new_modem_device, ModemDevice};
+
 use wifi::{new_wifi_device, WiFiDevice};
 
 #[derive(Clone)]
 pub struct Device {
-    dbus_manager: Rc<DBusNetworkManager>,
+    backend: Rc<dyn Backend>,
     path: String,
     interface: String,
     device_type: DeviceType,
 }
 
 impl Device {
-    fn init(dbus_manager: &Rc<DBusNetworkManager>, path: &str) -> Result<Self> {
-        let interface = dbus_manager.get_device_interface(path)?;
+    fn init(backend: &Rc<dyn Backend>, path: &str) -> Result<Self> {
+        let interface = backend.get_device_interface(path)?;
 
-        let device_type = dbus_manager.get_device_type(path)?;
+        let device_type = backend.get_device_type(path)?;
 
         Ok(Device {
-            dbus_manager: Rc::clone(dbus_manager),
+            backend: Rc::clone(backend),
             path: path.to_string(),
             interface,
             device_type,
@@ -37,50 +47,157 @@ impl Device {
     }
 
     pub fn get_state(&self) -> Result<DeviceState> {
-        self.dbus_manager.get_device_state(&self.path)
+        self.backend.get_device_state(&self.path)
+    }
+
+    /// Gets the current hardware (MAC) address of the device.
+    pub fn hw_address(&self) -> Result<String> {
+        self.backend.get_device_hw_address(&self.path)
+    }
+
+    /// Gets the permanent hardware address of the device, if its type has one.
+    pub fn perm_hw_address(&self) -> Result<Option<String>> {
+        self.backend.get_device_perm_hw_address(&self.path)
+    }
+
+    pub fn driver(&self) -> Result<String> {
+        self.backend.get_device_driver(&self.path)
+    }
+
+    pub fn driver_version(&self) -> Result<String> {
+        self.backend.get_device_driver_version(&self.path)
     }
 
-    pub fn as_wifi_device(&self) -> Option<WiFiDevice> {
+    pub fn firmware_version(&self) -> Result<String> {
+        self.backend.get_device_firmware_version(&self.path)
+    }
+
+    pub fn mtu(&self) -> Result<u32> {
+        self.backend.get_device_mtu(&self.path)
+    }
+
+    pub fn capabilities(&self) -> Result<DeviceCapabilities> {
+        self.backend.get_device_capabilities(&self.path)
+    }
+
+    /// Whether the device is managed by Network Manager.
+    pub fn is_managed(&self) -> Result<bool> {
+        self.backend.is_device_managed(&self.path)
+    }
+
+    /// Whether the device is allowed to connect automatically.
+    pub fn autoconnect(&self) -> Result<bool> {
+        self.backend.get_device_autoconnect(&self.path)
+    }
+
+    /// Gets the IPv4 connectivity of the device.
+    pub fn ip4_connectivity(&self) -> Result<Connectivity> {
+        self.backend.get_device_ip4_connectivity(&self.path)
+    }
+
+    /// Gets the device identifier of the operating system, e.g. its sysfs path.
+    pub fn udi(&self) -> Result<String> {
+        self.backend.get_device_udi(&self.path)
+    }
+
+    /// Whether the device exists, as opposed to being a placeholder for a
+    /// software device that has not been created yet.
+    pub fn is_real(&self) -> Result<bool> {
+        self.backend.is_device_real(&self.path)
+    }
+
+    /// Gets the saved connections that can be activated on the device.
+    pub fn available_connections(&self) -> Result<Vec<Connection>> {
+        get_device_available_connections(&self.backend, &self.path)
+    }
+
+    /// Gets the IPv4 configuration of the device. Returns `None` unless the
+    /// device is activated.
+    pub fn ip4_config(&self) -> Result<Option<Ip4Config>> {
+        self.backend.get_device_ip4_config(&self.path)
+    }
+
+    /// Gets the IPv6 configuration of the device. Returns `None` unless the
+    /// device is activated.
+    pub fn ip6_config(&self) -> Result<Option<Ip6Config>> {
+        self.backend.get_device_ip6_config(&self.path)
+    }
+
+    /// Gets the options of the current DHCPv4 lease of the device.
+    pub fn dhcp4_config(&self) -> Result<Option<Dhcp4Config>> {
+        self.backend.get_device_dhcp4_config(&self.path)
+    }
+
+    /// Gets the options of the current DHCPv6 lease of the device.
+    pub fn dhcp6_config(&self) -> Result<Option<Dhcp6Config>> {
+        self.backend.get_device_dhcp6_config(&self.path)
+    }
+
+    pub fn as_wifi_device(&self) -> Option<WiFiDevice<'_>> {
         if self.device_type == DeviceType::WiFi {
-            Some(new_wifi_device(&self.dbus_manager, self))
+            Some(new_wifi_device(&self.backend, self))
         } else {
             None
         }
     }
 
+    pub fn as_ethernet_device(&self) -> Option<EthernetDevice<'_>> {
+        if self.device_type == DeviceType::Ethernet {
+            Some(new_ethernet_device(&self.backend, self))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_modem_device(&self) -> Option<ModemDevice<'_>> {
+        if self.device_type == DeviceType::Modem {
+            Some(new_modem_device(&self.backend, self))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the reason of the last device state change.
+    pub fn get_state_reason(&self) -> Result<DeviceStateReason> {
+        self.backend.get_device_state_reason(&self.path)
+    }
+
     /// Connects a Network Manager device.
+    ///
+    /// Fails with `ErrorKind::DeviceFailed` if the device stops activating,
+    /// e.g. because the supplicant timed out or DHCP failed.
     pub fn connect(&self) -> Result<DeviceState> {
-        let state = self.get_state()?;
+        self.connect_with_timeout(Duration::from_secs(self.backend.method_timeout()))
+    }
 
-        match state {
-            DeviceState::Activated => Ok(DeviceState::Activated),
-            _ => {
-                self.dbus_manager.connect_device(&self.path)?;
+    /// Like `connect`, but waits up to `timeout` for the device to activate
+    /// instead of the method timeout. A zero timeout does not wait.
+    pub fn connect_with_timeout(&self, timeout: Duration) -> Result<DeviceState> {
+        match request_connect(self)? {
+            Some(watch) => {
+                let (state, reason) = wait(self, watch, timeout)?;
 
-                wait(
-                    self,
-                    &DeviceState::Activated,
-                    self.dbus_manager.method_timeout(),
-                )
+                connect_result(state, reason)
             }
+            None => Ok(DeviceState::Activated),
         }
     }
 
     /// Disconnect a Network Manager device.
     pub fn disconnect(&self) -> Result<DeviceState> {
-        let state = self.get_state()?;
+        self.disconnect_with_timeout(Duration::from_secs(self.backend.method_timeout()))
+    }
 
-        match state {
-            DeviceState::Disconnected => Ok(DeviceState::Disconnected),
-            _ => {
-                self.dbus_manager.disconnect_device(&self.path)?;
+    /// Like `disconnect`, but waits up to `timeout` for the device to
+    /// disconnect instead of the method timeout. A zero timeout does not wait.
+    pub fn disconnect_with_timeout(&self, timeout: Duration) -> Result<DeviceState> {
+        match request_disconnect(self)? {
+            Some(watch) => {
+                let (state, _) = wait(self, watch, timeout)?;
 
-                wait(
-                    self,
-                    &DeviceState::Disconnected,
-                    self.dbus_manager.method_timeout(),
-                )
+                Ok(state)
             }
+            None => Ok(DeviceState::Disconnected),
         }
     }
 }
@@ -201,6 +318,174 @@ pub enum DeviceState {
     Failed,
 }
 
+/// The reason of a device state change, reported by the `StateReason`
+/// property and the `StateChanged` signal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceStateReason {
+    None,
+    Unknown,
+    NowManaged,
+    NowUnmanaged,
+    ConfigFailed,
+    IpConfigUnavailable,
+    IpConfigExpired,
+    NoSecrets,
+    SupplicantDisconnect,
+    SupplicantConfigFailed,
+    SupplicantFailed,
+    SupplicantTimeout,
+    PppStartFailed,
+    PppDisconnect,
+    PppFailed,
+    DhcpStartFailed,
+    DhcpError,
+    DhcpFailed,
+    SharedStartFailed,
+    SharedFailed,
+    AutoipStartFailed,
+    AutoipError,
+    AutoipFailed,
+    ModemBusy,
+    ModemNoDialTone,
+    ModemNoCarrier,
+    ModemDialTimeout,
+    ModemDialFailed,
+    ModemInitFailed,
+    GsmApnFailed,
+    GsmRegistrationNotSearching,
+    GsmRegistrationDenied,
+    GsmRegistrationTimeout,
+    GsmRegistrationFailed,
+    GsmPinCheckFailed,
+    FirmwareMissing,
+    Removed,
+    Sleeping,
+    ConnectionRemoved,
+    UserRequested,
+    CarrierLost,
+    ConnectionAssumed,
+    SupplicantAvailable,
+    ModemNotFound,
+    BtFailed,
+    GsmSimNotInserted,
+    GsmSimPinRequired,
+    GsmSimPukRequired,
+    GsmSimWrong,
+    InfinibandMode,
+    DependencyFailed,
+    Br2684Failed,
+    ModemManagerUnavailable,
+    SsidNotFound,
+    SecondaryConnectionFailed,
+    DcbFcoeFailed,
+    TeamdControlFailed,
+    ModemFailed,
+    ModemAvailable,
+    SimPinIncorrect,
+    NewActivation,
+    ParentChanged,
+    ParentManagedChanged,
+    OvsdbFailed,
+    IpAddressDuplicate,
+    IpMethodUnsupported,
+    SriovConfigurationFailed,
+    PeerNotFound,
+}
+
+impl From<u32> for DeviceStateReason {
+    fn from(reason: u32) -> Self {
+        match reason {
+            0 => DeviceStateReason::None,
+            1 => DeviceStateReason::Unknown,
+            2 => DeviceStateReason::NowManaged,
+            3 => DeviceStateReason::NowUnmanaged,
+            4 => DeviceStateReason::ConfigFailed,
+            5 => DeviceStateReason::IpConfigUnavailable,
+            6 => DeviceStateReason::IpConfigExpired,
+            7 => DeviceStateReason::NoSecrets,
+            8 => DeviceStateReason::SupplicantDisconnect,
+            9 => DeviceStateReason::SupplicantConfigFailed,
+            10 => DeviceStateReason::SupplicantFailed,
+            11 => DeviceStateReason::SupplicantTimeout,
+            12 => DeviceStateReason::PppStartFailed,
+            13 => DeviceStateReason::PppDisconnect,
+            14 => DeviceStateReason::PppFailed,
+            15 => DeviceStateReason::DhcpStartFailed,
+            16 => DeviceStateReason::DhcpError,
+            17 => DeviceStateReason::DhcpFailed,
+            18 => DeviceStateReason::SharedStartFailed,
+            19 => DeviceStateReason::SharedFailed,
+            20 => DeviceStateReason::AutoipStartFailed,
+            21 => DeviceStateReason::AutoipError,
+            22 => DeviceStateReason::AutoipFailed,
+            23 => DeviceStateReason::ModemBusy,
+            24 => DeviceStateReason::ModemNoDialTone,
+            25 => DeviceStateReason::ModemNoCarrier,
+            26 => DeviceStateReason::ModemDialTimeout,
+            27 => DeviceStateReason::ModemDialFailed,
+            28 => DeviceStateReason::ModemInitFailed,
+            29 => DeviceStateReason::GsmApnFailed,
+            30 => DeviceStateReason::GsmRegistrationNotSearching,
+            31 => DeviceStateReason::GsmRegistrationDenied,
+            32 => DeviceStateReason::GsmRegistrationTimeout,
+            33 => DeviceStateReason::GsmRegistrationFailed,
+            34 => DeviceStateReason::GsmPinCheckFailed,
+            35 => DeviceStateReason::FirmwareMissing,
+            36 => DeviceStateReason::Removed,
+            37 => DeviceStateReason::Sleeping,
+            38 => DeviceStateReason::ConnectionRemoved,
+            39 => DeviceStateReason::UserRequested,
+            40 => DeviceStateReason::CarrierLost,
+            41 => DeviceStateReason::ConnectionAssumed,
+            42 => DeviceStateReason::SupplicantAvailable,
+            43 => DeviceStateReason::ModemNotFound,
+            44 => DeviceStateReason::BtFailed,
+            45 => DeviceStateReason::GsmSimNotInserted,
+            46 => DeviceStateReason::GsmSimPinRequired,
+            47 => DeviceStateReason::GsmSimPukRequired,
+            48 => DeviceStateReason::GsmSimWrong,
+            49 => DeviceStateReason::InfinibandMode,
+            50 => DeviceStateReason::DependencyFailed,
+            51 => DeviceStateReason::Br2684Failed,
+            52 => DeviceStateReason::ModemManagerUnavailable,
+            53 => DeviceStateReason::SsidNotFound,
+            54 => DeviceStateReason::SecondaryConnectionFailed,
+            55 => DeviceStateReason::DcbFcoeFailed,
+            56 => DeviceStateReason::TeamdControlFailed,
+            57 => DeviceStateReason::ModemFailed,
+            58 => DeviceStateReason::ModemAvailable,
+            59 => DeviceStateReason::SimPinIncorrect,
+            60 => DeviceStateReason::NewActivation,
+            61 => DeviceStateReason::ParentChanged,
+            62 => DeviceStateReason::ParentManagedChanged,
+            63 => DeviceStateReason::OvsdbFailed,
+            64 => DeviceStateReason::IpAddressDuplicate,
+            65 => DeviceStateReason::IpMethodUnsupported,
+            66 => DeviceStateReason::SriovConfigurationFailed,
+            67 => DeviceStateReason::PeerNotFound,
+            _ => {
+                warn!("Undefined device state reason: {}", reason);
+                DeviceStateReason::Unknown
+            }
+        }
+    }
+}
+
+bitflags! {
+    pub struct DeviceCapabilities: u32 {
+        // device has no special capabilities
+        const CAP_NONE                  = 0x0000_0000;
+        // NetworkManager supports this device
+        const CAP_NM_SUPPORTED          = 0x0000_0001;
+        // this device can indicate carrier status
+        const CAP_CARRIER_DETECT        = 0x0000_0002;
+        // this device is a software device
+        const CAP_IS_SOFTWARE           = 0x0000_0004;
+        // this device supports single-root I/O virtualization
+        const CAP_SRIOV                 = 0x0000_0008;
+    }
+}
+
 impl From<i64> for DeviceState {
     fn from(state: i64) -> Self {
         match state {
@@ -225,13 +510,13 @@ impl From<i64> for DeviceState {
     }
 }
 
-pub fn get_devices(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Device>> {
-    let device_paths = dbus_manager.get_devices()?;
+pub fn get_devices(backend: &Rc<dyn Backend>) -> Result<Vec<Device>> {
+    let device_paths = backend.get_devices()?;
 
     let mut result = Vec::with_capacity(device_paths.len());
 
     for path in device_paths {
-        let device = Device::init(dbus_manager, &path)?;
+        let device = Device::init(backend, &path)?;
 
         result.push(device);
     }
@@ -239,25 +524,22 @@ pub fn get_devices(dbus_manager: &Rc<DBusNetworkManager>) -> Result<Vec<Device>>
     Ok(result)
 }
 
-pub fn get_device_by_interface(
-    dbus_manager: &Rc<DBusNetworkManager>,
-    interface: &str,
-) -> Result<Device> {
-    let path = dbus_manager.get_device_by_interface(interface)?;
+pub fn get_device_by_interface(backend: &Rc<dyn Backend>, interface: &str) -> Result<Device> {
+    let path = backend.get_device_by_interface(interface)?;
 
-    Device::init(dbus_manager, &path)
+    Device::init(backend, &path)
 }
 
 pub fn get_active_connection_devices(
-    dbus_manager: &Rc<DBusNetworkManager>,
+    backend: &Rc<dyn Backend>,
     active_path: &str,
 ) -> Result<Vec<Device>> {
-    let device_paths = dbus_manager.get_active_connection_devices(active_path)?;
+    let device_paths = backend.get_active_connection_devices(active_path)?;
 
     let mut result = Vec::with_capacity(device_paths.len());
 
     for path in device_paths {
-        let device = Device::init(dbus_manager, &path)?;
+        let device = Device::init(backend, &path)?;
 
         result.push(device);
     }
@@ -265,41 +547,360 @@ pub fn get_active_connection_devices(
     Ok(result)
 }
 
-fn wait(device: &Device, target_state: &DeviceState, timeout: u64) -> Result<DeviceState> {
-    if timeout == 0 {
-        return device.get_state();
+/// Starts connecting `device`. Returns `None` if it is already activated.
+pub fn request_connect(device: &Device) -> Result<Option<DeviceStateWatch>> {
+    if device.get_state()? == DeviceState::Activated {
+        return Ok(None);
+    }
+
+    let events = device.backend.subscribe()?;
+
+    device.backend.connect_device(&device.path)?;
+
+    Ok(Some(DeviceStateWatch::new(
+        events,
+        device,
+        DeviceState::Activated,
+    )))
+}
+
+/// Starts disconnecting `device`. Returns `None` if it is already
+/// disconnected.
+pub fn request_disconnect(device: &Device) -> Result<Option<DeviceStateWatch>> {
+    if device.get_state()? == DeviceState::Disconnected {
+        return Ok(None);
+    }
+
+    let events = device.backend.subscribe()?;
+
+    device.backend.disconnect_device(&device.path)?;
+
+    Ok(Some(DeviceStateWatch::new(
+        events,
+        device,
+        DeviceState::Disconnected,
+    )))
+}
+
+/// The result of waiting for a device to connect, failing with
+/// `ErrorKind::DeviceFailed` if it stopped activating.
+pub fn connect_result(state: DeviceState, reason: DeviceStateReason) -> Result<DeviceState> {
+    if is_final_state(&state, reason, &DeviceState::Activated, true) {
+        bail!(ErrorKind::DeviceFailed(state, reason));
     }
 
-    debug!("Waiting for device state: {:?}", target_state);
+    Ok(state)
+}
 
-    let mut total_time = 0;
+/// Follows the state change signals of a device until it reaches its target
+/// state or a state that ends the transition.
+pub struct DeviceStateWatch {
+    events: EventStream,
+    path: String,
+    target_state: DeviceState,
+    // A device switching connections passes through `Disconnected` before
+    // the new activation starts
+    activation_started: bool,
+}
 
-    loop {
-        ::std::thread::sleep(::std::time::Duration::from_secs(1));
+impl DeviceStateWatch {
+    fn new(events: EventStream, device: &Device, target_state: DeviceState) -> Self {
+        DeviceStateWatch {
+            events,
+            path: device.path.clone(),
+            target_state,
+            activation_started: false,
+        }
+    }
 
-        let state = device.get_state()?;
+    /// Handles the events arriving within `timeout`. Returns the state the
+    /// transition ended in and its reason, or `None` if the device is still
+    /// changing after `timeout`. A zero timeout only handles the events
+    /// already received.
+    pub fn next(&mut self, timeout: Duration) -> Result<Option<(DeviceState, DeviceStateReason)>> {
+        let start = Instant::now();
+
+        loop {
+            let remaining = timeout
+                .checked_sub(start.elapsed())
+                .unwrap_or_else(|| Duration::from_secs(0));
+
+            let event = match self.events.next_timeout(remaining)? {
+                Some(event) => event,
+                None => return Ok(None),
+            };
+
+            if let Some(result) = self.handle(&event) {
+                return Ok(Some(result));
+            }
+        }
+    }
+
+    fn handle(&mut self, event: &NetworkManagerEvent) -> Option<(DeviceState, DeviceStateReason)> {
+        let (state, reason) = match *event {
+            NetworkManagerEvent::DeviceStateChanged {
+                ref path,
+                ref new_state,
+                reason,
+                ..
+            } if *path == self.path => (new_state.clone(), reason),
+            _ => return None,
+        };
+
+        self.activation_started |= is_activating_state(&state);
 
-        total_time += 1;
+        if state == self.target_state {
+            debug!("Device target state reached: {:?}", state);
 
-        if state == *target_state {
+            return Some((state, reason));
+        } else if is_final_state(&state, reason, &self.target_state, self.activation_started) {
             debug!(
-                "Device target state reached: {:?} / {}s elapsed",
-                state, total_time
+                "Device stopped waiting for state ({:?}): {:?} ({:?})",
+                self.target_state, state, reason
             );
 
-            return Ok(state);
-        } else if total_time >= timeout {
+            return Some((state, reason));
+        }
+
+        debug!(
+            "Still waiting for device state ({:?}): {:?}",
+            self.target_state, state
+        );
+
+        None
+    }
+}
+
+/// Waits up to `timeout` for `watch` to end, returning the current state of
+/// the device if it is still changing after that.
+fn wait(
+    device: &Device,
+    mut watch: DeviceStateWatch,
+    timeout: Duration,
+) -> Result<(DeviceState, DeviceStateReason)> {
+    if timeout == Duration::from_secs(0) {
+        return Ok((device.get_state()?, device.get_state_reason()?));
+    }
+
+    debug!("Waiting for device state: {:?}", watch.target_state);
+
+    let start = Instant::now();
+
+    let state = device.get_state()?;
+
+    if state == watch.target_state {
+        debug!("Device target state already reached: {:?}", state);
+
+        return Ok((state, device.get_state_reason()?));
+    }
+
+    match watch.next(timeout)? {
+        Some(result) => Ok(result),
+        None => {
+            let state = device.get_state()?;
+
             debug!(
-                "Timeout reached in waiting for device state ({:?}): {:?} / {}s elapsed",
-                target_state, state, total_time
+                "Timeout reached in waiting for device state ({:?}): {:?} / {:?} elapsed",
+                watch.target_state,
+                state,
+                start.elapsed()
             );
 
-            return Ok(state);
+            Ok((state, device.get_state_reason()?))
         }
+    }
+}
 
-        debug!(
-            "Still waiting for device state ({:?}): {:?} / {}s elapsed",
-            target_state, state, total_time
+/// Whether a device that changed to `state` can no longer reach
+/// `target_state` without another request.
+///
+/// `Disconnected` only ends an activation once it started, or if the device
+/// was disconnected for another reason than the new activation.
+fn is_final_state(
+    state: &DeviceState,
+    reason: DeviceStateReason,
+    target_state: &DeviceState,
+    activation_started: bool,
+) -> bool {
+    match *state {
+        DeviceState::Unmanaged | DeviceState::Unavailable | DeviceState::Failed => true,
+        DeviceState::Disconnected => {
+            *target_state == DeviceState::Activated
+                && (activation_started || !is_activation_reason(reason))
+        }
+        _ => false,
+    }
+}
+
+fn is_activating_state(state: &DeviceState) -> bool {
+    matches!(
+        *state,
+        DeviceState::Prepare
+            | DeviceState::Config
+            | DeviceState::NeedAuth
+            | DeviceState::IpConfig
+            | DeviceState::IpCheck
+            | DeviceState::Secondaries
+    )
+}
+
+/// Whether `reason` is reported when a device disconnects on the way to a new
+/// activation rather than because of a failure.
+fn is_activation_reason(reason: DeviceStateReason) -> bool {
+    matches!(
+        reason,
+        DeviceStateReason::None | DeviceStateReason::Unknown | DeviceStateReason::NewActivation
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+    use std::mem;
+
+    use events::EventSource;
+    use fake::{fixtures, FakeNetworkManager};
+
+    /// Replays device state changes recorded from Network Manager.
+    struct Replay(VecDeque<NetworkManagerEvent>);
+
+    impl EventSource for Replay {
+        fn next_event(&mut self, _timeout: Duration) -> Result<Option<NetworkManagerEvent>> {
+            Ok(self.0.pop_front())
+        }
+    }
+
+    fn wait_for_activation(
+        device: &Device,
+        changes: &[(DeviceState, DeviceStateReason)],
+    ) -> (DeviceState, DeviceStateReason) {
+        let mut old_state = DeviceState::Activated;
+
+        let events = changes
+            .iter()
+            .map(|(state, reason)| NetworkManagerEvent::DeviceStateChanged {
+                path: device.path.clone(),
+                new_state: state.clone(),
+                old_state: mem::replace(&mut old_state, state.clone()),
+                reason: *reason,
+            })
+            .collect();
+
+        let events = EventStream::new(Box::new(Replay(events)));
+
+        wait(
+            device,
+            DeviceStateWatch::new(events, device, DeviceState::Activated),
+            Duration::from_secs(1),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_wait_switching_connections() {
+        let fake = Rc::new(FakeNetworkManager::new());
+        fake.add_device("wlan0", DeviceType::WiFi);
+
+        let backend: Rc<dyn Backend> = fake;
+        let device = get_device_by_interface(&backend, "wlan0").unwrap();
+
+        let switched = [
+            (DeviceState::Deactivating, DeviceStateReason::NewActivation),
+            (DeviceState::Disconnected, DeviceStateReason::NewActivation),
+            (DeviceState::Prepare, DeviceStateReason::None),
+            (DeviceState::Config, DeviceStateReason::None),
+            (DeviceState::IpConfig, DeviceStateReason::None),
+            (DeviceState::Activated, DeviceStateReason::None),
+        ];
+
+        assert_eq!(
+            wait_for_activation(&device, &switched),
+            (DeviceState::Activated, DeviceStateReason::None)
+        );
+
+        let abandoned = [
+            (DeviceState::Prepare, DeviceStateReason::None),
+            (DeviceState::Config, DeviceStateReason::None),
+            (DeviceState::Disconnected, DeviceStateReason::None),
+            (DeviceState::Prepare, DeviceStateReason::None),
+        ];
+
+        assert_eq!(
+            wait_for_activation(&device, &abandoned),
+            (DeviceState::Disconnected, DeviceStateReason::None)
+        );
+
+        let disconnected = [
+            (DeviceState::Deactivating, DeviceStateReason::UserRequested),
+            (DeviceState::Disconnected, DeviceStateReason::UserRequested),
+            (DeviceState::Prepare, DeviceStateReason::None),
+        ];
+
+        assert_eq!(
+            wait_for_activation(&device, &disconnected),
+            (DeviceState::Disconnected, DeviceStateReason::UserRequested)
+        );
+    }
+
+    #[test]
+    fn test_device_properties() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        fake.set_device_hw_address(&device_path, "00:11:22:33:44:55");
+        fake.set_device_driver(&device_path, "brcmfmac");
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+
+        assert_eq!(device.hw_address().unwrap(), "00:11:22:33:44:55");
+        assert_eq!(
+            device.perm_hw_address().unwrap(),
+            Some("00:11:22:33:44:55".to_string())
         );
+        assert_eq!(device.driver().unwrap(), "brcmfmac");
+        assert!(device
+            .capabilities()
+            .unwrap()
+            .contains(DeviceCapabilities::CAP_NM_SUPPORTED));
+        assert!(device.is_managed().unwrap());
+
+        manager
+            .add_connection(&fixtures::wpa_settings("office", "password"))
+            .unwrap();
+        manager
+            .add_connection(&fixtures::wpa_settings("home", "password"))
+            .unwrap();
+
+        let available = device.available_connections().unwrap();
+        assert_eq!(available.len(), 1);
+        assert_eq!(available[0].settings().id(), "office");
+
+        fake.set_device_managed(&device_path, false);
+
+        assert!(!device.is_managed().unwrap());
+        assert_eq!(device.get_state().unwrap(), DeviceState::Unmanaged);
+        assert!(device.available_connections().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_device_connect_failure_reason() {
+        let (_, manager, _) = fixtures::wifi();
+
+        manager
+            .add_connection(&fixtures::wpa_settings("office", "wrong password"))
+            .unwrap();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+
+        match device.connect() {
+            Err(Error(ErrorKind::DeviceFailed(state, reason), _)) => {
+                assert_eq!(state, DeviceState::Failed);
+                assert_eq!(reason, DeviceStateReason::NoSecrets);
+            }
+            result => panic!("Unexpected connect result: {:?}", result),
+        }
+
+        assert_eq!(device.get_state().unwrap(), DeviceState::Disconnected);
     }
 }
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use settings::SettingGroup;
+
+/// The options of a DHCPv4 lease, as reported by a `DHCP4Config` object.
+///
+/// Well known options are decoded into fields, while `options` keeps all of
+/// them as received, e.g. `dhcp_lease_time` or `routers`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dhcp4Config {
+    /// Lease time in seconds.
+    pub lease_time: Option<u32>,
+    pub server_identifier: Option<Ipv4Addr>,
+    pub ntp_servers: Vec<Ipv4Addr>,
+    pub domain_name: Option<String>,
+    /// The raw payload of the vendor specific information (option 43).
+    pub vendor_options: Option<Vec<u8>>,
+    pub options: BTreeMap<String, String>,
+}
+
+impl Dhcp4Config {
+    pub fn from_options(options: BTreeMap<String, String>) -> Self {
+        Dhcp4Config {
+            lease_time: parse_option(&options, "dhcp_lease_time"),
+            server_identifier: parse_option(&options, "dhcp_server_identifier"),
+            ntp_servers: parse_list(&options, "ntp_servers"),
+            domain_name: options.get("domain_name").cloned(),
+            vendor_options: options
+                .get("vendor_encapsulated_options")
+                .and_then(|value| parse_hex(value)),
+            options,
+        }
+    }
+}
+
+/// The options of a DHCPv6 lease, as reported by a `DHCP6Config` object.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dhcp6Config {
+    /// The server DUID in colon separated hex notation.
+    pub server_id: Option<String>,
+    pub name_servers: Vec<Ipv6Addr>,
+    pub domain_search: Vec<String>,
+    pub ntp_servers: Vec<Ipv6Addr>,
+    pub options: BTreeMap<String, String>,
+}
+
+impl Dhcp6Config {
+    pub fn from_options(options: BTreeMap<String, String>) -> Self {
+        Dhcp6Config {
+            server_id: options.get("dhcp6_server_id").cloned(),
+            name_servers: parse_list(&options, "dhcp6_name_servers"),
+            domain_search: parse_list(&options, "dhcp6_domain_search"),
+            ntp_servers: parse_list(&options, "dhcp6_ntp_servers"),
+            options,
+        }
+    }
+}
+
+/// Converts the `Options` property of a `DHCP4Config` or `DHCP6Config`
+/// object. Network Manager reports all options as strings.
+pub fn dhcp_options(options: &SettingGroup) -> Option<BTreeMap<String, String>> {
+    options
+        .iter()
+        .map(|(name, value)| {
+            value
+                .as_str()
+                .map(|value| (name.clone(), value.to_string()))
+        })
+        .collect()
+}
+
+fn parse_option<T: FromStr>(options: &BTreeMap<String, String>, name: &str) -> Option<T> {
+    options
+        .get(name)
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Parses a space separated list, skipping invalid entries.
+fn parse_list<T: FromStr>(options: &BTreeMap<String, String>, name: &str) -> Vec<T> {
+    match options.get(name) {
+        Some(value) => value
+            .split_whitespace()
+            .filter_map(|item| item.parse().ok())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Parses bytes in colon separated hex notation, e.g. `01:04:c0:a8:01:01`.
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    value
+        .trim()
+        .split(':')
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use fake::fixtures;
+    use hotspot::HotspotConfig;
+    use settings::SettingValue;
+
+    fn options(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_dhcp4_config_from_options() {
+        let config = Dhcp4Config::from_options(options(&[
+            ("dhcp_lease_time", "86400"),
+            ("dhcp_server_identifier", "192.168.1.1"),
+            ("ntp_servers", "192.168.1.1 10.0.0.1"),
+            ("domain_name", "lan"),
+            ("vendor_encapsulated_options", "01:04:c0:a8:01:0a"),
+            ("routers", "192.168.1.1"),
+        ]));
+
+        assert_eq!(config.lease_time, Some(86400));
+        assert_eq!(
+            config.server_identifier,
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(
+            config.ntp_servers,
+            vec![Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(10, 0, 0, 1)]
+        );
+        assert_eq!(config.domain_name, Some("lan".to_string()));
+        assert_eq!(
+            config.vendor_options,
+            Some(vec![0x01, 0x04, 0xc0, 0xa8, 0x01, 0x0a])
+        );
+        assert_eq!(config.options["routers"], "192.168.1.1");
+    }
+
+    #[test]
+    fn test_dhcp6_config_from_options() {
+        let mut dict = SettingGroup::new();
+        dict.insert(
+            "dhcp6_name_servers".into(),
+            SettingValue::String("2001:db8::1 2001:db8::2".into()),
+        );
+        dict.insert(
+            "dhcp6_domain_search".into(),
+            SettingValue::String("lan example.com".into()),
+        );
+
+        let config = Dhcp6Config::from_options(dhcp_options(&dict).unwrap());
+
+        assert_eq!(config.name_servers.len(), 2);
+        assert_eq!(
+            config.domain_search,
+            vec!["lan".to_string(), "example.com".to_string()]
+        );
+        assert_eq!(config.server_id, None);
+
+        dict.insert("dhcp6_rebinding".into(), SettingValue::UInt32(3600));
+
+        assert_eq!(dhcp_options(&dict), None);
+    }
+
+    #[test]
+    fn test_dhcp_config() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        let mut options = ::std::collections::BTreeMap::new();
+        options.insert("ntp_servers".to_string(), "192.168.1.1".to_string());
+        fake.set_dhcp4_config(&device_path, Some(Dhcp4Config::from_options(options)));
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        assert_eq!(device.dhcp4_config().unwrap(), None);
+
+        device
+            .as_wifi_device()
+            .unwrap()
+            .create_hotspot("hotspot", &HotspotConfig::new())
+            .unwrap();
+
+        let config = device.dhcp4_config().unwrap().unwrap();
+        assert_eq!(config.ntp_servers, vec![Ipv4Addr::new(192, 168, 1, 1)]);
+        assert_eq!(device.dhcp6_config().unwrap(), None);
+    }
+}
@@ -21,6 +21,34 @@ error_chain! {
             display("{}", info)
         }
 
+        DeviceFailed(state: ::device::DeviceState, reason: ::device::DeviceStateReason) {
+            description("Device failed")
+            display("Device failed in state {:?}: {:?}", state, reason)
+        }
+
+        ActivationFailed(
+            reason: ::connection::ActiveConnectionStateReason,
+            device_reason: ::device::DeviceStateReason
+        ) {
+            description("Connection activation failed")
+            display("Connection activation failed: {:?} (device: {:?})", reason, device_reason)
+        }
+
+        Ieee8021x(info: String) {
+            description("Invalid 802.1X credentials")
+            display("802.1X error: {}", info)
+        }
+
+        ScanNotAllowed(info: String) {
+            description("Scan not allowed")
+            display("Scan not allowed: {}", info)
+        }
+
+        Keyfile(info: String) {
+            description("Invalid keyfile")
+            display("Keyfile error: {}", info)
+        }
+
         DBusAPI(info: String) {
             description("D-Bus API error")
             display("D-Bus failure: {}", info)
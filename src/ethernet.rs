@@ -0,0 +1,270 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::rc::Rc;
+
+use backend::Backend;
+use errors::*;
+
+use connection::{add_and_activate_connection, add_connection, Connection, ConnectionState};
+use device::{Device, PathGetter};
+use eap::Ieee8021xCredentials;
+use settings::{
+    ConnectionSetting, ConnectionSettings, EthernetSetting, IpAddress, Ipv4Setting,
+    ETHERNET_SETTING,
+};
+
+pub struct EthernetDevice<'a> {
+    backend: Rc<dyn Backend>,
+    device: &'a Device,
+}
+
+impl<'a> EthernetDevice<'a> {
+    /// Whether a cable is plugged in and the link is up.
+    pub fn carrier(&self) -> Result<bool> {
+        self.backend.get_wired_carrier(self.device.path())
+    }
+
+    /// The link speed in Mb/s, 0 if unknown.
+    pub fn speed(&self) -> Result<u32> {
+        self.backend.get_wired_speed(self.device.path())
+    }
+
+    pub fn hw_address(&self) -> Result<String> {
+        self.device.hw_address()
+    }
+
+    /// The subchannels of IBM s390 network devices. Empty on other hardware.
+    pub fn s390_subchannels(&self) -> Result<Vec<String>> {
+        self.backend.get_wired_s390_subchannels(self.device.path())
+    }
+
+    /// Saves a wired connection profile bound to the device, without
+    /// activating it.
+    pub fn add_connection(&self, config: &EthernetConfig) -> Result<Connection> {
+        let settings = config.to_settings(self.device.interface())?;
+
+        add_connection(&self.backend, &settings, true)
+    }
+
+    /// Creates a wired connection profile and activates it on the device.
+    pub fn connect(&self, config: &EthernetConfig) -> Result<(Connection, ConnectionState)> {
+        let settings = config.to_settings(self.device.interface())?;
+
+        add_and_activate_connection(&self.backend, self.device.path(), &settings)
+    }
+}
+
+pub fn new_ethernet_device<'a>(
+    backend: &Rc<dyn Backend>,
+    device: &'a Device,
+) -> EthernetDevice<'a> {
+    EthernetDevice {
+        backend: Rc::clone(backend),
+        device,
+    }
+}
+
+/// The options of a wired connection created with `EthernetDevice::connect`.
+///
+/// The default is a DHCP connection that is activated automatically:
+///
+/// ```
+/// use std::net::Ipv4Addr;
+///
+/// use network_manager::{EthernetConfig, Ieee8021xCredentials};
+///
+/// let config = EthernetConfig::new()
+///     .address(Ipv4Addr::new(192, 168, 1, 10), 24)
+///     .gateway(Ipv4Addr::new(192, 168, 1, 1))
+///     .dns(&[Ipv4Addr::new(192, 168, 1, 1)])
+///     .ieee8021x(Ieee8021xCredentials::peap("device", "password"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthernetConfig {
+    id: Option<String>,
+    address: Option<(Ipv4Addr, u32)>,
+    gateway: Option<Ipv4Addr>,
+    dns: Vec<Ipv4Addr>,
+    ieee8021x: Option<Ieee8021xCredentials>,
+    autoconnect: bool,
+}
+
+impl Default for EthernetConfig {
+    fn default() -> Self {
+        EthernetConfig {
+            id: None,
+            address: None,
+            gateway: None,
+            dns: Vec::new(),
+            ieee8021x: None,
+            autoconnect: true,
+        }
+    }
+}
+
+impl EthernetConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The connection id. Defaults to the interface name.
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// A static IPv4 address with its prefix length instead of DHCP.
+    pub fn address(mut self, address: Ipv4Addr, prefix: u32) -> Self {
+        self.address = Some((address, prefix));
+        self
+    }
+
+    /// The default gateway of a static address.
+    pub fn gateway(mut self, gateway: Ipv4Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// DNS servers, used in addition to the ones from DHCP.
+    pub fn dns(mut self, servers: &[Ipv4Addr]) -> Self {
+        self.dns = servers.to_vec();
+        self
+    }
+
+    /// Authenticates to the switch port with 802.1X.
+    pub fn ieee8021x(mut self, credentials: Ieee8021xCredentials) -> Self {
+        self.ieee8021x = Some(credentials);
+        self
+    }
+
+    /// Activates the connection automatically when a cable is plugged in.
+    /// Defaults to `true`.
+    pub fn autoconnect(mut self, autoconnect: bool) -> Self {
+        self.autoconnect = autoconnect;
+        self
+    }
+
+    /// Builds the settings of the wired connection bound to `interface`.
+    pub fn to_settings(&self, interface: &str) -> Result<ConnectionSettings> {
+        let mut ipv4 = match self.address {
+            Some((_, prefix)) if prefix == 0 || prefix > 32 => bail!(ErrorKind::NetworkManager(
+                format!("Invalid prefix length: {}", prefix)
+            )),
+            Some((address, prefix)) => Ipv4Setting {
+                method: Some("manual".into()),
+                address_data: Some(vec![IpAddress {
+                    address: IpAddr::V4(address),
+                    prefix,
+                    attributes: Default::default(),
+                }]),
+                gateway: self.gateway,
+                ..Default::default()
+            },
+            None if self.gateway.is_some() => bail!(ErrorKind::NetworkManager(
+                "A gateway requires a static address".into()
+            )),
+            None => Ipv4Setting {
+                method: Some("auto".into()),
+                ..Default::default()
+            },
+        };
+
+        if !self.dns.is_empty() {
+            ipv4.dns = Some(self.dns.clone());
+        }
+
+        let ieee8021x = match self.ieee8021x {
+            Some(ref credentials) => Some(credentials.to_setting()?),
+            None => None,
+        };
+
+        Ok(ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some(self.id.clone().unwrap_or_else(|| interface.to_string())),
+                kind: Some(ETHERNET_SETTING.into()),
+                interface_name: Some(interface.into()),
+                autoconnect: Some(self.autoconnect),
+                ..Default::default()
+            },
+            ethernet: Some(EthernetSetting::default()),
+            ieee8021x,
+            ipv4: Some(ipv4),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use device::DeviceType;
+    use fake::FakeNetworkManager;
+    use manager::{Connectivity, NetworkManager};
+
+    #[test]
+    fn test_static_settings() {
+        let settings = EthernetConfig::new()
+            .address(Ipv4Addr::new(192, 168, 1, 10), 24)
+            .gateway(Ipv4Addr::new(192, 168, 1, 1))
+            .dns(&[Ipv4Addr::new(1, 1, 1, 1)])
+            .ieee8021x(Ieee8021xCredentials::peap("device", "password"))
+            .to_settings("eth0")
+            .unwrap();
+
+        assert_eq!(settings.id(), "eth0");
+        assert_eq!(settings.kind(), ETHERNET_SETTING);
+
+        let ipv4 = settings.ipv4.unwrap();
+        assert_eq!(ipv4.method, Some("manual".into()));
+        assert_eq!(ipv4.gateway, Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(ipv4.address_data.unwrap()[0].prefix, 24);
+        assert_eq!(ipv4.dns, Some(vec![Ipv4Addr::new(1, 1, 1, 1)]));
+
+        let ieee8021x = settings.ieee8021x.unwrap();
+        assert_eq!(ieee8021x.eap, Some(vec!["peap".to_string()]));
+        assert_eq!(ieee8021x.identity, Some("device".into()));
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        assert!(EthernetConfig::new()
+            .gateway(Ipv4Addr::new(192, 168, 1, 1))
+            .to_settings("eth0")
+            .is_err());
+        assert!(EthernetConfig::new()
+            .address(Ipv4Addr::new(192, 168, 1, 10), 33)
+            .to_settings("eth0")
+            .is_err());
+    }
+
+    #[test]
+    fn test_connect() {
+        let fake = Rc::new(FakeNetworkManager::new());
+        let path = fake.add_device("eth0", DeviceType::Ethernet);
+        fake.add_device("wlan0", DeviceType::WiFi);
+
+        let manager = NetworkManager::with_backend(fake.clone());
+
+        let wifi = manager.get_device_by_interface("wlan0").unwrap();
+        assert!(wifi.as_ethernet_device().is_none());
+
+        let device = manager.get_device_by_interface("eth0").unwrap();
+        let ethernet = device.as_ethernet_device().unwrap();
+
+        assert!(ethernet.carrier().unwrap());
+        assert_eq!(ethernet.speed().unwrap(), 1000);
+
+        let config = EthernetConfig::new().ieee8021x(Ieee8021xCredentials::peap("device", "pw"));
+        let (connection, state) = ethernet.connect(&config).unwrap();
+
+        assert_eq!(state, ConnectionState::Activated);
+        assert_eq!(connection.settings().kind(), ETHERNET_SETTING);
+        assert_eq!(manager.get_connectivity().unwrap(), Connectivity::Full);
+
+        fake.set_device_carrier(&path, false);
+
+        assert!(!ethernet.carrier().unwrap());
+        assert!(manager.get_active_connections().unwrap().is_empty());
+        assert!(connection.activate().is_err());
+    }
+}
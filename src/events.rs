@@ -0,0 +1,428 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+
+use dbus::arg::{Array, Dict, Iter, Variant};
+use dbus::{Message, Path};
+
+use dbus_api::DBusApi;
+use dbus_nm::{
+    signal_match_rules, DBUS_PROPERTIES_INTERFACE, NM_ACTIVE_INTERFACE, NM_DEVICE_INTERFACE,
+    NM_SERVICE_INTERFACE, NM_SERVICE_PATH,
+};
+use errors::*;
+
+use connection::{ActiveConnectionStateReason, ConnectionState};
+use device::{DeviceState, DeviceStateReason};
+use manager::NetworkManagerState;
+
+const BLOCKING_RECEIVE_TIMEOUT: u64 = 60;
+
+/// A change reported by Network Manager through D-Bus signals.
+///
+/// Devices and active connections are identified by their D-Bus object paths.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkManagerEvent {
+    StateChanged(NetworkManagerState),
+    DeviceAdded(String),
+    DeviceRemoved(String),
+    DeviceStateChanged {
+        path: String,
+        new_state: DeviceState,
+        old_state: DeviceState,
+        reason: DeviceStateReason,
+    },
+    ActiveConnectionAdded(String),
+    ActiveConnectionRemoved(String),
+    ActiveConnectionStateChanged {
+        path: String,
+        state: ConnectionState,
+        reason: ActiveConnectionStateReason,
+    },
+    PropertiesChanged {
+        path: String,
+        interface: String,
+        properties: Vec<String>,
+    },
+}
+
+/// A source of Network Manager events, provided by a `Backend`.
+pub trait EventSource {
+    /// Waits up to `timeout` for the next event. Returns `Ok(None)` if no
+    /// event arrived in time.
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<NetworkManagerEvent>>;
+}
+
+/// A subscription to Network Manager events.
+///
+/// Events are queued from the moment the stream is created until they are
+/// consumed.
+pub struct EventStream {
+    source: Box<dyn EventSource>,
+}
+
+impl EventStream {
+    pub fn new(source: Box<dyn EventSource>) -> Self {
+        EventStream { source }
+    }
+
+    /// Waits up to `timeout` for the next event. Returns `Ok(None)` if no
+    /// event arrived in time.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Result<Option<NetworkManagerEvent>> {
+        self.source.next_event(timeout)
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = Result<NetworkManagerEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_timeout(Duration::from_secs(BLOCKING_RECEIVE_TIMEOUT)) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+type EventQueue = Rc<RefCell<VecDeque<NetworkManagerEvent>>>;
+
+/// Hands the signals received on the D-Bus connection of a backend out to
+/// all event streams subscribed through it.
+///
+/// The signal match rules are only added while there are subscribers, so
+/// that signals do not pile up unread on the connection.
+pub struct SignalDispatcher {
+    dbus: Rc<DBusApi>,
+    subscribers: Vec<Weak<RefCell<VecDeque<NetworkManagerEvent>>>>,
+    active_connections: Vec<String>,
+}
+
+impl SignalDispatcher {
+    pub fn new(dbus: Rc<DBusApi>) -> Self {
+        SignalDispatcher {
+            dbus,
+            subscribers: Vec::new(),
+            active_connections: Vec::new(),
+        }
+    }
+
+    fn subscribe(&mut self, queue: &EventQueue) -> Result<()> {
+        self.subscribers.retain(|queue| queue.upgrade().is_some());
+
+        if self.subscribers.is_empty() {
+            for rule in &signal_match_rules() {
+                self.dbus.add_match(rule)?;
+            }
+
+            self.active_connections =
+                self.dbus
+                    .property(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "ActiveConnections")?;
+        } else {
+            // Signals received before subscribing are only for the existing
+            // subscribers
+            while self.dispatch(Duration::from_secs(0)) {}
+        }
+
+        self.subscribers.push(Rc::downgrade(queue));
+
+        Ok(())
+    }
+
+    fn unsubscribe(&mut self, queue: &EventQueue) {
+        self.subscribers
+            .retain(|subscriber| match subscriber.upgrade() {
+                Some(ref subscriber) => !Rc::ptr_eq(subscriber, queue),
+                None => false,
+            });
+
+        if !self.subscribers.is_empty() {
+            return;
+        }
+
+        for rule in &signal_match_rules() {
+            if let Err(error) = self.dbus.remove_match(rule) {
+                warn!("{}", error);
+            }
+        }
+
+        while self.dispatch(Duration::from_secs(0)) {}
+    }
+
+    /// Waits up to `timeout` for the next signal and queues its events for
+    /// all subscribers. Returns `false` if no signal arrived in time.
+    fn dispatch(&mut self, timeout: Duration) -> bool {
+        let message = match self.dbus.receive_signal(timeout) {
+            Some(message) => message,
+            None => return false,
+        };
+
+        let events = decode_signal(&message, &mut self.active_connections);
+
+        for subscriber in self.subscribers.iter().filter_map(Weak::upgrade) {
+            subscriber.borrow_mut().extend(events.iter().cloned());
+        }
+
+        true
+    }
+}
+
+/// Events received over the D-Bus connection of the backend.
+struct DBusEventSource {
+    dispatcher: Rc<RefCell<SignalDispatcher>>,
+    queue: EventQueue,
+}
+
+impl EventSource for DBusEventSource {
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<NetworkManagerEvent>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(event) = self.queue.borrow_mut().pop_front() {
+                return Ok(Some(event));
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if !self.dispatcher.borrow_mut().dispatch(remaining) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl Drop for DBusEventSource {
+    fn drop(&mut self) {
+        self.dispatcher.borrow_mut().unsubscribe(&self.queue);
+    }
+}
+
+pub fn new_event_stream(dispatcher: &Rc<RefCell<SignalDispatcher>>) -> Result<EventStream> {
+    let queue: EventQueue = Rc::new(RefCell::new(VecDeque::new()));
+
+    dispatcher.borrow_mut().subscribe(&queue)?;
+
+    Ok(EventStream::new(Box::new(DBusEventSource {
+        dispatcher: Rc::clone(dispatcher),
+        queue,
+    })))
+}
+
+fn decode_signal(
+    message: &Message,
+    active_connections: &mut Vec<String>,
+) -> Vec<NetworkManagerEvent> {
+    let (path, interface, member) = match (message.path(), message.interface(), message.member()) {
+        (Some(path), Some(interface), Some(member)) => {
+            (path.to_string(), interface.to_string(), member.to_string())
+        }
+        _ => return vec![],
+    };
+
+    let mut events = Vec::new();
+
+    match (&interface as &str, &member as &str) {
+        (NM_SERVICE_INTERFACE, "StateChanged") => {
+            if let Some(state) = message.get1::<u32>() {
+                events.push(NetworkManagerEvent::StateChanged(
+                    NetworkManagerState::from(state),
+                ));
+            }
+        }
+        (NM_SERVICE_INTERFACE, "DeviceAdded") => {
+            if let Some(device) = message.get1::<Path>() {
+                events.push(NetworkManagerEvent::DeviceAdded(device.to_string()));
+            }
+        }
+        (NM_SERVICE_INTERFACE, "DeviceRemoved") => {
+            if let Some(device) = message.get1::<Path>() {
+                events.push(NetworkManagerEvent::DeviceRemoved(device.to_string()));
+            }
+        }
+        (NM_DEVICE_INTERFACE, "StateChanged") => {
+            if let (Some(new_state), Some(old_state), Some(reason)) =
+                message.get3::<u32, u32, u32>()
+            {
+                events.push(NetworkManagerEvent::DeviceStateChanged {
+                    path,
+                    new_state: DeviceState::from(i64::from(new_state)),
+                    old_state: DeviceState::from(i64::from(old_state)),
+                    reason: DeviceStateReason::from(reason),
+                });
+            }
+        }
+        (NM_ACTIVE_INTERFACE, "StateChanged") => {
+            if let (Some(state), Some(reason)) = message.get2::<u32, u32>() {
+                events.push(NetworkManagerEvent::ActiveConnectionStateChanged {
+                    path,
+                    state: ConnectionState::from(i64::from(state)),
+                    reason: ActiveConnectionStateReason::from(reason),
+                });
+            }
+        }
+        (DBUS_PROPERTIES_INTERFACE, "PropertiesChanged") => {
+            if let (Some(changed_interface), Some(changed)) =
+                message.get2::<&str, Dict<&str, Variant<Iter>, _>>()
+            {
+                let mut properties = Vec::new();
+
+                for (name, mut value) in changed {
+                    if path == NM_SERVICE_PATH
+                        && changed_interface == NM_SERVICE_INTERFACE
+                        && name == "ActiveConnections"
+                    {
+                        if let Some(paths) = value.0.get::<Array<Path, _>>() {
+                            let paths = paths.map(|p| p.to_string()).collect::<Vec<_>>();
+                            diff_active_connections(active_connections, paths, &mut events);
+                        }
+                    }
+
+                    properties.push(name.to_string());
+                }
+
+                events.push(NetworkManagerEvent::PropertiesChanged {
+                    path,
+                    interface: changed_interface.to_string(),
+                    properties,
+                });
+            }
+        }
+        _ => {}
+    }
+
+    events
+}
+
+fn diff_active_connections(
+    active_connections: &mut Vec<String>,
+    current: Vec<String>,
+    events: &mut Vec<NetworkManagerEvent>,
+) {
+    for path in active_connections.iter() {
+        if !current.contains(path) {
+            events.push(NetworkManagerEvent::ActiveConnectionRemoved(path.clone()));
+        }
+    }
+
+    for path in &current {
+        if !active_connections.contains(path) {
+            events.push(NetworkManagerEvent::ActiveConnectionAdded(path.clone()));
+        }
+    }
+
+    *active_connections = current;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    const ACTIVE_PATH_1: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const ACTIVE_PATH_2: &str = "/org/freedesktop/NetworkManager/ActiveConnection/2";
+    const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/3";
+
+    fn signal(path: &str, interface: &str, member: &str) -> Message {
+        Message::new_signal(path, interface, member).unwrap()
+    }
+
+    #[test]
+    fn test_decode_state_changed() {
+        let message = signal(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "StateChanged").append1(70_u32);
+
+        let events = decode_signal(&message, &mut vec![]);
+
+        assert_eq!(
+            events,
+            vec![NetworkManagerEvent::StateChanged(
+                NetworkManagerState::ConnectedGlobal
+            )]
+        );
+    }
+
+    #[test]
+    fn test_decode_device_added() {
+        let message = signal(NM_SERVICE_PATH, NM_SERVICE_INTERFACE, "DeviceAdded")
+            .append1(Path::new(DEVICE_PATH).unwrap());
+
+        let events = decode_signal(&message, &mut vec![]);
+
+        assert_eq!(
+            events,
+            vec![NetworkManagerEvent::DeviceAdded(DEVICE_PATH.into())]
+        );
+    }
+
+    #[test]
+    fn test_decode_device_state_changed() {
+        let message = signal(DEVICE_PATH, NM_DEVICE_INTERFACE, "StateChanged")
+            .append3(20_u32, 100_u32, 40_u32);
+
+        let events = decode_signal(&message, &mut vec![]);
+
+        assert_eq!(
+            events,
+            vec![NetworkManagerEvent::DeviceStateChanged {
+                path: DEVICE_PATH.into(),
+                new_state: DeviceState::Unavailable,
+                old_state: DeviceState::Activated,
+                reason: DeviceStateReason::CarrierLost,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decode_active_connection_state_changed() {
+        let message =
+            signal(ACTIVE_PATH_1, NM_ACTIVE_INTERFACE, "StateChanged").append2(4_u32, 10_u32);
+
+        let events = decode_signal(&message, &mut vec![]);
+
+        assert_eq!(
+            events,
+            vec![NetworkManagerEvent::ActiveConnectionStateChanged {
+                path: ACTIVE_PATH_1.into(),
+                state: ConnectionState::Deactivated,
+                reason: ActiveConnectionStateReason::LoginFailed,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decode_active_connections_changed() {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "ActiveConnections",
+            Variant(vec![Path::new(ACTIVE_PATH_2).unwrap()]),
+        );
+
+        let message = signal(
+            NM_SERVICE_PATH,
+            DBUS_PROPERTIES_INTERFACE,
+            "PropertiesChanged",
+        )
+        .append3(NM_SERVICE_INTERFACE, properties, Vec::<String>::new());
+
+        let mut active_connections = vec![ACTIVE_PATH_1.to_string()];
+
+        let events = decode_signal(&message, &mut active_connections);
+
+        assert_eq!(
+            events,
+            vec![
+                NetworkManagerEvent::ActiveConnectionRemoved(ACTIVE_PATH_1.into()),
+                NetworkManagerEvent::ActiveConnectionAdded(ACTIVE_PATH_2.into()),
+                NetworkManagerEvent::PropertiesChanged {
+                    path: NM_SERVICE_PATH.into(),
+                    interface: NM_SERVICE_INTERFACE.into(),
+                    properties: vec!["ActiveConnections".into()],
+                },
+            ]
+        );
+        assert_eq!(active_connections, vec![ACTIVE_PATH_2.to_string()]);
+    }
+}
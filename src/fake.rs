@@ -0,0 +1,2265 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+use backend::{sealed, Backend};
+use errors::*;
+
+use checkpoint::{CheckpointFlags, RollbackResult};
+use connection::{ActiveConnectionStateReason, ConnectionState};
+use dbus_nm::{NM_SERVICE_INTERFACE, NM_SERVICE_PATH, NM_WIRED_INTERFACE, NM_WIRELESS_INTERFACE};
+use device::{DeviceCapabilities, DeviceState, DeviceStateReason, DeviceType};
+use dhcp_config::{Dhcp4Config, Dhcp6Config};
+use events::{EventSource, EventStream, NetworkManagerEvent};
+use ip_config::{Ip4Config, Ip6Config};
+use manager::{Connectivity, NetworkManagerState};
+use modem::ModemCapabilities;
+use settings::{
+    ConnectionSetting, ConnectionSettings, WirelessSetting, ETHERNET_SETTING, GSM_SETTING,
+    WIRELESS_SETTING,
+};
+use ssid::{Ssid, SsidSlice};
+use wifi::{
+    AccessPoint, AccessPointCredentials, NM80211ApFlags, NM80211ApSecurityFlags, NM80211Mode,
+    Security,
+};
+
+const FAKE_METHOD_TIMEOUT: u64 = 1;
+
+const FAKE_BASE_PATH: &str = "/org/freedesktop/NetworkManager";
+
+type EventQueue = Rc<RefCell<VecDeque<NetworkManagerEvent>>>;
+
+/// An in-memory Network Manager for testing code built on this crate.
+///
+/// Devices, access points and saved connections are set up with the `add_*`
+/// methods. Activations complete immediately, going through the same device and
+/// active connection states as Network Manager does and emitting the matching
+/// events. An activation fails when the credentials do not match the access
+/// point passphrase, or when failures were requested with `fail_activations`.
+/// Checkpoints are only rolled back on request, never on timeout.
+///
+/// ```
+/// use std::rc::Rc;
+/// use network_manager::{DeviceType, FakeNetworkManager, NetworkManager, Security};
+///
+/// let fake = Rc::new(FakeNetworkManager::new());
+/// let device = fake.add_device("wlan0", DeviceType::WiFi);
+/// fake.add_access_point(&device, "office", 80, Security::WPA2, Some("password"));
+///
+/// let manager = NetworkManager::with_backend(fake.clone());
+/// assert_eq!(manager.get_devices().unwrap().len(), 1);
+/// ```
+pub struct FakeNetworkManager {
+    state: RefCell<FakeState>,
+    subscribers: RefCell<Vec<Weak<RefCell<VecDeque<NetworkManagerEvent>>>>>,
+}
+
+struct FakeState {
+    last_id: u32,
+    state: NetworkManagerState,
+    connectivity: Connectivity,
+    fail_activations: bool,
+    networking_enabled: bool,
+    asleep: bool,
+    wireless_enabled: bool,
+    wireless_hardware_enabled: bool,
+    wwan_enabled: bool,
+    wwan_hardware_enabled: bool,
+    devices: Vec<FakeDevice>,
+    access_points: Vec<FakeAccessPoint>,
+    connections: Vec<FakeConnection>,
+    active_connections: Vec<FakeActiveConnection>,
+    checkpoints: Vec<FakeCheckpoint>,
+}
+
+struct FakeDevice {
+    path: String,
+    interface: String,
+    device_type: DeviceType,
+    state: DeviceState,
+    state_reason: DeviceStateReason,
+    hw_address: String,
+    driver: String,
+    managed: bool,
+    carrier: bool,
+    speed: u32,
+    modem: Option<FakeModem>,
+    last_scan: i64,
+    scans_refused: u32,
+    last_connection: Option<String>,
+    ip4_config: Option<Ip4Config>,
+    ip6_config: Option<Ip6Config>,
+    dhcp4_config: Option<Dhcp4Config>,
+    dhcp6_config: Option<Dhcp6Config>,
+}
+
+struct FakeModem {
+    capabilities: ModemCapabilities,
+    device_id: String,
+    operator_code: String,
+}
+
+struct FakeAccessPoint {
+    path: String,
+    device: String,
+    ssid: Ssid,
+    strength: u32,
+    security: Security,
+    passphrase: Option<String>,
+    bssid: String,
+    frequency: u32,
+    hidden: bool,
+}
+
+#[derive(Clone)]
+struct FakeConnection {
+    path: String,
+    settings: ConnectionSettings,
+    secret: Option<String>,
+}
+
+struct FakeActiveConnection {
+    path: String,
+    connection: String,
+    device: String,
+    state: ConnectionState,
+}
+
+struct FakeCheckpoint {
+    path: String,
+    flags: CheckpointFlags,
+    connections: Vec<FakeConnection>,
+    /// The covered devices along with the connection active on each.
+    devices: Vec<(String, Option<String>)>,
+}
+
+struct FakeEventSource {
+    queue: EventQueue,
+}
+
+impl EventSource for FakeEventSource {
+    /// Returns immediately, as all fake state transitions are synchronous.
+    fn next_event(&mut self, _timeout: Duration) -> Result<Option<NetworkManagerEvent>> {
+        Ok(self.queue.borrow_mut().pop_front())
+    }
+}
+
+impl FakeNetworkManager {
+    pub fn new() -> Self {
+        FakeNetworkManager {
+            state: RefCell::new(FakeState {
+                last_id: 0,
+                state: NetworkManagerState::Disconnected,
+                connectivity: Connectivity::None,
+                fail_activations: false,
+                networking_enabled: true,
+                asleep: false,
+                wireless_enabled: true,
+                wireless_hardware_enabled: true,
+                wwan_enabled: true,
+                wwan_hardware_enabled: true,
+                devices: Vec::new(),
+                access_points: Vec::new(),
+                connections: Vec::new(),
+                active_connections: Vec::new(),
+                checkpoints: Vec::new(),
+            }),
+            subscribers: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Adds a disconnected device and returns its path.
+    pub fn add_device(&self, interface: &str, device_type: DeviceType) -> String {
+        let mut state = self.state.borrow_mut();
+
+        let path = state.next_path("Devices");
+
+        let hw_address = format!("02:00:00:00:00:{:02X}", state.last_id & 0xff);
+
+        let speed = if device_type == DeviceType::Ethernet {
+            1000
+        } else {
+            0
+        };
+
+        let modem = if device_type == DeviceType::Modem {
+            Some(FakeModem {
+                capabilities: ModemCapabilities::MODEM_CAP_GSM_UMTS
+                    | ModemCapabilities::MODEM_CAP_LTE,
+                device_id: format!("{:040x}", state.last_id),
+                operator_code: String::new(),
+            })
+        } else {
+            None
+        };
+
+        state.devices.push(FakeDevice {
+            path: path.clone(),
+            interface: interface.to_string(),
+            device_type,
+            state: DeviceState::Disconnected,
+            state_reason: DeviceStateReason::None,
+            hw_address,
+            driver: "fake".to_string(),
+            managed: true,
+            carrier: true,
+            speed,
+            modem,
+            last_scan: -1,
+            scans_refused: 0,
+            last_connection: None,
+            ip4_config: None,
+            ip6_config: None,
+            dhcp4_config: None,
+            dhcp6_config: None,
+        });
+
+        self.emit(vec![NetworkManagerEvent::DeviceAdded(path.clone())]);
+
+        path
+    }
+
+    /// Removes a device together with its access points and active connection.
+    pub fn remove_device(&self, path: &str) {
+        let mut events = Vec::new();
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            if let Some(active) = state.device_active_connection(path) {
+                state.deactivate(
+                    &active,
+                    ActiveConnectionStateReason::DeviceDisconnected,
+                    DeviceStateReason::UserRequested,
+                    &mut events,
+                );
+            }
+
+            state.devices.retain(|device| device.path != path);
+            state.access_points.retain(|ap| ap.device != path);
+
+            events.push(NetworkManagerEvent::DeviceRemoved(path.to_string()));
+
+            state.update_state(&mut events);
+        }
+
+        self.emit(events);
+    }
+
+    /// Makes an access point visible to a Wi-Fi device and returns its path.
+    ///
+    /// Connections to the access point succeed only with a matching
+    /// `passphrase`, or with any credentials if it is `None`.
+    pub fn add_access_point(
+        &self,
+        device_path: &str,
+        ssid: &str,
+        strength: u32,
+        security: Security,
+        passphrase: Option<&str>,
+    ) -> String {
+        let mut state = self.state.borrow_mut();
+
+        let path = state.next_path("AccessPoint");
+
+        let bssid = format!("02:00:00:00:01:{:02X}", state.last_id & 0xff);
+
+        state.access_points.push(FakeAccessPoint {
+            path: path.clone(),
+            device: device_path.to_string(),
+            ssid: Ssid::from_bytes(ssid.as_bytes().to_vec()).unwrap_or_default(),
+            strength,
+            security,
+            passphrase: passphrase.map(|p| p.to_string()),
+            bssid,
+            frequency: 2437,
+            hidden: false,
+        });
+
+        path
+    }
+
+    /// Sets the BSSID of an access point, e.g. to set up several access
+    /// points of the same network.
+    pub fn set_access_point_bssid(&self, path: &str, bssid: &str) {
+        if let Some(ap) = self.state.borrow_mut().access_point_mut(path) {
+            ap.bssid = bssid.to_string();
+        }
+    }
+
+    /// Sets the frequency in MHz of an access point. The default is 2437 MHz,
+    /// i.e. channel 6 of the 2.4 GHz band.
+    pub fn set_access_point_frequency(&self, path: &str, frequency: u32) {
+        if let Some(ap) = self.state.borrow_mut().access_point_mut(path) {
+            ap.frequency = frequency;
+        }
+    }
+
+    /// Makes the next `count` scan requests on a device fail as if a scan
+    /// was already running.
+    pub fn refuse_scans(&self, device_path: &str, count: u32) {
+        if let Some(device) = self.state.borrow_mut().device_mut(device_path) {
+            device.scans_refused = count;
+        }
+    }
+
+    /// Hides the SSID of an access point. Hidden access points report an
+    /// empty SSID and are only joined with profiles marked as hidden.
+    pub fn set_access_point_hidden(&self, path: &str, hidden: bool) {
+        if let Some(ap) = self.state.borrow_mut().access_point_mut(path) {
+            ap.hidden = hidden;
+        }
+    }
+
+    pub fn remove_access_point(&self, path: &str) {
+        self.state
+            .borrow_mut()
+            .access_points
+            .retain(|ap| ap.path != path);
+    }
+
+    pub fn set_state(&self, network_manager_state: NetworkManagerState) {
+        self.state.borrow_mut().state = network_manager_state.clone();
+
+        self.emit(vec![NetworkManagerEvent::StateChanged(
+            network_manager_state,
+        )]);
+    }
+
+    pub fn set_connectivity(&self, connectivity: Connectivity) {
+        self.state.borrow_mut().connectivity = connectivity;
+    }
+
+    /// Changes the state of a device, e.g. to simulate a link loss with
+    /// `DeviceStateReason::CarrierLost`. Leaving the `Activated` state
+    /// deactivates the device connection.
+    pub fn set_device_state(
+        &self,
+        path: &str,
+        device_state: DeviceState,
+        reason: DeviceStateReason,
+    ) {
+        let mut events = Vec::new();
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            if device_state != DeviceState::Activated {
+                if let Some(active) = state.device_active_connection(path) {
+                    state.deactivate(
+                        &active,
+                        ActiveConnectionStateReason::DeviceDisconnected,
+                        DeviceStateReason::UserRequested,
+                        &mut events,
+                    );
+                }
+            }
+
+            state.set_device_state(path, device_state, reason, &mut events);
+
+            state.update_state(&mut events);
+        }
+
+        self.emit(events);
+    }
+
+    pub fn set_device_hw_address(&self, path: &str, hw_address: &str) {
+        if let Some(device) = self.state.borrow_mut().device_mut(path) {
+            device.hw_address = hw_address.to_string();
+        }
+    }
+
+    pub fn set_device_driver(&self, path: &str, driver: &str) {
+        if let Some(device) = self.state.borrow_mut().device_mut(path) {
+            device.driver = driver.to_string();
+        }
+    }
+
+    /// Plugs or unplugs the cable of a wired device. Unplugging makes the
+    /// device unavailable, deactivating its connection.
+    pub fn set_device_carrier(&self, path: &str, carrier: bool) {
+        let changed = match self.state.borrow_mut().device_mut(path) {
+            Some(ref mut device) if device.carrier != carrier => {
+                device.carrier = carrier;
+                true
+            }
+            _ => false,
+        };
+
+        if !changed {
+            return;
+        }
+
+        self.emit(vec![NetworkManagerEvent::PropertiesChanged {
+            path: path.to_string(),
+            interface: NM_WIRED_INTERFACE.to_string(),
+            properties: vec!["Carrier".to_string()],
+        }]);
+
+        if carrier {
+            self.set_device_state(path, DeviceState::Disconnected, DeviceStateReason::None);
+        } else {
+            self.set_device_state(
+                path,
+                DeviceState::Unavailable,
+                DeviceStateReason::CarrierLost,
+            );
+        }
+    }
+
+    /// Sets the link speed of a wired device in Mb/s.
+    pub fn set_device_speed(&self, path: &str, speed: u32) {
+        if let Some(device) = self.state.borrow_mut().device_mut(path) {
+            device.speed = speed;
+        }
+    }
+
+    /// Registers a modem on the network with the given MCC and MNC.
+    pub fn set_modem_operator_code(&self, path: &str, operator_code: &str) {
+        if let Some(device) = self.state.borrow_mut().device_mut(path) {
+            if let Some(ref mut modem) = device.modem {
+                modem.operator_code = operator_code.to_string();
+            }
+        }
+    }
+
+    /// Hands a device over to or takes it away from Network Manager. Devices
+    /// that are not managed are kept in the `Unmanaged` state.
+    pub fn set_device_managed(&self, path: &str, managed: bool) {
+        let changed = match self.state.borrow_mut().device_mut(path) {
+            Some(ref mut device) if device.managed != managed => {
+                device.managed = managed;
+                true
+            }
+            _ => false,
+        };
+
+        if !changed {
+            return;
+        }
+
+        if managed {
+            self.set_device_state(
+                path,
+                DeviceState::Disconnected,
+                DeviceStateReason::NowManaged,
+            );
+        } else {
+            self.set_device_state(
+                path,
+                DeviceState::Unmanaged,
+                DeviceStateReason::NowUnmanaged,
+            );
+        }
+    }
+
+    /// Sets the IPv4 configuration reported for a device and its active
+    /// connection while the device is activated.
+    pub fn set_ip4_config(&self, device_path: &str, config: Option<Ip4Config>) {
+        if let Some(device) = self.state.borrow_mut().device_mut(device_path) {
+            device.ip4_config = config;
+        }
+    }
+
+    /// Sets the IPv6 configuration reported for a device and its active
+    /// connection while the device is activated.
+    pub fn set_ip6_config(&self, device_path: &str, config: Option<Ip6Config>) {
+        if let Some(device) = self.state.borrow_mut().device_mut(device_path) {
+            device.ip6_config = config;
+        }
+    }
+
+    /// Sets the DHCPv4 lease reported for a device while it is activated.
+    pub fn set_dhcp4_config(&self, device_path: &str, config: Option<Dhcp4Config>) {
+        if let Some(device) = self.state.borrow_mut().device_mut(device_path) {
+            device.dhcp4_config = config;
+        }
+    }
+
+    /// Sets the DHCPv6 lease reported for a device while it is activated.
+    pub fn set_dhcp6_config(&self, device_path: &str, config: Option<Dhcp6Config>) {
+        if let Some(device) = self.state.borrow_mut().device_mut(device_path) {
+            device.dhcp6_config = config;
+        }
+    }
+
+    /// Makes all following activations fail until called with `false`.
+    pub fn fail_activations(&self, fail: bool) {
+        self.state.borrow_mut().fail_activations = fail;
+    }
+
+    /// Simulates the Wi-Fi rfkill switch. Turning it off deactivates all
+    /// Wi-Fi connections.
+    pub fn set_wireless_hardware_enabled(&self, enabled: bool) {
+        self.change_radio_state("WirelessHardwareEnabled", |state| {
+            state.wireless_hardware_enabled = enabled;
+        });
+    }
+
+    /// Simulates the mobile broadband rfkill switch.
+    pub fn set_wwan_hardware_enabled(&self, enabled: bool) {
+        self.change_radio_state("WwanHardwareEnabled", |state| {
+            state.wwan_hardware_enabled = enabled;
+        });
+    }
+
+    /// Applies a change to the networking or radio state and deactivates the
+    /// connections of devices that are disabled by it.
+    fn change_radio_state<F>(&self, property: &str, change: F)
+    where
+        F: FnOnce(&mut FakeState),
+    {
+        let mut events = vec![NetworkManagerEvent::PropertiesChanged {
+            path: NM_SERVICE_PATH.to_string(),
+            interface: NM_SERVICE_INTERFACE.to_string(),
+            properties: vec![property.to_string()],
+        }];
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            change(&mut state);
+
+            let disabled = state
+                .active_connections
+                .iter()
+                .filter(|active| match state.device(&active.device) {
+                    Ok(device) => !state.is_device_enabled(&device.device_type),
+                    Err(_) => false,
+                })
+                .map(|active| active.path.clone())
+                .collect::<Vec<_>>();
+
+            for active in disabled {
+                state.deactivate(
+                    &active,
+                    ActiveConnectionStateReason::UserDisconnected,
+                    DeviceStateReason::UserRequested,
+                    &mut events,
+                );
+            }
+
+            state.update_state(&mut events);
+        }
+
+        self.emit(events);
+    }
+
+    fn emit(&self, events: Vec<NetworkManagerEvent>) {
+        let mut subscribers = self.subscribers.borrow_mut();
+
+        subscribers.retain(|subscriber| subscriber.upgrade().is_some());
+
+        for subscriber in subscribers.iter() {
+            if let Some(queue) = subscriber.upgrade() {
+                queue.borrow_mut().extend(events.iter().cloned());
+            }
+        }
+    }
+
+    fn activate(
+        &self,
+        connection_path: &str,
+        device_path: &str,
+        access_point: Option<&str>,
+    ) -> Result<String> {
+        let mut events = Vec::new();
+
+        let active_path = self.state.borrow_mut().activate(
+            connection_path,
+            device_path,
+            access_point,
+            &mut events,
+        )?;
+
+        self.emit(events);
+
+        Ok(active_path)
+    }
+}
+
+impl Default for FakeNetworkManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeState {
+    fn next_path(&mut self, kind: &str) -> String {
+        self.last_id += 1;
+
+        format!("{}/{}/{}", FAKE_BASE_PATH, kind, self.last_id)
+    }
+
+    fn next_uuid(&mut self) -> String {
+        self.last_id += 1;
+
+        format!("00000000-0000-4000-8000-{:012x}", self.last_id)
+    }
+
+    fn device(&self, path: &str) -> Result<&FakeDevice> {
+        self.devices
+            .iter()
+            .find(|device| device.path == path)
+            .ok_or_else(|| not_found("device", path))
+    }
+
+    fn device_mut(&mut self, path: &str) -> Option<&mut FakeDevice> {
+        self.devices.iter_mut().find(|device| device.path == path)
+    }
+
+    /// Returns the device if it is activated and so has an IP configuration
+    /// and DHCP lease.
+    fn configured_device(&self, path: &str) -> Result<Option<&FakeDevice>> {
+        let device = self.device(path)?;
+
+        if device.state == DeviceState::Activated {
+            Ok(Some(device))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn access_point(&self, path: &str) -> Result<&FakeAccessPoint> {
+        self.access_points
+            .iter()
+            .find(|ap| ap.path == path)
+            .ok_or_else(|| not_found("access point", path))
+    }
+
+    fn access_point_mut(&mut self, path: &str) -> Option<&mut FakeAccessPoint> {
+        self.access_points.iter_mut().find(|ap| ap.path == path)
+    }
+
+    fn connection(&self, path: &str) -> Result<&FakeConnection> {
+        self.connections
+            .iter()
+            .find(|connection| connection.path == path)
+            .ok_or_else(|| not_found("connection", path))
+    }
+
+    fn active_connection(&self, path: &str) -> Result<&FakeActiveConnection> {
+        self.active_connections
+            .iter()
+            .find(|active| active.path == path)
+            .ok_or_else(|| not_found("active connection", path))
+    }
+
+    /// Whether networking is on and, for radios, enabled in both software
+    /// and hardware.
+    fn is_device_enabled(&self, device_type: &DeviceType) -> bool {
+        if !self.networking_enabled || self.asleep {
+            return false;
+        }
+
+        match *device_type {
+            DeviceType::WiFi => self.wireless_enabled && self.wireless_hardware_enabled,
+            DeviceType::Modem => self.wwan_enabled && self.wwan_hardware_enabled,
+            _ => true,
+        }
+    }
+
+    fn modem(&self, path: &str) -> Result<&FakeModem> {
+        match self.device(path)?.modem {
+            Some(ref modem) => Ok(modem),
+            None => Err(not_found("modem", path)),
+        }
+    }
+
+    fn checkpoint_index(&self, path: &str) -> Result<usize> {
+        self.checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.path == path)
+            .ok_or_else(|| not_found("checkpoint", path))
+    }
+
+    fn device_active_connection(&self, device_path: &str) -> Option<String> {
+        self.active_connections
+            .iter()
+            .find(|active| active.device == device_path)
+            .map(|active| active.path.clone())
+    }
+
+    fn add_wireless_connection(
+        &mut self,
+        ssid: &SsidSlice,
+        mode: &str,
+        secret: Option<String>,
+    ) -> String {
+        let path = self.next_path("Settings");
+
+        let settings = ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some(String::from_utf8_lossy(ssid.as_bytes()).into_owned()),
+                uuid: Some(self.next_uuid()),
+                kind: Some(WIRELESS_SETTING.into()),
+                ..Default::default()
+            },
+            wireless: Some(WirelessSetting {
+                ssid: Ssid::from_bytes(ssid.as_bytes().to_vec()).ok(),
+                mode: Some(mode.into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.connections.push(FakeConnection {
+            path: path.clone(),
+            settings,
+            secret,
+        });
+
+        path
+    }
+
+    fn find_access_point(&self, device_path: &str, ssid: &Ssid, hidden: bool) -> Option<String> {
+        self.access_points
+            .iter()
+            .filter(|ap| ap.device == device_path && ap.ssid == *ssid && (hidden || !ap.hidden))
+            .max_by_key(|ap| ap.strength)
+            .map(|ap| ap.path.clone())
+    }
+
+    /// Picks the access point a Wi-Fi client connection joins on a device.
+    /// Hidden access points are only joined by profiles marked as hidden.
+    fn connection_access_point(
+        &self,
+        device_path: &str,
+        settings: &ConnectionSettings,
+    ) -> Option<String> {
+        let hidden = settings
+            .wireless
+            .as_ref()
+            .and_then(|wireless| wireless.hidden)
+            .unwrap_or(false);
+
+        settings
+            .ssid()
+            .and_then(|ssid| self.find_access_point(device_path, ssid, hidden))
+    }
+
+    /// Adds a connection, generating the UUID if it is missing.
+    fn add_connection(&mut self, settings: &ConnectionSettings) -> String {
+        let path = self.next_path("Settings");
+
+        let mut settings = settings.clone();
+
+        if settings.uuid().is_empty() {
+            settings.connection.uuid = Some(self.next_uuid());
+        }
+
+        self.connections.push(FakeConnection {
+            path: path.clone(),
+            secret: settings_secret(&settings),
+            settings,
+        });
+
+        path
+    }
+
+    /// Picks a device for a saved connection, along with the access point to
+    /// connect to for Wi-Fi client connections.
+    fn find_device(&self, connection: &FakeConnection) -> Result<(String, Option<String>)> {
+        let settings = &connection.settings;
+
+        for device in &self.devices {
+            let suitable = match device.device_type {
+                DeviceType::WiFi => settings.kind() == WIRELESS_SETTING,
+                DeviceType::Ethernet => settings.kind() == ETHERNET_SETTING,
+                DeviceType::Modem => settings.kind() == GSM_SETTING,
+                _ => false,
+            };
+
+            let bound_elsewhere = match settings.connection.interface_name {
+                Some(ref interface) => *interface != device.interface,
+                None => false,
+            };
+
+            if !suitable || bound_elsewhere || !device.managed {
+                continue;
+            }
+
+            if device.device_type == DeviceType::WiFi && settings.mode() != Some("ap") {
+                match self.connection_access_point(&device.path, settings) {
+                    Some(ap) => return Ok((device.path.clone(), Some(ap))),
+                    None => continue,
+                }
+            }
+
+            return Ok((device.path.clone(), None));
+        }
+
+        bail!(ErrorKind::NetworkManager(format!(
+            "No suitable device found for connection {}",
+            connection.path
+        )))
+    }
+
+    fn activate(
+        &mut self,
+        connection_path: &str,
+        device_path: &str,
+        access_point: Option<&str>,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) -> Result<String> {
+        let secret = self.connection(connection_path)?.secret.clone();
+
+        let device = self.device(device_path)?;
+
+        if !self.is_device_enabled(&device.device_type) {
+            bail!(ErrorKind::NetworkManager(format!(
+                "Device {} is disabled",
+                device_path
+            )));
+        }
+
+        if !device.carrier {
+            bail!(ErrorKind::NetworkManager(format!(
+                "Device {} has no carrier",
+                device_path
+            )));
+        }
+
+        let secrets_match = match access_point {
+            Some(access_point) => match self.access_point(access_point)?.passphrase {
+                Some(ref passphrase) => secret.as_ref() == Some(passphrase),
+                None => true,
+            },
+            None => true,
+        };
+
+        if let Some(active) = self.device_active_connection(device_path) {
+            self.deactivate(
+                &active,
+                ActiveConnectionStateReason::UserDisconnected,
+                DeviceStateReason::NewActivation,
+                events,
+            );
+        }
+
+        let active_path = self.next_path("ActiveConnection");
+
+        self.active_connections.push(FakeActiveConnection {
+            path: active_path.clone(),
+            connection: connection_path.to_string(),
+            device: device_path.to_string(),
+            state: ConnectionState::Activating,
+        });
+
+        if let Some(device) = self.device_mut(device_path) {
+            device.last_connection = Some(connection_path.to_string());
+        }
+
+        events.push(NetworkManagerEvent::ActiveConnectionAdded(
+            active_path.clone(),
+        ));
+        self.set_active_state(
+            &active_path,
+            ConnectionState::Activating,
+            ActiveConnectionStateReason::None,
+            events,
+        );
+
+        for device_state in &[DeviceState::Prepare, DeviceState::Config] {
+            self.set_device_state(
+                device_path,
+                device_state.clone(),
+                DeviceStateReason::None,
+                events,
+            );
+        }
+
+        if !secrets_match {
+            self.set_device_state(
+                device_path,
+                DeviceState::NeedAuth,
+                DeviceStateReason::None,
+                events,
+            );
+            self.fail(
+                &active_path,
+                DeviceStateReason::NoSecrets,
+                ActiveConnectionStateReason::NoSecrets,
+                events,
+            );
+        } else if self.fail_activations {
+            self.fail(
+                &active_path,
+                DeviceStateReason::ConfigFailed,
+                ActiveConnectionStateReason::DeviceDisconnected,
+                events,
+            );
+        } else {
+            for device_state in &[DeviceState::IpConfig, DeviceState::Activated] {
+                self.set_device_state(
+                    device_path,
+                    device_state.clone(),
+                    DeviceStateReason::None,
+                    events,
+                );
+            }
+
+            self.set_active_state(
+                &active_path,
+                ConnectionState::Activated,
+                ActiveConnectionStateReason::None,
+                events,
+            );
+        }
+
+        self.update_state(events);
+
+        Ok(active_path)
+    }
+
+    fn fail(
+        &mut self,
+        active_path: &str,
+        device_reason: DeviceStateReason,
+        active_reason: ActiveConnectionStateReason,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) {
+        let device_path = match self.active_connection(active_path) {
+            Ok(active) => active.device.clone(),
+            Err(_) => return,
+        };
+
+        self.set_device_state(&device_path, DeviceState::Failed, device_reason, events);
+        self.set_active_state(
+            active_path,
+            ConnectionState::Deactivated,
+            active_reason,
+            events,
+        );
+        self.set_device_state(
+            &device_path,
+            DeviceState::Disconnected,
+            DeviceStateReason::None,
+            events,
+        );
+
+        self.remove_active_connection(active_path, events);
+    }
+
+    fn deactivate(
+        &mut self,
+        active_path: &str,
+        active_reason: ActiveConnectionStateReason,
+        device_reason: DeviceStateReason,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) {
+        let device_path = match self.active_connection(active_path) {
+            Ok(active) => active.device.clone(),
+            Err(_) => return,
+        };
+
+        self.set_active_state(
+            active_path,
+            ConnectionState::Deactivating,
+            active_reason,
+            events,
+        );
+        self.set_device_state(
+            &device_path,
+            DeviceState::Deactivating,
+            device_reason,
+            events,
+        );
+        self.set_device_state(
+            &device_path,
+            DeviceState::Disconnected,
+            device_reason,
+            events,
+        );
+        self.set_active_state(
+            active_path,
+            ConnectionState::Deactivated,
+            active_reason,
+            events,
+        );
+
+        self.remove_active_connection(active_path, events);
+    }
+
+    fn remove_active_connection(
+        &mut self,
+        active_path: &str,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) {
+        self.active_connections
+            .retain(|active| active.path != active_path);
+
+        events.push(NetworkManagerEvent::ActiveConnectionRemoved(
+            active_path.to_string(),
+        ));
+    }
+
+    fn set_device_state(
+        &mut self,
+        path: &str,
+        new_state: DeviceState,
+        reason: DeviceStateReason,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) {
+        if let Some(device) = self.device_mut(path) {
+            if device.state == new_state {
+                return;
+            }
+
+            let old_state = device.state.clone();
+
+            device.state = new_state.clone();
+            device.state_reason = reason;
+
+            events.push(NetworkManagerEvent::DeviceStateChanged {
+                path: path.to_string(),
+                new_state,
+                old_state,
+                reason,
+            });
+        }
+    }
+
+    fn set_active_state(
+        &mut self,
+        path: &str,
+        state: ConnectionState,
+        reason: ActiveConnectionStateReason,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) {
+        if let Some(active) = self
+            .active_connections
+            .iter_mut()
+            .find(|active| active.path == path)
+        {
+            active.state = state.clone();
+
+            events.push(NetworkManagerEvent::ActiveConnectionStateChanged {
+                path: path.to_string(),
+                state,
+                reason,
+            });
+        }
+    }
+
+    /// Snapshots the connections of `devices`, or of all devices if empty.
+    fn create_checkpoint(&mut self, devices: &[String], flags: CheckpointFlags) -> Result<String> {
+        let devices = if devices.is_empty() {
+            self.devices
+                .iter()
+                .map(|device| device.path.clone())
+                .collect()
+        } else {
+            for device in devices {
+                self.device(device)?;
+            }
+
+            devices.to_vec()
+        };
+
+        if flags.contains(CheckpointFlags::CHECKPOINT_DESTROY_ALL) {
+            self.checkpoints.clear();
+        }
+
+        if !flags.contains(CheckpointFlags::CHECKPOINT_ALLOW_OVERLAPPING) {
+            let overlapping = self.checkpoints.iter().any(|checkpoint| {
+                checkpoint
+                    .devices
+                    .iter()
+                    .any(|(device, _)| devices.contains(device))
+            });
+
+            if overlapping {
+                bail!(ErrorKind::NetworkManager(
+                    "A device is already covered by a checkpoint".into()
+                ));
+            }
+        }
+
+        let devices = devices
+            .into_iter()
+            .map(|device| {
+                let connection = self
+                    .active_connections
+                    .iter()
+                    .find(|active| active.device == device)
+                    .map(|active| active.connection.clone());
+
+                (device, connection)
+            })
+            .collect();
+
+        let path = self.next_path("Checkpoint");
+
+        self.checkpoints.push(FakeCheckpoint {
+            path: path.clone(),
+            flags,
+            connections: self.connections.clone(),
+            devices,
+        });
+
+        Ok(path)
+    }
+
+    /// Restores the saved connections and reactivates the connections that
+    /// were active on each covered device.
+    fn rollback_checkpoint(
+        &mut self,
+        path: &str,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) -> Result<BTreeMap<String, RollbackResult>> {
+        let index = self.checkpoint_index(path)?;
+        let checkpoint = self.checkpoints.remove(index);
+
+        if checkpoint
+            .flags
+            .contains(CheckpointFlags::CHECKPOINT_DELETE_NEW_CONNECTIONS)
+        {
+            let new_active = self
+                .active_connections
+                .iter()
+                .filter(|active| {
+                    !checkpoint
+                        .connections
+                        .iter()
+                        .any(|connection| connection.path == active.connection)
+                })
+                .map(|active| active.path.clone())
+                .collect::<Vec<_>>();
+
+            for active in new_active {
+                self.deactivate(
+                    &active,
+                    ActiveConnectionStateReason::UserDisconnected,
+                    DeviceStateReason::UserRequested,
+                    events,
+                );
+            }
+
+            self.connections.retain(|connection| {
+                checkpoint
+                    .connections
+                    .iter()
+                    .any(|saved| saved.path == connection.path)
+            });
+        }
+
+        for saved in checkpoint.connections {
+            match self.connections.iter().position(|c| c.path == saved.path) {
+                Some(index) => self.connections[index] = saved,
+                None => self.connections.push(saved),
+            }
+        }
+
+        let mut results = BTreeMap::new();
+
+        for (device_path, connection) in checkpoint.devices {
+            let result = self.rollback_device(&device_path, connection, events);
+
+            results.insert(device_path, result);
+        }
+
+        self.update_state(events);
+
+        Ok(results)
+    }
+
+    fn rollback_device(
+        &mut self,
+        device_path: &str,
+        connection: Option<String>,
+        events: &mut Vec<NetworkManagerEvent>,
+    ) -> RollbackResult {
+        let managed = match self.device(device_path) {
+            Ok(device) => device.managed,
+            Err(_) => return RollbackResult::NoDevice,
+        };
+
+        if !managed {
+            return RollbackResult::DeviceUnmanaged;
+        }
+
+        let active = self
+            .active_connections
+            .iter()
+            .find(|active| active.device == device_path)
+            .map(|active| (active.path.clone(), active.connection.clone()));
+
+        if active.as_ref().map(|(_, current)| current) == connection.as_ref() {
+            return RollbackResult::Ok;
+        }
+
+        if let Some((active_path, _)) = active {
+            self.deactivate(
+                &active_path,
+                ActiveConnectionStateReason::UserDisconnected,
+                DeviceStateReason::UserRequested,
+                events,
+            );
+        }
+
+        let connection = match connection {
+            Some(connection) => connection,
+            None => return RollbackResult::Ok,
+        };
+
+        let access_point = match self.connection(&connection) {
+            Ok(saved) => {
+                let settings = &saved.settings;
+
+                if settings.kind() == WIRELESS_SETTING && settings.mode() != Some("ap") {
+                    match self.connection_access_point(device_path, settings) {
+                        Some(access_point) => Some(access_point),
+                        None => return RollbackResult::Failed,
+                    }
+                } else {
+                    None
+                }
+            }
+            Err(_) => return RollbackResult::Failed,
+        };
+
+        let activated = self
+            .activate(
+                &connection,
+                device_path,
+                access_point.as_ref().map(|ap| ap as &str),
+                events,
+            )
+            .is_ok();
+
+        match self.device(device_path) {
+            Ok(device) if activated && device.state == DeviceState::Activated => RollbackResult::Ok,
+            _ => RollbackResult::Failed,
+        }
+    }
+
+    /// Derives the global state from the activated connections. Hotspots
+    /// only provide local connectivity.
+    fn update_state(&mut self, events: &mut Vec<NetworkManagerEvent>) {
+        let (mut local, mut global) = (false, false);
+
+        for active in &self.active_connections {
+            if active.state != ConnectionState::Activated {
+                continue;
+            }
+
+            match self.connection(&active.connection) {
+                Ok(connection) if connection.settings.mode() == Some("ap") => local = true,
+                _ => global = true,
+            }
+        }
+
+        let state = if !self.networking_enabled || self.asleep {
+            NetworkManagerState::Asleep
+        } else if global {
+            NetworkManagerState::ConnectedGlobal
+        } else if local {
+            NetworkManagerState::ConnectedLocal
+        } else {
+            NetworkManagerState::Disconnected
+        };
+
+        self.connectivity = if global {
+            Connectivity::Full
+        } else {
+            Connectivity::None
+        };
+
+        if self.state != state {
+            self.state = state.clone();
+
+            events.push(NetworkManagerEvent::StateChanged(state));
+        }
+    }
+}
+
+impl sealed::Sealed for FakeNetworkManager {}
+
+impl Backend for FakeNetworkManager {
+    fn method_timeout(&self) -> u64 {
+        FAKE_METHOD_TIMEOUT
+    }
+
+    fn subscribe(&self) -> Result<EventStream> {
+        let queue: EventQueue = Rc::new(RefCell::new(VecDeque::new()));
+
+        self.subscribers.borrow_mut().push(Rc::downgrade(&queue));
+
+        Ok(EventStream::new(Box::new(FakeEventSource { queue })))
+    }
+
+    fn get_state(&self) -> Result<NetworkManagerState> {
+        Ok(self.state.borrow().state.clone())
+    }
+
+    fn check_connectivity(&self) -> Result<Connectivity> {
+        Ok(self.state.borrow().connectivity.clone())
+    }
+
+    fn is_wireless_enabled(&self) -> Result<bool> {
+        Ok(self.state.borrow().wireless_enabled)
+    }
+
+    fn is_networking_enabled(&self) -> Result<bool> {
+        Ok(self.state.borrow().networking_enabled)
+    }
+
+    fn is_wireless_hardware_enabled(&self) -> Result<bool> {
+        Ok(self.state.borrow().wireless_hardware_enabled)
+    }
+
+    fn is_wwan_enabled(&self) -> Result<bool> {
+        Ok(self.state.borrow().wwan_enabled)
+    }
+
+    fn is_wwan_hardware_enabled(&self) -> Result<bool> {
+        Ok(self.state.borrow().wwan_hardware_enabled)
+    }
+
+    fn set_networking_enabled(&self, enabled: bool) -> Result<()> {
+        if self.state.borrow().networking_enabled == enabled {
+            bail!(ErrorKind::NetworkManager(format!(
+                "Networking already {}",
+                if enabled { "enabled" } else { "disabled" }
+            )));
+        }
+
+        self.change_radio_state("NetworkingEnabled", |state| {
+            state.networking_enabled = enabled;
+        });
+
+        Ok(())
+    }
+
+    fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        self.change_radio_state("WirelessEnabled", |state| {
+            state.wireless_enabled = enabled;
+        });
+
+        Ok(())
+    }
+
+    fn set_wwan_enabled(&self, enabled: bool) -> Result<()> {
+        self.change_radio_state("WwanEnabled", |state| {
+            state.wwan_enabled = enabled;
+        });
+
+        Ok(())
+    }
+
+    fn sleep(&self, sleep: bool) -> Result<()> {
+        if self.state.borrow().asleep == sleep {
+            bail!(ErrorKind::NetworkManager(format!(
+                "Already {}",
+                if sleep { "asleep" } else { "awake" }
+            )));
+        }
+
+        self.change_radio_state("State", |state| {
+            state.asleep = sleep;
+        });
+
+        Ok(())
+    }
+
+    fn create_checkpoint(
+        &self,
+        devices: &[String],
+        _rollback_timeout: u32,
+        flags: CheckpointFlags,
+    ) -> Result<String> {
+        self.state.borrow_mut().create_checkpoint(devices, flags)
+    }
+
+    fn destroy_checkpoint(&self, path: &str) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+
+        let index = state.checkpoint_index(path)?;
+        state.checkpoints.remove(index);
+
+        Ok(())
+    }
+
+    fn rollback_checkpoint(&self, path: &str) -> Result<BTreeMap<String, RollbackResult>> {
+        let mut events = Vec::new();
+
+        let results = self
+            .state
+            .borrow_mut()
+            .rollback_checkpoint(path, &mut events)?;
+
+        self.emit(events);
+
+        Ok(results)
+    }
+
+    fn adjust_checkpoint_rollback_timeout(&self, path: &str, _add_timeout: u32) -> Result<()> {
+        self.state.borrow().checkpoint_index(path)?;
+
+        Ok(())
+    }
+
+    fn list_connections(&self) -> Result<Vec<String>> {
+        Ok(self
+            .state
+            .borrow()
+            .connections
+            .iter()
+            .map(|connection| connection.path.clone())
+            .collect())
+    }
+
+    fn get_active_connections(&self) -> Result<Vec<String>> {
+        Ok(self
+            .state
+            .borrow()
+            .active_connections
+            .iter()
+            .map(|active| active.path.clone())
+            .collect())
+    }
+
+    fn get_active_connection_path(&self, path: &str) -> Option<String> {
+        self.state
+            .borrow()
+            .active_connection(path)
+            .ok()
+            .map(|active| active.connection.clone())
+    }
+
+    fn get_connection_state(&self, path: &str) -> Result<ConnectionState> {
+        Ok(self
+            .state
+            .borrow()
+            .active_connection(path)
+            .map(|active| active.state.clone())
+            .unwrap_or(ConnectionState::Unknown))
+    }
+
+    fn get_connection_settings(&self, path: &str) -> Result<ConnectionSettings> {
+        Ok(self.state.borrow().connection(path)?.settings.clone())
+    }
+
+    fn get_active_connection_devices(&self, path: &str) -> Result<Vec<String>> {
+        Ok(vec![self
+            .state
+            .borrow()
+            .active_connection(path)?
+            .device
+            .clone()])
+    }
+
+    fn get_active_connection_ip4_config(&self, path: &str) -> Result<Option<Ip4Config>> {
+        let state = self.state.borrow();
+
+        let active = state.active_connection(path)?;
+
+        if active.state != ConnectionState::Activated {
+            return Ok(None);
+        }
+
+        Ok(state
+            .configured_device(&active.device)?
+            .and_then(|device| device.ip4_config.clone()))
+    }
+
+    fn get_active_connection_ip6_config(&self, path: &str) -> Result<Option<Ip6Config>> {
+        let state = self.state.borrow();
+
+        let active = state.active_connection(path)?;
+
+        if active.state != ConnectionState::Activated {
+            return Ok(None);
+        }
+
+        Ok(state
+            .configured_device(&active.device)?
+            .and_then(|device| device.ip6_config.clone()))
+    }
+
+    fn delete_connection(&self, path: &str) -> Result<()> {
+        let mut events = Vec::new();
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.connection(path)?;
+
+            let active = state
+                .active_connections
+                .iter()
+                .find(|active| active.connection == path)
+                .map(|active| active.path.clone());
+
+            if let Some(active) = active {
+                state.deactivate(
+                    &active,
+                    ActiveConnectionStateReason::UserDisconnected,
+                    DeviceStateReason::UserRequested,
+                    &mut events,
+                );
+                state.update_state(&mut events);
+            }
+
+            state
+                .connections
+                .retain(|connection| connection.path != path);
+        }
+
+        self.emit(events);
+
+        Ok(())
+    }
+
+    fn add_connection(&self, settings: &ConnectionSettings, _save: bool) -> Result<String> {
+        Ok(self.state.borrow_mut().add_connection(settings))
+    }
+
+    fn update_connection(
+        &self,
+        path: &str,
+        settings: &ConnectionSettings,
+        _save: bool,
+    ) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+
+        state.connection(path)?;
+
+        if let Some(connection) = state.connections.iter_mut().find(|c| c.path == path) {
+            connection.settings = settings.clone();
+            connection.secret = settings_secret(settings);
+        }
+
+        Ok(())
+    }
+
+    fn save_connection(&self, path: &str) -> Result<()> {
+        self.state.borrow().connection(path)?;
+
+        Ok(())
+    }
+
+    fn activate_connection(&self, path: &str) -> Result<String> {
+        let (device, access_point) = {
+            let state = self.state.borrow();
+
+            state.find_device(state.connection(path)?)?
+        };
+
+        self.activate(path, &device, access_point.as_ref().map(|ap| ap as &str))
+    }
+
+    fn deactivate_connection(&self, path: &str) -> Result<()> {
+        let mut events = Vec::new();
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.active_connection(path)?;
+
+            state.deactivate(
+                path,
+                ActiveConnectionStateReason::UserDisconnected,
+                DeviceStateReason::UserRequested,
+                &mut events,
+            );
+            state.update_state(&mut events);
+        }
+
+        self.emit(events);
+
+        Ok(())
+    }
+
+    fn connect_to_access_point(
+        &self,
+        device_path: &str,
+        access_point: &AccessPoint,
+        credentials: &AccessPointCredentials,
+    ) -> Result<(String, String)> {
+        let secret = match *credentials {
+            AccessPointCredentials::None | AccessPointCredentials::Owe => None,
+            AccessPointCredentials::Wep { ref passphrase }
+            | AccessPointCredentials::Wpa { ref passphrase }
+            | AccessPointCredentials::Sae { ref passphrase } => Some(passphrase.clone()),
+            AccessPointCredentials::Enterprise(ref ieee8021x)
+            | AccessPointCredentials::EnterpriseSuiteB192(ref ieee8021x) => {
+                ieee8021x.to_setting()?;
+
+                ieee8021x.secret().map(|secret| secret.to_string())
+            }
+        };
+
+        let path = {
+            let mut state = self.state.borrow_mut();
+
+            state.device(device_path)?;
+            state.access_point(&access_point.path)?;
+
+            state.add_wireless_connection(access_point.ssid(), "infrastructure", secret)
+        };
+
+        let active_path = self.activate(&path, device_path, Some(&access_point.path))?;
+
+        Ok((path, active_path))
+    }
+
+    fn add_and_activate_connection(
+        &self,
+        settings: &ConnectionSettings,
+        device_path: &str,
+    ) -> Result<(String, String)> {
+        let (path, access_point) = {
+            let mut state = self.state.borrow_mut();
+
+            state.device(device_path)?;
+
+            let access_point =
+                if settings.kind() == WIRELESS_SETTING && settings.mode() != Some("ap") {
+                    let access_point = state.connection_access_point(device_path, settings);
+
+                    match access_point {
+                        Some(access_point) => Some(access_point),
+                        None => bail!(ErrorKind::NetworkManager(format!(
+                            "No access point found for {}",
+                            settings.id()
+                        ))),
+                    }
+                } else {
+                    None
+                };
+
+            (state.add_connection(settings), access_point)
+        };
+
+        let active_path = self.activate(
+            &path,
+            device_path,
+            access_point.as_ref().map(|ap| ap as &str),
+        )?;
+
+        Ok((path, active_path))
+    }
+
+    fn get_devices(&self) -> Result<Vec<String>> {
+        Ok(self
+            .state
+            .borrow()
+            .devices
+            .iter()
+            .map(|device| device.path.clone())
+            .collect())
+    }
+
+    fn get_device_by_interface(&self, interface: &str) -> Result<String> {
+        self.state
+            .borrow()
+            .devices
+            .iter()
+            .find(|device| device.interface == interface)
+            .map(|device| device.path.clone())
+            .ok_or_else(|| not_found("device", interface))
+    }
+
+    fn get_device_interface(&self, path: &str) -> Result<String> {
+        Ok(self.state.borrow().device(path)?.interface.clone())
+    }
+
+    fn get_device_type(&self, path: &str) -> Result<DeviceType> {
+        Ok(self.state.borrow().device(path)?.device_type.clone())
+    }
+
+    fn get_device_state(&self, path: &str) -> Result<DeviceState> {
+        Ok(self.state.borrow().device(path)?.state.clone())
+    }
+
+    fn get_device_state_reason(&self, path: &str) -> Result<DeviceStateReason> {
+        Ok(self.state.borrow().device(path)?.state_reason)
+    }
+
+    fn get_device_hw_address(&self, path: &str) -> Result<String> {
+        Ok(self.state.borrow().device(path)?.hw_address.clone())
+    }
+
+    fn get_device_perm_hw_address(&self, path: &str) -> Result<Option<String>> {
+        let state = self.state.borrow();
+
+        let device = state.device(path)?;
+
+        match device.device_type {
+            DeviceType::Ethernet | DeviceType::WiFi => Ok(Some(device.hw_address.clone())),
+            _ => Ok(None),
+        }
+    }
+
+    fn get_device_driver(&self, path: &str) -> Result<String> {
+        Ok(self.state.borrow().device(path)?.driver.clone())
+    }
+
+    fn get_device_driver_version(&self, path: &str) -> Result<String> {
+        self.state.borrow().device(path)?;
+
+        Ok(String::new())
+    }
+
+    fn get_device_firmware_version(&self, path: &str) -> Result<String> {
+        self.state.borrow().device(path)?;
+
+        Ok(String::new())
+    }
+
+    fn get_device_mtu(&self, path: &str) -> Result<u32> {
+        self.state.borrow().device(path)?;
+
+        Ok(1500)
+    }
+
+    fn get_device_capabilities(&self, path: &str) -> Result<DeviceCapabilities> {
+        match self.state.borrow().device(path)?.device_type {
+            DeviceType::Ethernet => {
+                Ok(DeviceCapabilities::CAP_NM_SUPPORTED | DeviceCapabilities::CAP_CARRIER_DETECT)
+            }
+            _ => Ok(DeviceCapabilities::CAP_NM_SUPPORTED),
+        }
+    }
+
+    fn is_device_managed(&self, path: &str) -> Result<bool> {
+        Ok(self.state.borrow().device(path)?.managed)
+    }
+
+    fn get_device_autoconnect(&self, path: &str) -> Result<bool> {
+        self.state.borrow().device(path)?;
+
+        Ok(true)
+    }
+
+    fn get_device_ip4_connectivity(&self, path: &str) -> Result<Connectivity> {
+        let state = self.state.borrow();
+
+        match state.configured_device(path)? {
+            Some(_) => Ok(state.connectivity.clone()),
+            None => Ok(Connectivity::None),
+        }
+    }
+
+    fn get_device_udi(&self, path: &str) -> Result<String> {
+        Ok(format!(
+            "/sys/devices/virtual/net/{}",
+            self.state.borrow().device(path)?.interface
+        ))
+    }
+
+    fn is_device_real(&self, path: &str) -> Result<bool> {
+        self.state.borrow().device(path)?;
+
+        Ok(true)
+    }
+
+    fn get_device_available_connections(&self, path: &str) -> Result<Vec<String>> {
+        let state = self.state.borrow();
+
+        if !state.device(path)?.managed {
+            return Ok(Vec::new());
+        }
+
+        Ok(state
+            .connections
+            .iter()
+            .filter(|connection| match state.find_device(connection) {
+                Ok((device_path, _)) => device_path == path,
+                Err(_) => false,
+            })
+            .map(|connection| connection.path.clone())
+            .collect())
+    }
+
+    fn get_device_ip4_config(&self, path: &str) -> Result<Option<Ip4Config>> {
+        Ok(self
+            .state
+            .borrow()
+            .configured_device(path)?
+            .and_then(|device| device.ip4_config.clone()))
+    }
+
+    fn get_device_ip6_config(&self, path: &str) -> Result<Option<Ip6Config>> {
+        Ok(self
+            .state
+            .borrow()
+            .configured_device(path)?
+            .and_then(|device| device.ip6_config.clone()))
+    }
+
+    fn get_device_dhcp4_config(&self, path: &str) -> Result<Option<Dhcp4Config>> {
+        Ok(self
+            .state
+            .borrow()
+            .configured_device(path)?
+            .and_then(|device| device.dhcp4_config.clone()))
+    }
+
+    fn get_device_dhcp6_config(&self, path: &str) -> Result<Option<Dhcp6Config>> {
+        Ok(self
+            .state
+            .borrow()
+            .configured_device(path)?
+            .and_then(|device| device.dhcp6_config.clone()))
+    }
+
+    fn connect_device(&self, path: &str) -> Result<()> {
+        let (connection, access_point) = {
+            let state = self.state.borrow();
+
+            let device = state.device(path)?;
+
+            let mut candidates = state
+                .connections
+                .iter()
+                .filter(|connection| device.last_connection.as_ref() == Some(&connection.path))
+                .chain(state.connections.iter());
+
+            let found = candidates.find_map(|connection| match state.find_device(connection) {
+                Ok((device_path, access_point)) if device_path == path => {
+                    Some((connection.path.clone(), access_point))
+                }
+                _ => None,
+            });
+
+            match found {
+                Some(found) => found,
+                None => bail!(ErrorKind::NetworkManager(format!(
+                    "No suitable connection found for device {}",
+                    path
+                ))),
+            }
+        };
+
+        self.activate(
+            &connection,
+            path,
+            access_point.as_ref().map(|ap| ap as &str),
+        )?;
+
+        Ok(())
+    }
+
+    fn disconnect_device(&self, path: &str) -> Result<()> {
+        let mut events = Vec::new();
+
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.device(path)?;
+
+            if let Some(active) = state.device_active_connection(path) {
+                state.deactivate(
+                    &active,
+                    ActiveConnectionStateReason::UserDisconnected,
+                    DeviceStateReason::UserRequested,
+                    &mut events,
+                );
+            }
+
+            state.update_state(&mut events);
+        }
+
+        self.emit(events);
+
+        Ok(())
+    }
+
+    fn get_wired_carrier(&self, path: &str) -> Result<bool> {
+        Ok(self.state.borrow().device(path)?.carrier)
+    }
+
+    fn get_wired_speed(&self, path: &str) -> Result<u32> {
+        Ok(self.state.borrow().device(path)?.speed)
+    }
+
+    fn get_wired_s390_subchannels(&self, path: &str) -> Result<Vec<String>> {
+        self.state.borrow().device(path)?;
+
+        Ok(Vec::new())
+    }
+
+    fn get_modem_capabilities(&self, path: &str) -> Result<ModemCapabilities> {
+        Ok(self.state.borrow().modem(path)?.capabilities)
+    }
+
+    fn get_modem_current_capabilities(&self, path: &str) -> Result<ModemCapabilities> {
+        Ok(self.state.borrow().modem(path)?.capabilities)
+    }
+
+    fn get_modem_device_id(&self, path: &str) -> Result<String> {
+        Ok(self.state.borrow().modem(path)?.device_id.clone())
+    }
+
+    fn get_modem_operator_code(&self, path: &str) -> Result<String> {
+        Ok(self.state.borrow().modem(path)?.operator_code.clone())
+    }
+
+    fn request_access_point_scan(&self, path: &str, _ssids: &[&SsidSlice]) -> Result<()> {
+        {
+            let mut state = self.state.borrow_mut();
+
+            state.device(path)?;
+
+            if let Some(device) = state.device_mut(path) {
+                if device.scans_refused > 0 {
+                    device.scans_refused -= 1;
+
+                    bail!(ErrorKind::ScanNotAllowed(
+                        "Scanning not allowed while already scanning".into()
+                    ));
+                }
+
+                // Scans are reported to complete a second apart
+                device.last_scan = device.last_scan.max(0) + 1000;
+            }
+        }
+
+        self.emit(vec![NetworkManagerEvent::PropertiesChanged {
+            path: path.to_string(),
+            interface: NM_WIRELESS_INTERFACE.to_string(),
+            properties: vec!["LastScan".to_string()],
+        }]);
+
+        Ok(())
+    }
+
+    fn get_device_last_scan(&self, path: &str) -> Result<i64> {
+        Ok(self.state.borrow().device(path)?.last_scan)
+    }
+
+    fn get_device_access_points(&self, path: &str) -> Result<Vec<String>> {
+        let state = self.state.borrow();
+
+        state.device(path)?;
+
+        Ok(state
+            .access_points
+            .iter()
+            .filter(|ap| ap.device == path)
+            .map(|ap| ap.path.clone())
+            .collect())
+    }
+
+    fn get_access_point_ssid(&self, path: &str) -> Option<Ssid> {
+        self.state.borrow().access_point(path).ok().map(|ap| {
+            if ap.hidden {
+                Ssid::new()
+            } else {
+                ap.ssid.clone()
+            }
+        })
+    }
+
+    fn get_access_point_strength(&self, path: &str) -> Result<u32> {
+        Ok(self.state.borrow().access_point(path)?.strength)
+    }
+
+    fn get_access_point_flags(&self, path: &str) -> Result<NM80211ApFlags> {
+        if self.state.borrow().access_point(path)?.security == Security::NONE {
+            Ok(NM80211ApFlags::AP_FLAGS_NONE)
+        } else {
+            Ok(NM80211ApFlags::AP_FLAGS_PRIVACY)
+        }
+    }
+
+    fn get_access_point_wpa_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
+        let security = self.state.borrow().access_point(path)?.security;
+
+        Ok(security_flags(security, Security::WPA))
+    }
+
+    fn get_access_point_rsn_flags(&self, path: &str) -> Result<NM80211ApSecurityFlags> {
+        let security = self.state.borrow().access_point(path)?.security;
+
+        Ok(security_flags(security, Security::WPA2))
+    }
+
+    fn get_access_point_bssid(&self, path: &str) -> Result<String> {
+        Ok(self.state.borrow().access_point(path)?.bssid.clone())
+    }
+
+    fn get_access_point_frequency(&self, path: &str) -> Result<u32> {
+        Ok(self.state.borrow().access_point(path)?.frequency)
+    }
+
+    fn get_access_point_max_bitrate(&self, path: &str) -> Result<u32> {
+        self.state.borrow().access_point(path)?;
+
+        Ok(54_000)
+    }
+
+    fn get_access_point_mode(&self, path: &str) -> Result<NM80211Mode> {
+        self.state.borrow().access_point(path)?;
+
+        Ok(NM80211Mode::Infra)
+    }
+
+    fn get_access_point_last_seen(&self, path: &str) -> Result<i32> {
+        self.state.borrow().access_point(path)?;
+
+        Ok(0)
+    }
+}
+
+/// Builds the WPA or RSN flags an access point with `security` advertises.
+fn security_flags(security: Security, protocol: Security) -> NM80211ApSecurityFlags {
+    let mut key_management = NM80211ApSecurityFlags::AP_SEC_NONE;
+
+    if security.contains(protocol) {
+        key_management |= if security.contains(Security::ENTERPRISE) {
+            NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_802_1X
+        } else {
+            NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_PSK
+        };
+    }
+
+    if protocol == Security::WPA2 {
+        if security.contains(Security::WPA3) {
+            key_management |= NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE;
+        }
+
+        if security.contains(Security::OWE) {
+            key_management |= NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_OWE;
+        }
+
+        if security.contains(Security::SUITE_B_192) {
+            key_management |= NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_EAP_SUITE_B_192;
+        }
+    }
+
+    if key_management == NM80211ApSecurityFlags::AP_SEC_NONE {
+        return key_management;
+    }
+
+    let ciphers = if protocol == Security::WPA {
+        NM80211ApSecurityFlags::AP_SEC_PAIR_TKIP | NM80211ApSecurityFlags::AP_SEC_GROUP_TKIP
+    } else {
+        NM80211ApSecurityFlags::AP_SEC_PAIR_CCMP | NM80211ApSecurityFlags::AP_SEC_GROUP_CCMP
+    };
+
+    ciphers | key_management
+}
+
+/// The secret compared to the access point passphrase on activation.
+fn settings_secret(settings: &ConnectionSettings) -> Option<String> {
+    if let Some(ref security) = settings.wireless_security {
+        if let Some(ref secret) = security.psk.as_ref().or(security.wep_key0.as_ref()) {
+            return Some(secret.to_string());
+        }
+    }
+
+    settings
+        .ieee8021x
+        .as_ref()
+        .and_then(|ieee8021x| ieee8021x.password.clone())
+}
+
+fn not_found(kind: &str, path: &str) -> Error {
+    ErrorKind::NetworkManager(format!("No such {}: {}", kind, path)).into()
+}
+
+/// Fixtures shared by the tests of the modules built on the backend.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    use manager::NetworkManager;
+    use settings::WirelessSecuritySetting;
+
+    /// A Wi-Fi device `wlan0` in range of the open network `open` and the
+    /// WPA2 network `office` with the passphrase `password`. Returns the fake,
+    /// a manager on top of it and the device path.
+    pub fn wifi() -> (Rc<FakeNetworkManager>, NetworkManager, String) {
+        let fake = Rc::new(FakeNetworkManager::new());
+        let device = fake.add_device("wlan0", DeviceType::WiFi);
+
+        fake.add_access_point(&device, "open", 40, Security::NONE, None);
+        fake.add_access_point(&device, "office", 80, Security::WPA2, Some("password"));
+
+        let manager = NetworkManager::with_backend(fake.clone());
+
+        (fake, manager, device)
+    }
+
+    /// The settings of a WPA2 connection profile for `ssid`.
+    pub fn wpa_settings(ssid: &str, psk: &str) -> ConnectionSettings {
+        ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some(ssid.into()),
+                uuid: Some("0b3a4a72-49a1-4b3a-8f5e-3c2d1e0f9a87".into()),
+                kind: Some(WIRELESS_SETTING.into()),
+                ..Default::default()
+            },
+            wireless: Some(WirelessSetting {
+                ssid: Some(Ssid::from_bytes(ssid).unwrap()),
+                ..Default::default()
+            }),
+            wireless_security: Some(WirelessSecuritySetting {
+                key_mgmt: Some("wpa-psk".into()),
+                psk: Some(psk.into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fake::fixtures;
+
+    use hotspot::HotspotConfig;
+
+    #[test]
+    fn test_access_points() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let access_points = device
+            .as_wifi_device()
+            .unwrap()
+            .get_access_points()
+            .unwrap();
+
+        assert_eq!(access_points.len(), 2);
+        assert_eq!(access_points[0].ssid().as_str().unwrap(), "office");
+        assert_eq!(access_points[0].security, Security::WPA2);
+        assert_eq!(access_points[1].security, Security::NONE);
+        assert_eq!(access_points[0].channel(), Some(6));
+        assert_ne!(access_points[0].bssid, access_points[1].bssid);
+    }
+
+    #[test]
+    fn test_connect() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+        let access_points = wifi_device.get_access_points().unwrap();
+
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "password".into(),
+        };
+        let (connection, state) = wifi_device
+            .connect(&access_points[0], &credentials)
+            .unwrap();
+
+        assert_eq!(state, ConnectionState::Activated);
+        assert_eq!(connection.settings().id(), "office");
+        assert_eq!(device.get_state().unwrap(), DeviceState::Activated);
+        assert_eq!(
+            manager.get_state().unwrap(),
+            NetworkManagerState::ConnectedGlobal
+        );
+        assert_eq!(manager.get_connectivity().unwrap(), Connectivity::Full);
+    }
+
+    #[test]
+    fn test_connect_wrong_passphrase() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+        let access_points = wifi_device.get_access_points().unwrap();
+
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "wrong password".into(),
+        };
+        match wifi_device.connect(&access_points[0], &credentials) {
+            Err(Error(ErrorKind::ActivationFailed(reason, device_reason), _)) => {
+                assert_eq!(reason, ActiveConnectionStateReason::NoSecrets);
+                assert_eq!(device_reason, DeviceStateReason::NoSecrets);
+            }
+            result => panic!("Unexpected connect result: {:?}", result),
+        }
+
+        assert_eq!(device.get_state().unwrap(), DeviceState::Disconnected);
+        assert!(manager.get_active_connections().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reactivate_saved_connection() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+        let access_points = wifi_device.get_access_points().unwrap();
+
+        let (connection, _) = wifi_device
+            .connect(&access_points[1], &AccessPointCredentials::None)
+            .unwrap();
+
+        assert_eq!(
+            connection.deactivate().unwrap(),
+            ConnectionState::Deactivated
+        );
+        assert_eq!(device.get_state().unwrap(), DeviceState::Disconnected);
+
+        assert_eq!(device.connect().unwrap(), DeviceState::Activated);
+        assert_eq!(connection.get_state().unwrap(), ConnectionState::Activated);
+    }
+
+    #[test]
+    fn test_fail_activations() {
+        let (fake, manager, _) = fixtures::wifi();
+
+        fake.fail_activations(true);
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let result = device
+            .as_wifi_device()
+            .unwrap()
+            .create_hotspot("hotspot", &HotspotConfig::new());
+
+        match result {
+            Err(Error(ErrorKind::ActivationFailed(reason, device_reason), _)) => {
+                assert_eq!(reason, ActiveConnectionStateReason::DeviceDisconnected);
+                assert_eq!(device_reason, DeviceStateReason::ConfigFailed);
+            }
+            result => panic!("Unexpected hotspot result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_link_loss_events() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        device
+            .as_wifi_device()
+            .unwrap()
+            .create_hotspot("hotspot", &HotspotConfig::new())
+            .unwrap();
+
+        let mut events = manager.subscribe().unwrap();
+
+        fake.set_device_state(
+            &device_path,
+            DeviceState::Unavailable,
+            DeviceStateReason::CarrierLost,
+        );
+
+        let mut received = Vec::new();
+        while let Some(event) = events.next_timeout(Duration::from_secs(0)).unwrap() {
+            received.push(event);
+        }
+
+        assert!(received.contains(&NetworkManagerEvent::DeviceStateChanged {
+            path: device_path,
+            new_state: DeviceState::Unavailable,
+            old_state: DeviceState::Disconnected,
+            reason: DeviceStateReason::CarrierLost,
+        }));
+        assert!(received.contains(&NetworkManagerEvent::StateChanged(
+            NetworkManagerState::Disconnected
+        )));
+    }
+}
@@ -0,0 +1,517 @@
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+use errors::*;
+
+use settings::{
+    ConnectionSetting, ConnectionSettings, IpAddress, Ipv4Setting, Ipv6Setting,
+    WirelessSecuritySetting, WirelessSetting, WIRELESS_SETTING,
+};
+use ssid::{Ssid, SsidSlice};
+use wifi::{verify_ascii_password, WiFiBand};
+
+const DEFAULT_PREFIX: u32 = 24;
+
+/// Where Network Manager keeps the dnsmasq lease files of shared connections.
+const LEASE_FILE_DIR: &str = "/var/lib/NetworkManager";
+
+/// The security of a password protected hotspot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HotspotSecurity {
+    /// WPA2-Personal.
+    #[default]
+    Wpa2,
+    /// WPA3-Personal (SAE).
+    Wpa3,
+}
+
+/// Protected Management Frames, i.e. the `pmf` setting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Pmf {
+    /// Use the global default of Network Manager.
+    #[default]
+    Default,
+    Disable,
+    Optional,
+    Required,
+}
+
+impl Pmf {
+    fn value(self) -> i32 {
+        match self {
+            Pmf::Default => 0,
+            Pmf::Disable => 1,
+            Pmf::Optional => 2,
+            Pmf::Required => 3,
+        }
+    }
+}
+
+/// The options of an access point connection created with
+/// `WiFiDevice::create_hotspot`.
+///
+/// The default is an open 2.4 GHz hotspot sharing the IPv4 connectivity of
+/// the host, on an automatically picked channel:
+///
+/// ```
+/// use network_manager::{HotspotConfig, HotspotSecurity, WiFiBand};
+///
+/// let config = HotspotConfig::new()
+///     .password("password")
+///     .security(HotspotSecurity::Wpa3)
+///     .band(WiFiBand::FiveGhz)
+///     .channel(36);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HotspotConfig {
+    id: Option<String>,
+    password: Option<String>,
+    security: HotspotSecurity,
+    pmf: Pmf,
+    band: WiFiBand,
+    channel: Option<u32>,
+    hidden: bool,
+    address: Option<Ipv4Addr>,
+    prefix: Option<u32>,
+    ipv6_shared: bool,
+    autoconnect: bool,
+}
+
+impl Default for HotspotConfig {
+    fn default() -> Self {
+        HotspotConfig {
+            id: None,
+            password: None,
+            security: HotspotSecurity::default(),
+            pmf: Pmf::default(),
+            band: WiFiBand::TwoPointFourGhz,
+            channel: None,
+            hidden: false,
+            address: None,
+            prefix: None,
+            ipv6_shared: false,
+            autoconnect: false,
+        }
+    }
+}
+
+impl HotspotConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The connection id. Defaults to the SSID.
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Protects the hotspot with a password.
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Selects WPA2 or WPA3 for password protected hotspots.
+    pub fn security(mut self, security: HotspotSecurity) -> Self {
+        self.security = security;
+        self
+    }
+
+    pub fn pmf(mut self, pmf: Pmf) -> Self {
+        self.pmf = pmf;
+        self
+    }
+
+    /// The 2.4 or 5 GHz band.
+    pub fn band(mut self, band: WiFiBand) -> Self {
+        self.band = band;
+        self
+    }
+
+    /// A fixed channel, which has to be in the selected band.
+    pub fn channel(mut self, channel: u32) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Stops broadcasting the SSID.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// A static IPv4 address for the hotspot instead of a shared network.
+    pub fn address(mut self, address: Ipv4Addr) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// The prefix length of the static address. Defaults to 24 and requires
+    /// `address` to be set.
+    pub fn prefix(mut self, prefix: u32) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Shares the IPv6 connectivity of the host as well.
+    pub fn ipv6_shared(mut self, ipv6_shared: bool) -> Self {
+        self.ipv6_shared = ipv6_shared;
+        self
+    }
+
+    /// Activates the hotspot automatically, e.g. after a reboot.
+    pub fn autoconnect(mut self, autoconnect: bool) -> Self {
+        self.autoconnect = autoconnect;
+        self
+    }
+
+    /// Builds the settings of the access point connection bound to
+    /// `interface`.
+    pub fn to_settings(&self, ssid: &SsidSlice, interface: &str) -> Result<ConnectionSettings> {
+        let band = match self.band {
+            WiFiBand::TwoPointFourGhz => "bg",
+            WiFiBand::FiveGhz => "a",
+            WiFiBand::SixGhz => bail!(ErrorKind::NetworkManager(
+                "Hotspots are not supported on the 6 GHz band".into()
+            )),
+        };
+
+        let prefix = match (self.address, self.prefix) {
+            (None, Some(_)) => bail!(ErrorKind::NetworkManager(
+                "A hotspot prefix length requires a static address".into()
+            )),
+            (_, Some(prefix)) if prefix == 0 || prefix > 32 => bail!(ErrorKind::NetworkManager(
+                format!("Invalid hotspot prefix length: {}", prefix)
+            )),
+            (_, prefix) => prefix.unwrap_or(DEFAULT_PREFIX),
+        };
+
+        let id = match self.id {
+            Some(ref id) => id.clone(),
+            None => String::from_utf8_lossy(ssid.as_bytes()).into_owned(),
+        };
+
+        let wireless_security = match self.password {
+            Some(ref password) => {
+                let (key_mgmt, psk) = match self.security {
+                    HotspotSecurity::Wpa2 => ("wpa-psk", verify_ascii_password(password)?),
+                    HotspotSecurity::Wpa3 if password.is_empty() => {
+                        bail!(ErrorKind::PreSharedKey("Empty password".into()))
+                    }
+                    HotspotSecurity::Wpa3 => ("sae", password as &str),
+                };
+
+                Some(WirelessSecuritySetting {
+                    key_mgmt: Some(key_mgmt.into()),
+                    psk: Some(psk.into()),
+                    pmf: Some(self.pmf.value()),
+                    ..Default::default()
+                })
+            }
+            None => None,
+        };
+
+        let ipv4 = match self.address {
+            Some(address) => Ipv4Setting {
+                method: Some("manual".into()),
+                address_data: Some(vec![IpAddress {
+                    address: IpAddr::V4(address),
+                    prefix,
+                    attributes: Default::default(),
+                }]),
+                ..Default::default()
+            },
+            None => Ipv4Setting {
+                method: Some("shared".into()),
+                ..Default::default()
+            },
+        };
+
+        let ipv6 = if self.ipv6_shared {
+            Some(Ipv6Setting {
+                method: Some("shared".into()),
+                ..Default::default()
+            })
+        } else {
+            None
+        };
+
+        Ok(ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some(id),
+                kind: Some(WIRELESS_SETTING.into()),
+                interface_name: Some(interface.into()),
+                autoconnect: Some(self.autoconnect),
+                ..Default::default()
+            },
+            wireless: Some(WirelessSetting {
+                ssid: Some(Ssid::from_bytes(ssid.as_bytes().to_vec())?),
+                mode: Some("ap".into()),
+                band: Some(band.into()),
+                channel: self.channel,
+                hidden: Some(self.hidden),
+                ..Default::default()
+            }),
+            wireless_security,
+            ipv4: Some(ipv4),
+            ipv6,
+            ..Default::default()
+        })
+    }
+}
+
+/// A client that leased an address from a hotspot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HotspotClient {
+    pub mac_address: String,
+    pub ip_address: IpAddr,
+    pub hostname: Option<String>,
+    pub client_id: Option<String>,
+    /// When the lease expires in seconds since the Unix epoch, or `None` for
+    /// infinite leases.
+    pub expires: Option<u64>,
+}
+
+/// The dnsmasq lease file Network Manager writes for a shared connection on
+/// `interface`.
+pub fn lease_file_path(interface: &str) -> PathBuf {
+    Path::new(LEASE_FILE_DIR).join(format!("dnsmasq-{}.leases", interface))
+}
+
+/// Reads the clients from a dnsmasq lease file. A missing file is treated as
+/// empty, as dnsmasq creates it only once the first lease is handed out.
+pub fn read_hotspot_clients<P>(lease_file: P) -> Result<Vec<HotspotClient>>
+where
+    P: AsRef<Path>,
+{
+    let lease_file = lease_file.as_ref();
+
+    match fs::read_to_string(lease_file) {
+        Ok(contents) => Ok(parse_leases(&contents)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).chain_err(|| {
+            ErrorKind::NetworkManager(format!(
+                "Unable to read lease file {}",
+                lease_file.display()
+            ))
+        }),
+    }
+}
+
+/// Parses IPv4 leases in the `<expiry> <mac> <ip> <hostname> <client id>`
+/// format. IPv6 leases, which have an IAID in place of the MAC address, and
+/// malformed lines are skipped.
+fn parse_leases(contents: &str) -> Vec<HotspotClient> {
+    let optional = |field: &str| match field {
+        "*" => None,
+        field => Some(field.to_string()),
+    };
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+
+            if fields.len() != 5 || !is_mac_address(fields[1]) {
+                return None;
+            }
+
+            let expires = fields[0].parse::<u64>().ok()?;
+
+            Some(HotspotClient {
+                mac_address: fields[1].to_lowercase(),
+                ip_address: fields[2].parse().ok()?,
+                hostname: optional(fields[3]),
+                client_id: optional(fields[4]),
+                expires: if expires == 0 { None } else { Some(expires) },
+            })
+        })
+        .collect()
+}
+
+fn is_mac_address(value: &str) -> bool {
+    let bytes = value.split(':').collect::<Vec<_>>();
+
+    bytes.len() == 6
+        && bytes
+            .iter()
+            .all(|byte| byte.len() == 2 && u8::from_str_radix(byte, 16).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use connection::ConnectionState;
+    use fake::fixtures;
+    use ssid::AsSsidSlice;
+    use wifi::AccessPointCredentials;
+
+    #[test]
+    fn test_hotspot_settings() {
+        let ssid = "hotspot".as_ssid_slice().unwrap();
+
+        let settings = HotspotConfig::new()
+            .password("password")
+            .security(HotspotSecurity::Wpa3)
+            .pmf(Pmf::Required)
+            .band(WiFiBand::FiveGhz)
+            .channel(36)
+            .hidden(true)
+            .address(Ipv4Addr::new(192, 168, 42, 1))
+            .prefix(28)
+            .ipv6_shared(true)
+            .to_settings(ssid, "wlan0")
+            .unwrap();
+
+        let wireless = settings.wireless.as_ref().unwrap();
+        assert_eq!(wireless.band, Some("a".to_string()));
+        assert_eq!(wireless.channel, Some(36));
+        assert_eq!(wireless.hidden, Some(true));
+
+        let security = settings.wireless_security.as_ref().unwrap();
+        assert_eq!(security.key_mgmt, Some("sae".to_string()));
+        assert_eq!(security.pmf, Some(3));
+
+        let address_data = settings.ipv4.as_ref().unwrap().address_data.as_ref();
+        assert_eq!(address_data.unwrap()[0].prefix, 28);
+        assert_eq!(
+            settings.ipv6.as_ref().unwrap().method,
+            Some("shared".to_string())
+        );
+        assert_eq!(settings.id(), "hotspot");
+        assert_eq!(settings.connection.autoconnect, Some(false));
+    }
+
+    #[test]
+    fn test_invalid_hotspot_settings() {
+        let ssid = "hotspot".as_ssid_slice().unwrap();
+
+        assert!(HotspotConfig::new()
+            .password("short")
+            .to_settings(ssid, "wlan0")
+            .is_err());
+        assert!(HotspotConfig::new()
+            .band(WiFiBand::SixGhz)
+            .to_settings(ssid, "wlan0")
+            .is_err());
+        assert!(HotspotConfig::new()
+            .address(Ipv4Addr::new(192, 168, 42, 1))
+            .prefix(33)
+            .to_settings(ssid, "wlan0")
+            .is_err());
+        assert!(HotspotConfig::new()
+            .prefix(28)
+            .to_settings(ssid, "wlan0")
+            .is_err());
+    }
+
+    #[test]
+    fn test_read_hotspot_clients() {
+        let clients = read_hotspot_clients(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/dnsmasq-wlan0.leases"
+        ))
+        .unwrap();
+
+        assert_eq!(clients.len(), 3);
+        assert_eq!(
+            clients[0],
+            HotspotClient {
+                mac_address: "a4:5e:60:d1:22:01".into(),
+                ip_address: "10.42.0.23".parse().unwrap(),
+                hostname: Some("iPhone".into()),
+                client_id: Some("01:a4:5e:60:d1:22:01".into()),
+                expires: Some(1_767_225_600),
+            }
+        );
+        assert_eq!(clients[1].hostname, None);
+        assert_eq!(clients[2].expires, None);
+
+        assert_eq!(
+            read_hotspot_clients("/nonexistent/dnsmasq-wlan0.leases").unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            lease_file_path("wlan0"),
+            Path::new("/var/lib/NetworkManager/dnsmasq-wlan0.leases")
+        );
+    }
+
+    #[test]
+    fn test_create_hotspot_config() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let config = HotspotConfig::new()
+            .id("onboarding")
+            .password("password")
+            .band(WiFiBand::FiveGhz)
+            .channel(36);
+
+        let (connection, state) = device
+            .as_wifi_device()
+            .unwrap()
+            .create_hotspot("hotspot", &config)
+            .unwrap();
+
+        assert_eq!(state, ConnectionState::Activated);
+
+        let settings = connection.settings();
+        assert_eq!(settings.id(), "onboarding");
+        assert!(!settings.uuid().is_empty());
+        assert_eq!(settings.mode(), Some("ap"));
+        assert_eq!(
+            settings.wireless.as_ref().unwrap().band,
+            Some("a".to_string())
+        );
+        assert_eq!(
+            settings.connection.interface_name,
+            Some("wlan0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_hotspot_clients() {
+        let (_, manager, _) = fixtures::wifi();
+
+        let lease_file = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/dnsmasq-wlan0.leases"
+        );
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+
+        let (connection, _) = wifi_device
+            .create_hotspot("hotspot", &HotspotConfig::new())
+            .unwrap();
+
+        assert!(!connection
+            .hotspot_clients_from(lease_file)
+            .unwrap()
+            .is_empty());
+
+        connection.deactivate().unwrap();
+
+        assert!(connection
+            .hotspot_clients_from(lease_file)
+            .unwrap()
+            .is_empty());
+
+        let access_point = wifi_device
+            .get_access_points()
+            .unwrap()
+            .into_iter()
+            .find(|access_point| access_point.ssid().as_str().unwrap() == "open")
+            .unwrap();
+        let (client, _) = wifi_device
+            .connect(&access_point, &AccessPointCredentials::None)
+            .unwrap();
+
+        assert!(client.hotspot_clients_from(lease_file).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use settings::{SettingGroup, SettingType};
+
+/// The IPv4 configuration of a device or active connection.
+pub type Ip4Config = IpConfig<Ipv4Addr>;
+
+/// The IPv6 configuration of a device or active connection.
+pub type Ip6Config = IpConfig<Ipv6Addr>;
+
+/// The contents of an `IP4Config` or `IP6Config` object, i.e. the addresses,
+/// routes and DNS configuration currently applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpConfig<A> {
+    pub addresses: Vec<AddressData<A>>,
+    pub gateway: Option<A>,
+    pub routes: Vec<RouteData<A>>,
+    pub nameservers: Vec<A>,
+    pub domains: Vec<String>,
+    pub searches: Vec<String>,
+}
+
+impl<A> Default for IpConfig<A> {
+    fn default() -> Self {
+        IpConfig {
+            addresses: Vec::new(),
+            gateway: None,
+            routes: Vec::new(),
+            nameservers: Vec::new(),
+            domains: Vec::new(),
+            searches: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressData<A> {
+    pub address: A,
+    pub prefix: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteData<A> {
+    pub dest: A,
+    pub prefix: u32,
+    pub next_hop: Option<A>,
+    pub metric: Option<u32>,
+}
+
+impl<A: FromStr> AddressData<A> {
+    /// Decodes an entry of the `AddressData` property.
+    pub fn from_attributes(attributes: &SettingGroup) -> Option<Self> {
+        Some(AddressData {
+            address: attributes.get("address")?.as_str()?.parse().ok()?,
+            prefix: u32::from_setting(attributes.get("prefix")?)?,
+        })
+    }
+}
+
+impl<A: FromStr> RouteData<A> {
+    /// Decodes an entry of the `RouteData` property.
+    pub fn from_attributes(attributes: &SettingGroup) -> Option<Self> {
+        let next_hop = match attributes.get("next-hop") {
+            Some(next_hop) => Some(next_hop.as_str()?.parse().ok()?),
+            None => None,
+        };
+
+        let metric = match attributes.get("metric") {
+            Some(metric) => Some(u32::from_setting(metric)?),
+            None => None,
+        };
+
+        Some(RouteData {
+            dest: attributes.get("dest")?.as_str()?.parse().ok()?,
+            prefix: u32::from_setting(attributes.get("prefix")?)?,
+            next_hop,
+            metric,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fake::fixtures;
+    use settings::SettingValue;
+    use wifi::AccessPointCredentials;
+
+    #[test]
+    fn test_address_data() {
+        let mut address = SettingGroup::new();
+        address.insert("address".into(), SettingValue::String("10.0.0.5".into()));
+        address.insert("prefix".into(), SettingValue::UInt32(24));
+
+        assert_eq!(
+            AddressData::from_attributes(&address),
+            Some(AddressData {
+                address: Ipv4Addr::new(10, 0, 0, 5),
+                prefix: 24,
+            })
+        );
+        assert_eq!(AddressData::<Ipv6Addr>::from_attributes(&address), None);
+
+        address.remove("prefix");
+
+        assert_eq!(AddressData::<Ipv4Addr>::from_attributes(&address), None);
+    }
+
+    #[test]
+    fn test_route_data() {
+        let mut route = SettingGroup::new();
+        route.insert("dest".into(), SettingValue::String("10.1.0.0".into()));
+        route.insert("prefix".into(), SettingValue::UInt32(16));
+        route.insert("next-hop".into(), SettingValue::String("10.0.0.1".into()));
+        route.insert("metric".into(), SettingValue::UInt32(600));
+
+        assert_eq!(
+            RouteData::from_attributes(&route),
+            Some(RouteData {
+                dest: Ipv4Addr::new(10, 1, 0, 0),
+                prefix: 16,
+                next_hop: Some(Ipv4Addr::new(10, 0, 0, 1)),
+                metric: Some(600),
+            })
+        );
+
+        route.remove("next-hop");
+        route.remove("metric");
+
+        let route = RouteData::<Ipv4Addr>::from_attributes(&route).unwrap();
+        assert_eq!(route.next_hop, None);
+        assert_eq!(route.metric, None);
+    }
+
+    #[test]
+    fn test_ip_config() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        let config = Ip4Config {
+            addresses: vec![AddressData {
+                address: Ipv4Addr::new(192, 168, 1, 20),
+                prefix: 24,
+            }],
+            gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+            nameservers: vec![Ipv4Addr::new(192, 168, 1, 1)],
+            ..Default::default()
+        };
+        fake.set_ip4_config(&device_path, Some(config.clone()));
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        assert_eq!(device.ip4_config().unwrap(), None);
+
+        let wifi_device = device.as_wifi_device().unwrap();
+        let access_points = wifi_device.get_access_points().unwrap();
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "password".into(),
+        };
+        let (connection, _) = wifi_device
+            .connect(&access_points[0], &credentials)
+            .unwrap();
+
+        assert_eq!(device.ip4_config().unwrap(), Some(config.clone()));
+        assert_eq!(connection.ip4_config().unwrap(), Some(config));
+        assert_eq!(connection.ip6_config().unwrap(), None);
+
+        connection.deactivate().unwrap();
+
+        assert_eq!(device.ip4_config().unwrap(), None);
+        assert_eq!(connection.ip4_config().unwrap(), None);
+    }
+}
@@ -0,0 +1,1158 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use errors::*;
+
+use settings::{
+    setting_signature, ConnectionSettings, IpAddress, IpRoute, SettingGroup, SettingType,
+    SettingValue, CONNECTION_SETTING, ETHERNET_SETTING, IEEE8021X_SETTING, IPV4_SETTING,
+    IPV6_SETTING, VPN_SETTING, WIRELESS_SETTING,
+};
+
+/// Setting names that are written differently in keyfiles.
+const SETTING_ALIASES: &[(&str, &str)] = &[
+    ("802-3-ethernet", "ethernet"),
+    ("802-11-wireless", "wifi"),
+    ("802-11-wireless-security", "wifi-security"),
+];
+
+/// Dictionary keys that are stored as separate keyfile groups.
+const DICT_GROUPS: &[(&str, &str, &str)] = &[
+    (VPN_SETTING, "secrets", "vpn-secrets"),
+    (ETHERNET_SETTING, "s390-options", "ethernet-s390-options"),
+];
+
+const MAC_ADDRESS_KEYS: &[(&str, &str)] = &[
+    (WIRELESS_SETTING, "bssid"),
+    (WIRELESS_SETTING, "mac-address"),
+    (WIRELESS_SETTING, "cloned-mac-address"),
+    (ETHERNET_SETTING, "mac-address"),
+    (ETHERNET_SETTING, "cloned-mac-address"),
+];
+
+const CERTIFICATE_KEYS: &[&str] = &[
+    "ca-cert",
+    "client-cert",
+    "private-key",
+    "phase2-ca-cert",
+    "phase2-client-cert",
+    "phase2-private-key",
+];
+
+/// Types of common keys without a typed field. Other unknown keys are read
+/// as strings.
+const KNOWN_SIGNATURES: &[(&str, &str, &str)] = &[
+    (CONNECTION_SETTING, "auth-retries", "i"),
+    (CONNECTION_SETTING, "autoconnect-slaves", "i"),
+    (CONNECTION_SETTING, "gateway-ping-timeout", "u"),
+    (CONNECTION_SETTING, "lldp", "i"),
+    (CONNECTION_SETTING, "llmnr", "i"),
+    (CONNECTION_SETTING, "mdns", "i"),
+    (CONNECTION_SETTING, "multi-connect", "i"),
+    (CONNECTION_SETTING, "secondaries", "as"),
+    (CONNECTION_SETTING, "wait-device-timeout", "i"),
+    (WIRELESS_SETTING, "mac-address-blacklist", "as"),
+    (WIRELESS_SETTING, "mac-address-randomization", "u"),
+    (WIRELESS_SETTING, "rate", "u"),
+    (WIRELESS_SETTING, "tx-power", "u"),
+    (WIRELESS_SETTING, "wake-on-wlan", "u"),
+    ("802-11-wireless-security", "fils", "i"),
+    ("802-11-wireless-security", "wps-method", "u"),
+    (ETHERNET_SETTING, "mac-address-blacklist", "as"),
+    (IPV4_SETTING, "dad-timeout", "i"),
+    (IPV4_SETTING, "dns-options", "as"),
+    (IPV6_SETTING, "dns-options", "as"),
+    (IPV6_SETTING, "ra-timeout", "i"),
+    ("proxy", "browser-only", "b"),
+    ("proxy", "method", "i"),
+];
+
+/// Types of the `ethtool` options by prefix, e.g. `coalesce-rx-usecs`.
+const ETHTOOL_SIGNATURES: &[(&str, &str)] = &[
+    ("channels-", "u"),
+    ("coalesce-", "u"),
+    ("feature-", "b"),
+    ("pause-", "b"),
+    ("ring-", "u"),
+];
+
+/// Enum values that keyfiles may contain as nicks instead of numbers.
+const ENUM_NICKS: &[(&str, &str, &str, i32)] = &[
+    (IPV6_SETTING, "addr-gen-mode", "eui64", 0),
+    (IPV6_SETTING, "addr-gen-mode", "stable-privacy", 1),
+    (IPV6_SETTING, "addr-gen-mode", "default-or-eui64", 2),
+    (IPV6_SETTING, "addr-gen-mode", "default", 3),
+];
+
+/// Types of route attributes, written as `routeN_options=key=value,...`.
+const ROUTE_ATTRIBUTE_SIGNATURES: &[(&str, &str)] = &[
+    ("advmss", "u"),
+    ("cwnd", "u"),
+    ("initcwnd", "u"),
+    ("initrwnd", "u"),
+    ("lock-advmss", "b"),
+    ("lock-cwnd", "b"),
+    ("lock-initcwnd", "b"),
+    ("lock-initrwnd", "b"),
+    ("lock-mtu", "b"),
+    ("lock-window", "b"),
+    ("mtu", "u"),
+    ("onlink", "b"),
+    ("quickack", "b"),
+    ("rto_min", "u"),
+    ("scope", "y"),
+    ("table", "u"),
+    ("tos", "y"),
+    ("window", "u"),
+];
+
+impl ConnectionSettings {
+    /// Parses a Network Manager keyfile, i.e. the contents of a
+    /// `.nmconnection` file in `/etc/NetworkManager/system-connections`.
+    pub fn from_keyfile(keyfile: &str) -> Result<Self> {
+        let mut groups = BTreeMap::new();
+
+        for (name, entries) in parse_ini(keyfile)? {
+            read_group(&mut groups, &name, entries)?;
+        }
+
+        Ok(ConnectionSettings::from_groups(groups))
+    }
+
+    /// Serializes the settings in the Network Manager keyfile format.
+    ///
+    /// Fails if a value has no keyfile representation, e.g. a dictionary key
+    /// that Network Manager itself does not store in keyfiles.
+    pub fn to_keyfile(&self) -> Result<String> {
+        let mut groups = self.to_groups();
+
+        let mut sections = Vec::new();
+
+        if let Some(group) = groups.remove(CONNECTION_SETTING) {
+            write_group(&mut sections, CONNECTION_SETTING, &group)?;
+        }
+
+        for (name, group) in &groups {
+            write_group(&mut sections, name, group)?;
+        }
+
+        let mut keyfile = String::new();
+
+        for (name, entries) in sections {
+            if !keyfile.is_empty() {
+                keyfile.push('\n');
+            }
+
+            keyfile.push_str(&format!("[{}]\n", name));
+
+            for (key, value) in entries {
+                keyfile.push_str(&format!("{}={}\n", key, value));
+            }
+        }
+
+        Ok(keyfile)
+    }
+}
+
+type Section = (String, Vec<(String, String)>);
+
+fn parse_ini(keyfile: &str) -> Result<Vec<Section>> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (number, line) in keyfile.lines().enumerate() {
+        let line = line.trim_start();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            match line.trim_end().strip_suffix(']') {
+                Some(name) => sections.push((name[1..].to_string(), Vec::new())),
+                None => bail!(keyfile_error(format!(
+                    "Invalid group header on line {}",
+                    number + 1
+                ))),
+            }
+
+            continue;
+        }
+
+        let (key, value) = match line.find('=') {
+            Some(index) => (line[..index].trim_end(), &line[index + 1..]),
+            None => bail!(keyfile_error(format!(
+                "Key-value pair expected on line {}",
+                number + 1
+            ))),
+        };
+
+        match sections.last_mut() {
+            Some(&mut (_, ref mut entries)) => {
+                entries.push((key.to_string(), value.trim_start().to_string()))
+            }
+            None => bail!(keyfile_error(format!(
+                "Key outside of a group on line {}",
+                number + 1
+            ))),
+        }
+    }
+
+    Ok(sections)
+}
+
+fn read_group(
+    groups: &mut BTreeMap<String, SettingGroup>,
+    name: &str,
+    entries: Vec<(String, String)>,
+) -> Result<()> {
+    for &(setting, key, group_name) in DICT_GROUPS {
+        if name == group_name {
+            let dict = entries
+                .into_iter()
+                .map(|(key, value)| (key, unescape(&value)))
+                .collect::<BTreeMap<_, _>>();
+
+            groups
+                .entry(setting.to_string())
+                .or_default()
+                .insert(key.to_string(), dict.to_setting());
+
+            return Ok(());
+        }
+    }
+
+    let setting = SETTING_ALIASES
+        .iter()
+        .find(|&&(_, alias)| alias == name)
+        .map_or(name, |&(setting, _)| setting)
+        .to_string();
+
+    let mut group = groups.remove(&setting).unwrap_or_default();
+
+    if setting == IPV4_SETTING || setting == IPV6_SETTING {
+        read_ip_group(&mut group, &setting, entries)?;
+    } else {
+        let mut vpn_data = BTreeMap::new();
+
+        for (key, value) in entries {
+            if setting == VPN_SETTING && setting_signature(VPN_SETTING, &key).is_none() {
+                vpn_data.insert(key, unescape(&value));
+                continue;
+            }
+
+            let value = read_value(&setting, &key, &value)?;
+
+            group.insert(key, value);
+        }
+
+        if !vpn_data.is_empty() {
+            group.insert("data".into(), vpn_data.to_setting());
+        }
+    }
+
+    groups.insert(setting, group);
+
+    Ok(())
+}
+
+fn read_ip_group(
+    group: &mut SettingGroup,
+    setting: &str,
+    entries: Vec<(String, String)>,
+) -> Result<()> {
+    let is_ipv4 = setting == IPV4_SETTING;
+
+    let mut addresses = BTreeMap::new();
+    let mut routes = BTreeMap::new();
+    let mut route_options = BTreeMap::new();
+    let mut legacy_gateway = None;
+
+    for (key, value) in entries {
+        if let Some(index) = numbered_key(&key, &["address", "addresses"]) {
+            let mut parts = value.split(',');
+
+            let (address, prefix) = read_ip_prefix(parts.next().unwrap_or(""), is_ipv4)?;
+
+            if let Some(gateway) = parts.next() {
+                legacy_gateway = Some(gateway.trim().to_string());
+            }
+
+            addresses.insert(
+                index,
+                IpAddress {
+                    address,
+                    prefix,
+                    attributes: SettingGroup::new(),
+                },
+            );
+        } else if let Some(index) = route_options_key(&key) {
+            route_options.insert(index, read_route_options(&value)?);
+        } else if let Some(index) = numbered_key(&key, &["route", "routes"]) {
+            routes.insert(index, read_route(&value, is_ipv4)?);
+        } else if key == "dns" {
+            let servers = split_list(&value)
+                .iter()
+                .map(|server| {
+                    server
+                        .parse::<IpAddr>()
+                        .map_err(|_| keyfile_error(format!("Invalid DNS server: {}", server)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            group.insert(key, dns_setting(servers, is_ipv4)?);
+        } else {
+            let value = read_value(setting, &key, &value)?;
+
+            group.insert(key, value);
+        }
+    }
+
+    if !addresses.is_empty() {
+        let addresses = addresses.into_values().collect::<Vec<_>>();
+
+        group.insert("address-data".into(), addresses.to_setting());
+    }
+
+    if let Some(gateway) = legacy_gateway {
+        if !group.contains_key("gateway") {
+            group.insert("gateway".into(), SettingValue::String(gateway));
+        }
+    }
+
+    if !routes.is_empty() {
+        let routes = routes
+            .into_iter()
+            .map(|(index, mut route)| {
+                if let Some(attributes) = route_options.remove(&index) {
+                    route.attributes = attributes;
+                }
+                route
+            })
+            .collect::<Vec<_>>();
+
+        group.insert("route-data".into(), routes.to_setting());
+    }
+
+    Ok(())
+}
+
+/// Returns `N` of keys like `address1` or `routes2`.
+fn numbered_key(key: &str, prefixes: &[&str]) -> Option<u32> {
+    prefixes
+        .iter()
+        .filter_map(|prefix| key.strip_prefix(prefix))
+        .filter_map(|index| index.parse().ok())
+        .next()
+}
+
+fn route_options_key(key: &str) -> Option<u32> {
+    key.strip_suffix("_options")
+        .and_then(|key| numbered_key(key, &["route", "routes"]))
+}
+
+fn read_ip_prefix(value: &str, is_ipv4: bool) -> Result<(IpAddr, u32)> {
+    let mut parts = value.trim().splitn(2, '/');
+
+    let address = parts
+        .next()
+        .and_then(|address| address.parse::<IpAddr>().ok())
+        .filter(|address| address.is_ipv4() == is_ipv4)
+        .ok_or_else(|| keyfile_error(format!("Invalid IP address: {}", value)))?;
+
+    let prefix = match parts.next() {
+        Some(prefix) => prefix
+            .parse()
+            .map_err(|_| keyfile_error(format!("Invalid prefix: {}", value)))?,
+        None if is_ipv4 => 32,
+        None => 128,
+    };
+
+    Ok((address, prefix))
+}
+
+fn read_route(value: &str, is_ipv4: bool) -> Result<IpRoute> {
+    let mut parts = value.split(',');
+
+    let (dest, prefix) = read_ip_prefix(parts.next().unwrap_or(""), is_ipv4)?;
+
+    let next_hop = match parts.next().map(|p| p.trim()) {
+        Some("") | None => None,
+        Some(next_hop) => {
+            let next_hop = next_hop
+                .parse::<IpAddr>()
+                .map_err(|_| keyfile_error(format!("Invalid route next hop: {}", value)))?;
+
+            if next_hop.is_unspecified() {
+                None
+            } else {
+                Some(next_hop)
+            }
+        }
+    };
+
+    let metric = match parts.next().map(|p| p.trim()) {
+        Some("") | None => None,
+        Some(metric) => Some(
+            metric
+                .parse()
+                .map_err(|_| keyfile_error(format!("Invalid route metric: {}", value)))?,
+        ),
+    };
+
+    Ok(IpRoute {
+        dest,
+        prefix,
+        next_hop,
+        metric,
+        attributes: SettingGroup::new(),
+    })
+}
+
+fn read_route_options(value: &str) -> Result<SettingGroup> {
+    let mut attributes = SettingGroup::new();
+
+    for option in value.split(',').filter(|option| !option.is_empty()) {
+        let mut parts = option.splitn(2, '=');
+
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+
+        let signature = ROUTE_ATTRIBUTE_SIGNATURES
+            .iter()
+            .find(|&&(attribute, _)| attribute == name)
+            .map_or("s", |&(_, signature)| signature);
+
+        attributes.insert(name.to_string(), read_scalar(signature, value)?);
+    }
+
+    Ok(attributes)
+}
+
+fn dns_setting(servers: Vec<IpAddr>, is_ipv4: bool) -> Result<SettingValue> {
+    let mut ipv4_servers = Vec::new();
+    let mut ipv6_servers = Vec::new();
+
+    for server in servers {
+        match server {
+            IpAddr::V4(server) if is_ipv4 => ipv4_servers.push(server),
+            IpAddr::V6(server) if !is_ipv4 => ipv6_servers.push(server),
+            _ => bail!(keyfile_error(format!(
+                "DNS server of the wrong address family: {}",
+                server
+            ))),
+        }
+    }
+
+    if is_ipv4 {
+        Ok(ipv4_servers.to_setting())
+    } else {
+        Ok(ipv6_servers.to_setting())
+    }
+}
+
+fn read_value(setting: &str, key: &str, value: &str) -> Result<SettingValue> {
+    if setting == CONNECTION_SETTING && key == "type" {
+        let kind = unescape(value);
+
+        let kind = SETTING_ALIASES
+            .iter()
+            .find(|&&(_, alias)| alias == kind)
+            .map_or(kind.clone(), |&(setting, _)| setting.to_string());
+
+        return Ok(SettingValue::String(kind));
+    }
+
+    if MAC_ADDRESS_KEYS.contains(&(setting, key)) {
+        if let Some(bytes) = parse_mac_address(value) {
+            return Ok(SettingValue::bytes(&bytes));
+        }
+    }
+
+    if setting == IEEE8021X_SETTING && CERTIFICATE_KEYS.contains(&key) {
+        return Ok(SettingValue::bytes(&read_certificate(value)?));
+    }
+
+    if setting == WIRELESS_SETTING && key == "ssid" {
+        let bytes = match parse_byte_list(value) {
+            Some(bytes) => bytes,
+            None => unescape(value).into_bytes(),
+        };
+
+        return Ok(SettingValue::bytes(&bytes));
+    }
+
+    if let Some(&(_, _, _, number)) = ENUM_NICKS
+        .iter()
+        .find(|&&(s, k, nick, _)| s == setting && k == key && nick == value.trim_end())
+    {
+        return Ok(SettingValue::Int32(number));
+    }
+
+    let signature = setting_signature(setting, key)
+        .or_else(|| {
+            KNOWN_SIGNATURES
+                .iter()
+                .find(|&&(s, k, _)| s == setting && k == key)
+                .map(|&(_, _, signature)| signature)
+        })
+        .or_else(|| {
+            ETHTOOL_SIGNATURES
+                .iter()
+                .find(|&&(prefix, _)| setting == "ethtool" && key.starts_with(prefix))
+                .map(|&(_, signature)| signature)
+        })
+        .unwrap_or("s");
+
+    let error = || keyfile_error(format!("Invalid value for {}.{}: {}", setting, key, value));
+
+    match signature {
+        "ay" => parse_byte_list(value)
+            .map(|bytes| SettingValue::bytes(&bytes))
+            .ok_or_else(error),
+        "as" => Ok(SettingValue::strings(&split_list(value))),
+        "au" => Ok(SettingValue::Array(
+            split_list(value)
+                .iter()
+                .map(|item| read_scalar("u", item))
+                .collect::<Result<_>>()
+                .map_err(|_| error())?,
+            "u".into(),
+        )),
+        signature => read_scalar(signature, value).map_err(|_| error()),
+    }
+}
+
+fn read_scalar(signature: &str, value: &str) -> Result<SettingValue> {
+    let value = value.trim_end();
+
+    let error = || keyfile_error(format!("Invalid value: {}", value));
+
+    let value = match signature {
+        "b" => match value {
+            "true" => SettingValue::Bool(true),
+            "false" => SettingValue::Bool(false),
+            _ => bail!(error()),
+        },
+        "y" => SettingValue::Byte(value.parse().map_err(|_| error())?),
+        "i" => SettingValue::Int32(value.parse().map_err(|_| error())?),
+        "u" => SettingValue::UInt32(value.parse().map_err(|_| error())?),
+        "x" => SettingValue::Int64(value.parse().map_err(|_| error())?),
+        "t" => SettingValue::UInt64(value.parse().map_err(|_| error())?),
+        _ => SettingValue::String(unescape(value)),
+    };
+
+    Ok(value)
+}
+
+fn read_certificate(value: &str) -> Result<Vec<u8>> {
+    let value = unescape(value);
+
+    if let Some(data) = value.strip_prefix("data:;base64,") {
+        return decode_base64(data)
+            .ok_or_else(|| keyfile_error(format!("Invalid certificate data: {}", value)));
+    }
+
+    if let Some(bytes) = parse_byte_list(&value) {
+        return Ok(bytes);
+    }
+
+    let path = if value.starts_with("file://") {
+        value
+    } else if value.starts_with('/') {
+        format!("file://{}", value)
+    } else {
+        bail!(keyfile_error(format!(
+            "Certificate paths must be absolute: {}",
+            value
+        )))
+    };
+
+    let mut bytes = path.into_bytes();
+    bytes.push(0);
+
+    Ok(bytes)
+}
+
+fn write_group(sections: &mut Vec<Section>, setting: &str, group: &SettingGroup) -> Result<()> {
+    let name = SETTING_ALIASES
+        .iter()
+        .find(|&&(s, _)| s == setting)
+        .map_or(setting, |&(_, alias)| alias);
+
+    let mut entries = Vec::new();
+    let mut dict_sections = Vec::new();
+
+    for (key, value) in group {
+        if let Some(&(_, _, group_name)) = DICT_GROUPS
+            .iter()
+            .find(|&&(s, k, _)| s == setting && k == key)
+        {
+            dict_sections.push((group_name.to_string(), write_dict(setting, key, value)?));
+            continue;
+        }
+
+        if setting == VPN_SETTING && key == "data" {
+            entries.extend(write_dict(setting, key, value)?);
+            continue;
+        }
+
+        if setting == IPV4_SETTING || setting == IPV6_SETTING {
+            match key as &str {
+                // Deprecated duplicates of `address-data` and `route-data`
+                "addresses" | "routes" => continue,
+                "address-data" => {
+                    entries.extend(write_addresses(setting, value)?);
+                    continue;
+                }
+                "route-data" => {
+                    entries.extend(write_routes(setting, value)?);
+                    continue;
+                }
+                "dns" => {
+                    entries.push((key.clone(), write_dns(setting, value)?));
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        entries.push((key.clone(), write_value(setting, key, value)?));
+    }
+
+    sections.push((name.to_string(), entries));
+    sections.extend(dict_sections);
+
+    Ok(())
+}
+
+fn write_value(setting: &str, key: &str, value: &SettingValue) -> Result<String> {
+    if setting == CONNECTION_SETTING && key == "type" {
+        if let Some(kind) = value.as_str() {
+            let kind = SETTING_ALIASES
+                .iter()
+                .find(|&&(s, _)| s == kind)
+                .map_or(kind, |&(_, alias)| alias);
+
+            return Ok(escape(kind));
+        }
+    }
+
+    if let Some(bytes) = value.as_bytes() {
+        if MAC_ADDRESS_KEYS.contains(&(setting, key)) && bytes.len() == 6 {
+            return Ok(format_mac_address(&bytes));
+        }
+
+        if setting == IEEE8021X_SETTING && CERTIFICATE_KEYS.contains(&key) {
+            if let Some(path) = bytes
+                .strip_suffix(&[0])
+                .and_then(|path| path.strip_prefix(b"file://"))
+                .and_then(|path| ::std::str::from_utf8(path).ok())
+            {
+                return Ok(escape(path));
+            }
+        }
+
+        if setting == WIRELESS_SETTING && key == "ssid" {
+            if let Ok(ssid) = ::std::str::from_utf8(&bytes) {
+                if !ssid.is_empty()
+                    && !ssid.contains(';')
+                    && !ssid.chars().any(|c| c.is_control())
+                    && parse_byte_list(ssid).is_none()
+                {
+                    return Ok(escape(ssid));
+                }
+            }
+        }
+
+        return Ok(format_byte_list(&bytes));
+    }
+
+    write_plain_value(value)
+        .ok_or_else(|| keyfile_error(format!("{}.{} cannot be stored in a keyfile", setting, key)))
+}
+
+fn write_plain_value(value: &SettingValue) -> Option<String> {
+    match *value {
+        SettingValue::Array(ref items, _) => {
+            let mut list = String::new();
+
+            for item in items {
+                list.push_str(&write_scalar(item)?.replace(';', "\\;"));
+                list.push(';');
+            }
+
+            Some(list)
+        }
+        ref value => write_scalar(value),
+    }
+}
+
+fn write_scalar(value: &SettingValue) -> Option<String> {
+    let value = match *value {
+        SettingValue::Bool(value) => value.to_string(),
+        SettingValue::Byte(value) => value.to_string(),
+        SettingValue::Int16(value) => value.to_string(),
+        SettingValue::UInt16(value) => value.to_string(),
+        SettingValue::Int32(value) => value.to_string(),
+        SettingValue::UInt32(value) => value.to_string(),
+        SettingValue::Int64(value) => value.to_string(),
+        SettingValue::UInt64(value) => value.to_string(),
+        SettingValue::Double(value) => value.to_string(),
+        SettingValue::String(ref value) | SettingValue::ObjectPath(ref value) => escape(value),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn write_dict(setting: &str, key: &str, value: &SettingValue) -> Result<Vec<(String, String)>> {
+    let dict = BTreeMap::<String, String>::from_setting(value)
+        .ok_or_else(|| keyfile_error(format!("{}.{} is not a string dictionary", setting, key)))?;
+
+    Ok(dict
+        .into_iter()
+        .map(|(key, value)| (key, escape(&value)))
+        .collect())
+}
+
+fn write_addresses(setting: &str, value: &SettingValue) -> Result<Vec<(String, String)>> {
+    let addresses = Vec::<IpAddress>::from_setting(value)
+        .ok_or_else(|| keyfile_error(format!("Invalid {}.address-data", setting)))?;
+
+    let mut entries = Vec::new();
+
+    for (index, address) in addresses.iter().enumerate() {
+        if !address.attributes.is_empty() {
+            bail!(keyfile_error(format!(
+                "Attributes of {}.address-data cannot be stored in a keyfile",
+                setting
+            )));
+        }
+
+        entries.push((
+            format!("address{}", index + 1),
+            format!("{}/{}", address.address, address.prefix),
+        ));
+    }
+
+    Ok(entries)
+}
+
+fn write_routes(setting: &str, value: &SettingValue) -> Result<Vec<(String, String)>> {
+    let routes = Vec::<IpRoute>::from_setting(value)
+        .ok_or_else(|| keyfile_error(format!("Invalid {}.route-data", setting)))?;
+
+    let unspecified = if setting == IPV4_SETTING {
+        "0.0.0.0"
+    } else {
+        "::"
+    };
+
+    let mut entries = Vec::new();
+
+    for (index, route) in routes.iter().enumerate() {
+        let mut line = format!("{}/{}", route.dest, route.prefix);
+
+        match (route.next_hop, route.metric) {
+            (Some(next_hop), Some(metric)) => line += &format!(",{},{}", next_hop, metric),
+            (Some(next_hop), None) => line += &format!(",{}", next_hop),
+            (None, Some(metric)) => line += &format!(",{},{}", unspecified, metric),
+            (None, None) => {}
+        }
+
+        entries.push((format!("route{}", index + 1), line));
+
+        if !route.attributes.is_empty() {
+            let mut options = Vec::new();
+
+            for (name, value) in &route.attributes {
+                let value = write_scalar(value).ok_or_else(|| {
+                    keyfile_error(format!("Route attribute {} cannot be stored", name))
+                })?;
+
+                options.push(format!("{}={}", name, value));
+            }
+
+            entries.push((format!("route{}_options", index + 1), options.join(",")));
+        }
+    }
+
+    Ok(entries)
+}
+
+fn write_dns(setting: &str, value: &SettingValue) -> Result<String> {
+    let servers = if setting == IPV4_SETTING {
+        Vec::<::std::net::Ipv4Addr>::from_setting(value)
+            .map(|servers| servers.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    } else {
+        Vec::<::std::net::Ipv6Addr>::from_setting(value)
+            .map(|servers| servers.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    };
+
+    let servers = servers.ok_or_else(|| keyfile_error(format!("Invalid {}.dns", setting)))?;
+
+    Ok(servers.iter().map(|s| format!("{};", s)).collect())
+}
+
+/// Splits a keyfile list on unescaped `;` and unescapes the items.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                item.push(c);
+                if let Some(next) = chars.next() {
+                    item.push(next);
+                }
+            }
+            ';' => items.push(unescape(&::std::mem::take(&mut item))),
+            c => item.push(c),
+        }
+    }
+
+    if !item.trim().is_empty() {
+        items.push(unescape(&item));
+    }
+
+    items
+}
+
+fn parse_byte_list(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim_end();
+
+    if !value.ends_with(';') {
+        return None;
+    }
+
+    value
+        .split(';')
+        .filter(|item| !item.is_empty())
+        .map(|item| item.trim().parse::<u8>().ok())
+        .collect()
+}
+
+fn format_byte_list(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{};", b)).collect()
+}
+
+fn parse_mac_address(value: &str) -> Option<Vec<u8>> {
+    let bytes = value
+        .trim()
+        .split(':')
+        .map(|part| u8::from_str_radix(part, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    if bytes.len() == 6 {
+        Some(bytes)
+    } else {
+        None
+    }
+}
+
+fn format_mac_address(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Escapes a value the way GLib key files do.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            ' ' if index == 0 => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in data.trim().bytes().filter(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+fn keyfile_error(info: String) -> Error {
+    ErrorKind::Keyfile(info).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    use settings::{
+        ConnectionSetting, Ipv4Setting, VpnSetting, WirelessSecuritySetting, WirelessSetting,
+    };
+    use ssid::Ssid;
+
+    const WIFI_KEYFILE: &str = "\
+[connection]
+id=Office Wi-Fi
+uuid=a4a8c6b8-2f0e-4b4c-9d31-3b7f6f7c2d10
+type=wifi
+interface-name=wlan0
+autoconnect-priority=10
+permissions=
+
+[wifi]
+mac-address=00:11:22:AA:BB:CC
+mode=infrastructure
+ssid=office
+
+[wifi-security]
+key-mgmt=wpa-psk
+psk=pass;word
+
+[ipv4]
+address1=192.168.1.10/24,192.168.1.1
+dns=8.8.8.8;1.1.1.1;
+method=manual
+route1=10.0.0.0/8,192.168.1.254,100
+route1_options=table=100
+
+[ipv6]
+addr-gen-mode=stable-privacy
+method=auto
+
+[proxy]
+method=0
+";
+
+    #[test]
+    fn test_from_keyfile() {
+        let settings = ConnectionSettings::from_keyfile(WIFI_KEYFILE).unwrap();
+
+        assert_eq!(settings.id(), "Office Wi-Fi");
+        assert_eq!(settings.kind(), "802-11-wireless");
+        assert_eq!(
+            settings.connection.interface_name,
+            Some("wlan0".to_string())
+        );
+        assert_eq!(settings.connection.autoconnect_priority, Some(10));
+        assert_eq!(settings.connection.permissions, Some(vec![]));
+
+        let wireless = settings.wireless.as_ref().unwrap();
+        assert_eq!(wireless.ssid, Some(Ssid::from_bytes("office").unwrap()));
+        assert_eq!(
+            wireless.mac_address,
+            Some(vec![0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc])
+        );
+
+        let security = settings.wireless_security.as_ref().unwrap();
+        assert_eq!(security.psk, Some("pass;word".to_string()));
+
+        let ipv4 = settings.ipv4.as_ref().unwrap();
+        assert_eq!(ipv4.method, Some("manual".to_string()));
+        assert_eq!(ipv4.gateway, Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(
+            ipv4.dns,
+            Some(vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)])
+        );
+        assert_eq!(ipv4.address_data.as_ref().unwrap()[0].prefix, 24);
+
+        let route = &ipv4.route_data.as_ref().unwrap()[0];
+        assert_eq!(route.next_hop, Some("192.168.1.254".parse().unwrap()));
+        assert_eq!(route.metric, Some(100));
+        assert_eq!(
+            route.attributes.get("table"),
+            Some(&SettingValue::UInt32(100))
+        );
+
+        assert_eq!(settings.ipv6.as_ref().unwrap().addr_gen_mode, Some(1));
+        assert_eq!(
+            settings.other["proxy"].get("method"),
+            Some(&SettingValue::Int32(0))
+        );
+    }
+
+    #[test]
+    fn test_keyfile_round_trip() {
+        let settings = ConnectionSettings::from_keyfile(WIFI_KEYFILE).unwrap();
+
+        let keyfile = settings.to_keyfile().unwrap();
+
+        assert_eq!(
+            ConnectionSettings::from_keyfile(&keyfile).unwrap(),
+            settings
+        );
+    }
+
+    #[test]
+    fn test_created_settings_round_trip() {
+        let mut data = BTreeMap::new();
+        data.insert("gateway".to_string(), "vpn.example.com".to_string());
+
+        let settings = ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some(" hotspot\\".into()),
+                uuid: Some("0b3a4a72-49a1-4b3a-8f5e-3c2d1e0f9a87".into()),
+                kind: Some("802-11-wireless".into()),
+                autoconnect: Some(false),
+                ..Default::default()
+            },
+            wireless: Some(WirelessSetting {
+                ssid: Some(Ssid::from_bytes(vec![0xff, 0x00, 0x41]).unwrap()),
+                mode: Some("ap".into()),
+                ..Default::default()
+            }),
+            wireless_security: Some(WirelessSecuritySetting {
+                key_mgmt: Some("wpa-psk".into()),
+                proto: Some(vec!["rsn".into()]),
+                psk: Some("secret".into()),
+                ..Default::default()
+            }),
+            ipv4: Some(Ipv4Setting {
+                method: Some("shared".into()),
+                address_data: Some(vec![IpAddress {
+                    address: "192.168.42.1".parse().unwrap(),
+                    prefix: 24,
+                    attributes: SettingGroup::new(),
+                }]),
+                ..Default::default()
+            }),
+            vpn: Some(VpnSetting {
+                service_type: Some("org.freedesktop.NetworkManager.openvpn".into()),
+                data: Some(data),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let keyfile = settings.to_keyfile().unwrap();
+
+        assert!(keyfile.starts_with("[connection]\nautoconnect=false\n"));
+        assert!(keyfile.contains("ssid=255;0;65;\n"));
+        assert!(keyfile.contains("\n[vpn]\ngateway=vpn.example.com\n"));
+
+        assert_eq!(
+            ConnectionSettings::from_keyfile(&keyfile).unwrap(),
+            settings
+        );
+    }
+
+    #[test]
+    fn test_certificate_paths() {
+        let keyfile =
+            "[802-1x]\neap=tls;\nca-cert=/etc/ssl/ca.pem\nclient-cert=data:;base64,AQID\n";
+
+        let settings = ConnectionSettings::from_keyfile(keyfile).unwrap();
+        let ieee8021x = settings.ieee8021x.as_ref().unwrap();
+
+        assert_eq!(
+            ieee8021x.ca_cert,
+            Some(b"file:///etc/ssl/ca.pem\0".to_vec())
+        );
+        assert_eq!(ieee8021x.client_cert, Some(vec![1, 2, 3]));
+        assert!(settings
+            .to_keyfile()
+            .unwrap()
+            .contains("ca-cert=/etc/ssl/ca.pem\n"));
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let keyfile = "\
+[connection]
+id=bridge
+type=ovs-bridge
+
+[ethtool]
+coalesce-rx-usecs=64
+feature-rx=false
+
+[ipv4]
+dhcp-iaid=1234
+method=auto
+
+[ovs-bridge]
+datapath-type=netdev
+";
+
+        let settings = ConnectionSettings::from_keyfile(keyfile).unwrap();
+
+        let ethtool = &settings.other["ethtool"];
+        assert_eq!(ethtool.get("feature-rx"), Some(&SettingValue::Bool(false)));
+        assert_eq!(
+            ethtool.get("coalesce-rx-usecs"),
+            Some(&SettingValue::UInt32(64))
+        );
+        assert_eq!(
+            settings.ipv4.as_ref().unwrap().other.get("dhcp-iaid"),
+            Some(&SettingValue::String("1234".into()))
+        );
+        assert_eq!(
+            settings.other["ovs-bridge"].get("datapath-type"),
+            Some(&SettingValue::String("netdev".into()))
+        );
+
+        assert_eq!(
+            ConnectionSettings::from_keyfile(&settings.to_keyfile().unwrap()).unwrap(),
+            settings
+        );
+    }
+
+    #[test]
+    fn test_invalid_keyfile() {
+        assert!(ConnectionSettings::from_keyfile("id=orphan\n").is_err());
+        assert!(ConnectionSettings::from_keyfile("[connection]\nautoconnect=maybe\n").is_err());
+    }
+}
@@ -16,19 +16,60 @@ extern crate dbus;
 
 extern crate ascii;
 
+extern crate futures;
+
 pub mod errors;
 
+mod async_manager;
+mod backend;
+mod checkpoint;
 mod connection;
 mod dbus_api;
 mod dbus_nm;
 mod device;
+mod dhcp_config;
+mod eap;
+mod ethernet;
+mod events;
+#[cfg(any(test, feature = "fake"))]
+mod fake;
+mod hotspot;
+mod ip_config;
+mod keyfile;
 mod manager;
+mod modem;
+mod onboarding;
 mod service;
+mod settings;
 mod ssid;
 mod wifi;
 
-pub use connection::{Connection, ConnectionSettings, ConnectionState};
-pub use device::{Device, DeviceState, DeviceType};
-pub use manager::{Connectivity, NetworkManager};
+pub use async_manager::{AsyncNetworkManager, NetworkManagerEventStream, NetworkManagerFuture};
+pub use backend::Backend;
+pub use checkpoint::{Checkpoint, CheckpointFlags, CheckpointGuard, RollbackResult};
+pub use connection::{ActiveConnectionStateReason, Connection, ConnectionState};
+pub use device::{Device, DeviceCapabilities, DeviceState, DeviceStateReason, DeviceType};
+pub use dhcp_config::{Dhcp4Config, Dhcp6Config};
+pub use eap::{Certificate, EapMethod, Ieee8021xCredentials, Phase2Auth};
+pub use ethernet::{EthernetConfig, EthernetDevice};
+pub use events::{EventSource, EventStream, NetworkManagerEvent};
+#[cfg(any(test, feature = "fake"))]
+pub use fake::FakeNetworkManager;
+pub use hotspot::{HotspotClient, HotspotConfig, HotspotSecurity, Pmf};
+pub use ip_config::{AddressData, Ip4Config, Ip6Config, IpConfig, RouteData};
+pub use manager::{Connectivity, NetworkManager, NetworkManagerState};
+pub use modem::{GsmConfig, ModemCapabilities, ModemDevice};
+pub use onboarding::{
+    CredentialSource, NetworkCredentials, OnboardingConfig, OnboardingController, OnboardingState,
+};
 pub use service::ServiceState;
-pub use wifi::{AccessPoint, AccessPointCredentials, Security};
+pub use settings::{
+    ConnectionSetting, ConnectionSettings, EthernetSetting, GsmSetting, Ieee8021xSetting,
+    IpAddress, IpRoute, Ipv4Setting, Ipv6Setting, SettingGroup, SettingValue, VpnSetting,
+    WirelessSecuritySetting, WirelessSetting,
+};
+pub use ssid::{AsSsidSlice, IntoSsid, Ssid, SsidSlice};
+pub use wifi::{
+    AccessPoint, AccessPointCredentials, NM80211ApFlags, NM80211ApSecurityFlags, NM80211Mode,
+    Security, WiFiBand,
+};
@@ -1,29 +1,39 @@
 use std::rc::Rc;
+use std::time::Duration;
 
+use backend::Backend;
 use dbus_nm::DBusNetworkManager;
 use errors::*;
 
-use connection::{get_active_connections, get_connections, Connection};
-use device::{get_device_by_interface, get_devices, Device};
+use checkpoint::{create_checkpoint, Checkpoint, CheckpointFlags};
+use connection::{add_connection, get_active_connections, get_connections, Connection};
+use device::{get_device_by_interface, get_devices, Device, PathGetter};
+use events::EventStream;
 use service::{get_service_state, start_service, stop_service, ServiceState};
+use settings::ConnectionSettings;
 
 pub struct NetworkManager {
-    dbus_manager: Rc<DBusNetworkManager>,
+    backend: Rc<dyn Backend>,
 }
 
 impl NetworkManager {
     pub fn new() -> Self {
         NetworkManager {
-            dbus_manager: Rc::new(DBusNetworkManager::new(None)),
+            backend: Rc::new(DBusNetworkManager::new(None)),
         }
     }
 
     pub fn with_method_timeout(timeout: u64) -> Self {
         NetworkManager {
-            dbus_manager: Rc::new(DBusNetworkManager::new(Some(timeout))),
+            backend: Rc::new(DBusNetworkManager::new(Some(timeout))),
         }
     }
 
+    /// Creates a manager on top of a custom backend, e.g. `FakeNetworkManager`.
+    pub fn with_backend(backend: Rc<dyn Backend>) -> Self {
+        NetworkManager { backend }
+    }
+
     /// Starts the Network Manager service.
     pub fn start_service(timeout: u64) -> Result<ServiceState> {
         start_service(timeout)
@@ -41,39 +51,120 @@ impl NetworkManager {
 
     /// Get a list of Network Manager connections sorted by path.
     pub fn get_connections(&self) -> Result<Vec<Connection>> {
-        get_connections(&self.dbus_manager)
+        get_connections(&self.backend)
     }
 
     pub fn get_active_connections(&self) -> Result<Vec<Connection>> {
-        get_active_connections(&self.dbus_manager)
+        get_active_connections(&self.backend)
+    }
+
+    /// Adds a connection profile without activating it and writes it to disk.
+    pub fn add_connection(&self, settings: &ConnectionSettings) -> Result<Connection> {
+        add_connection(&self.backend, settings, true)
+    }
+
+    /// Adds a connection profile without activating it. The profile is lost
+    /// on restart unless saved with `Connection::save`.
+    pub fn add_connection_unsaved(&self, settings: &ConnectionSettings) -> Result<Connection> {
+        add_connection(&self.backend, settings, false)
     }
 
     /// Get a list of Network Manager devices.
     pub fn get_devices(&self) -> Result<Vec<Device>> {
-        get_devices(&self.dbus_manager)
+        get_devices(&self.backend)
     }
 
     pub fn get_device_by_interface(&self, interface: &str) -> Result<Device> {
-        get_device_by_interface(&self.dbus_manager, interface)
+        get_device_by_interface(&self.backend, interface)
+    }
+
+    /// Subscribes to Network Manager events.
+    ///
+    /// Events that happen after this call are queued until read from the
+    /// returned stream.
+    pub fn subscribe(&self) -> Result<EventStream> {
+        self.backend.subscribe()
     }
 
     pub fn get_state(&self) -> Result<NetworkManagerState> {
-        self.dbus_manager.get_state()
+        self.backend.get_state()
     }
 
     pub fn get_connectivity(&self) -> Result<Connectivity> {
-        self.dbus_manager.check_connectivity()
+        self.backend.check_connectivity()
     }
 
     pub fn is_networking_enabled(&self) -> Result<bool> {
-        self.dbus_manager.is_networking_enabled()
+        self.backend.is_networking_enabled()
     }
 
     pub fn is_wireless_enabled(&self) -> Result<bool> {
-        self.dbus_manager.is_wireless_enabled()
+        self.backend.is_wireless_enabled()
+    }
+
+    /// Whether the Wi-Fi radio is enabled by a hardware switch. When this is
+    /// `false` the radio is off regardless of `is_wireless_enabled`.
+    pub fn is_wireless_hardware_enabled(&self) -> Result<bool> {
+        self.backend.is_wireless_hardware_enabled()
+    }
+
+    pub fn is_wwan_enabled(&self) -> Result<bool> {
+        self.backend.is_wwan_enabled()
+    }
+
+    /// Whether the mobile broadband radio is enabled by a hardware switch.
+    pub fn is_wwan_hardware_enabled(&self) -> Result<bool> {
+        self.backend.is_wwan_hardware_enabled()
+    }
+
+    /// Enables or disables networking, deactivating all connections when
+    /// disabled.
+    pub fn set_networking_enabled(&self, enabled: bool) -> Result<()> {
+        self.backend.set_networking_enabled(enabled)
+    }
+
+    pub fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        self.backend.set_wireless_enabled(enabled)
+    }
+
+    pub fn set_wwan_enabled(&self, enabled: bool) -> Result<()> {
+        self.backend.set_wwan_enabled(enabled)
+    }
+
+    /// Puts networking to sleep, e.g. before the system suspends.
+    pub fn sleep(&self) -> Result<()> {
+        self.backend.sleep(true)
+    }
+
+    pub fn wake(&self) -> Result<()> {
+        self.backend.sleep(false)
+    }
+
+    /// Snapshots the configuration of `devices`, or of all devices if none
+    /// are given. Network Manager rolls back to it after `rollback_timeout`
+    /// unless the checkpoint is destroyed before. A zero timeout disables the
+    /// automatic rollback.
+    pub fn create_checkpoint(
+        &self,
+        devices: &[&Device],
+        rollback_timeout: Duration,
+        flags: CheckpointFlags,
+    ) -> Result<Checkpoint> {
+        let devices = devices
+            .iter()
+            .map(|device| device.path().to_string())
+            .collect::<Vec<_>>();
+
+        create_checkpoint(&self.backend, &devices, rollback_timeout, flags)
     }
 }
 
+/// How long the calls of `manager` wait for Network Manager to respond or
+/// complete a request.
+pub fn method_timeout(manager: &NetworkManager) -> Duration {
+    Duration::from_secs(manager.backend.method_timeout())
+}
+
 impl Default for NetworkManager {
     fn default() -> Self {
         Self::new()
@@ -135,3 +226,68 @@ impl From<u32> for Connectivity {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fake::fixtures;
+    use wifi::AccessPointCredentials;
+
+    #[test]
+    fn test_radio_state() {
+        let (fake, manager, _) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "password".into(),
+        };
+
+        let access_points = wifi_device.get_access_points().unwrap();
+        wifi_device
+            .connect(&access_points[0], &credentials)
+            .unwrap();
+
+        fake.set_wireless_hardware_enabled(false);
+
+        assert!(manager.is_wireless_enabled().unwrap());
+        assert!(!manager.is_wireless_hardware_enabled().unwrap());
+        assert!(manager.get_active_connections().unwrap().is_empty());
+        assert!(wifi_device
+            .connect(&access_points[0], &credentials)
+            .is_err());
+
+        fake.set_wireless_hardware_enabled(true);
+        manager.set_wireless_enabled(false).unwrap();
+
+        assert!(wifi_device
+            .connect(&access_points[0], &credentials)
+            .is_err());
+
+        manager.set_wireless_enabled(true).unwrap();
+        wifi_device
+            .connect(&access_points[0], &credentials)
+            .unwrap();
+
+        manager.sleep().unwrap();
+
+        assert!(manager.sleep().is_err());
+        assert_eq!(manager.get_state().unwrap(), NetworkManagerState::Asleep);
+        assert!(manager.get_active_connections().unwrap().is_empty());
+
+        manager.wake().unwrap();
+        manager.set_networking_enabled(false).unwrap();
+
+        assert!(manager.set_networking_enabled(false).is_err());
+        assert!(!manager.is_networking_enabled().unwrap());
+        assert_eq!(manager.get_state().unwrap(), NetworkManagerState::Asleep);
+
+        manager.set_networking_enabled(true).unwrap();
+
+        assert_eq!(
+            manager.get_state().unwrap(),
+            NetworkManagerState::Disconnected
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use std::rc::Rc;
+
+use backend::Backend;
+use errors::*;
+
+use connection::{add_and_activate_connection, add_connection, Connection, ConnectionState};
+use device::{Device, PathGetter};
+use settings::{ConnectionSetting, ConnectionSettings, GsmSetting, Ipv4Setting, GSM_SETTING};
+
+bitflags! {
+    pub struct ModemCapabilities: u32 {
+        // modem has no usable capabilities
+        const MODEM_CAP_NONE      = 0x0000_0000;
+        // modem uses analog wired telephone lines
+        const MODEM_CAP_POTS      = 0x0000_0001;
+        // modem uses CDMA 1x or EV-DO
+        const MODEM_CAP_CDMA_EVDO = 0x0000_0002;
+        // modem uses GSM, GPRS, EDGE, UMTS or HSPA
+        const MODEM_CAP_GSM_UMTS  = 0x0000_0004;
+        // modem uses LTE
+        const MODEM_CAP_LTE       = 0x0000_0008;
+        // modem uses 5G NR
+        const MODEM_CAP_5GNR      = 0x0000_0040;
+    }
+}
+
+pub struct ModemDevice<'a> {
+    backend: Rc<dyn Backend>,
+    device: &'a Device,
+}
+
+impl<'a> ModemDevice<'a> {
+    /// The access technologies the modem supports.
+    pub fn modem_capabilities(&self) -> Result<ModemCapabilities> {
+        self.backend.get_modem_capabilities(self.device.path())
+    }
+
+    /// The access technologies the modem can use right now, e.g. depending
+    /// on the firmware loaded.
+    pub fn current_capabilities(&self) -> Result<ModemCapabilities> {
+        self.backend
+            .get_modem_current_capabilities(self.device.path())
+    }
+
+    /// The identifier of the modem as reported by ModemManager.
+    pub fn device_id(&self) -> Result<String> {
+        self.backend.get_modem_device_id(self.device.path())
+    }
+
+    /// The MCC and MNC of the network the modem is registered on, e.g.
+    /// `26201`. Empty if not registered.
+    pub fn operator_code(&self) -> Result<String> {
+        self.backend.get_modem_operator_code(self.device.path())
+    }
+
+    /// Saves a mobile broadband connection profile bound to the modem,
+    /// without activating it.
+    pub fn add_connection(&self, config: &GsmConfig) -> Result<Connection> {
+        let settings = config.to_settings(self.device.interface())?;
+
+        add_connection(&self.backend, &settings, true)
+    }
+
+    /// Creates a mobile broadband connection profile and activates it on the
+    /// modem.
+    pub fn connect(&self, config: &GsmConfig) -> Result<(Connection, ConnectionState)> {
+        let settings = config.to_settings(self.device.interface())?;
+
+        add_and_activate_connection(&self.backend, self.device.path(), &settings)
+    }
+}
+
+pub fn new_modem_device<'a>(backend: &Rc<dyn Backend>, device: &'a Device) -> ModemDevice<'a> {
+    ModemDevice {
+        backend: Rc::clone(backend),
+        device,
+    }
+}
+
+/// The options of a GSM, UMTS or LTE connection created with
+/// `ModemDevice::connect`.
+///
+/// ```
+/// use network_manager::GsmConfig;
+///
+/// let config = GsmConfig::new("internet")
+///     .username("user")
+///     .password("password")
+///     .pin("1234")
+///     .network_id("26201");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GsmConfig {
+    id: Option<String>,
+    apn: String,
+    username: Option<String>,
+    password: Option<String>,
+    pin: Option<String>,
+    network_id: Option<String>,
+    autoconnect: bool,
+}
+
+impl GsmConfig {
+    /// A connection to the access point name `apn`. Some carriers accept an
+    /// empty APN and pick one themselves.
+    pub fn new(apn: &str) -> Self {
+        GsmConfig {
+            id: None,
+            apn: apn.to_string(),
+            username: None,
+            password: None,
+            pin: None,
+            network_id: None,
+            autoconnect: true,
+        }
+    }
+
+    /// The connection id. Defaults to the APN.
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// The PIN unlocking the SIM card.
+    pub fn pin(mut self, pin: &str) -> Self {
+        self.pin = Some(pin.to_string());
+        self
+    }
+
+    /// Only registers on the network with this MCC and MNC, e.g. `26201`.
+    pub fn network_id(mut self, network_id: &str) -> Self {
+        self.network_id = Some(network_id.to_string());
+        self
+    }
+
+    /// Activates the connection automatically, e.g. after a reboot.
+    /// Defaults to `true`.
+    pub fn autoconnect(mut self, autoconnect: bool) -> Self {
+        self.autoconnect = autoconnect;
+        self
+    }
+
+    /// Builds the settings of the `gsm` connection bound to `interface`.
+    pub fn to_settings(&self, interface: &str) -> Result<ConnectionSettings> {
+        if let Some(ref pin) = self.pin {
+            if pin.len() < 4 || pin.len() > 8 || !pin.chars().all(|c| c.is_ascii_digit()) {
+                bail!(ErrorKind::NetworkManager(
+                    "The PIN must be 4 to 8 digits".into()
+                ));
+            }
+        }
+
+        if let Some(ref network_id) = self.network_id {
+            let digits = network_id.chars().all(|c| c.is_ascii_digit());
+
+            if !digits || network_id.len() < 5 || network_id.len() > 6 {
+                bail!(ErrorKind::NetworkManager(format!(
+                    "Invalid network id: {}",
+                    network_id
+                )));
+            }
+        }
+
+        let id = match self.id {
+            Some(ref id) => id.clone(),
+            None if self.apn.is_empty() => interface.to_string(),
+            None => self.apn.clone(),
+        };
+
+        Ok(ConnectionSettings {
+            connection: ConnectionSetting {
+                id: Some(id),
+                kind: Some(GSM_SETTING.into()),
+                interface_name: Some(interface.into()),
+                autoconnect: Some(self.autoconnect),
+                ..Default::default()
+            },
+            gsm: Some(GsmSetting {
+                apn: Some(self.apn.clone()),
+                username: self.username.clone(),
+                password: self.password.clone(),
+                pin: self.pin.clone(),
+                network_id: self.network_id.clone(),
+                ..Default::default()
+            }),
+            ipv4: Some(Ipv4Setting {
+                method: Some("auto".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use device::DeviceType;
+    use fake::FakeNetworkManager;
+    use manager::{Connectivity, NetworkManager};
+
+    #[test]
+    fn test_gsm_settings() {
+        let settings = GsmConfig::new("internet")
+            .username("user")
+            .password("password")
+            .pin("1234")
+            .network_id("26201")
+            .to_settings("cdc-wdm0")
+            .unwrap();
+
+        assert_eq!(settings.id(), "internet");
+        assert_eq!(settings.kind(), GSM_SETTING);
+
+        let gsm = settings.gsm.unwrap();
+        assert_eq!(gsm.apn, Some("internet".into()));
+        assert_eq!(gsm.pin, Some("1234".into()));
+        assert_eq!(gsm.network_id, Some("26201".into()));
+
+        assert!(GsmConfig::new("internet")
+            .pin("12")
+            .to_settings("cdc-wdm0")
+            .is_err());
+        assert!(GsmConfig::new("internet")
+            .network_id("carrier")
+            .to_settings("cdc-wdm0")
+            .is_err());
+    }
+
+    #[test]
+    fn test_connect() {
+        let fake = Rc::new(FakeNetworkManager::new());
+        let path = fake.add_device("cdc-wdm0", DeviceType::Modem);
+        fake.set_modem_operator_code(&path, "26201");
+
+        let manager = NetworkManager::with_backend(fake.clone());
+
+        let device = manager.get_device_by_interface("cdc-wdm0").unwrap();
+        let modem = device.as_modem_device().unwrap();
+
+        assert!(modem
+            .modem_capabilities()
+            .unwrap()
+            .contains(ModemCapabilities::MODEM_CAP_LTE));
+        assert_eq!(modem.operator_code().unwrap(), "26201");
+
+        manager.set_wwan_enabled(false).unwrap();
+
+        assert!(modem.connect(&GsmConfig::new("internet")).is_err());
+
+        manager.set_wwan_enabled(true).unwrap();
+
+        let (connection, state) = modem.connect(&GsmConfig::new("internet")).unwrap();
+
+        assert_eq!(state, ConnectionState::Activated);
+        assert_eq!(connection.settings().kind(), GSM_SETTING);
+        assert_eq!(manager.get_connectivity().unwrap(), Connectivity::Full);
+    }
+}
@@ -0,0 +1,345 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use errors::*;
+
+use connection::{Connection, ConnectionState};
+use device::Device;
+use hotspot::HotspotConfig;
+use manager::{Connectivity, NetworkManager};
+use ssid::Ssid;
+use wifi::{AccessPointCredentials, WiFiDevice};
+
+const DEFAULT_CREDENTIALS_TIMEOUT: u64 = 60;
+const DEFAULT_SCAN_TIMEOUT: u64 = 10;
+
+/// The network to join, e.g. as entered on a captive portal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkCredentials {
+    pub ssid: Ssid,
+    pub credentials: AccessPointCredentials,
+}
+
+/// Where an `OnboardingController` gets the credentials of the network to
+/// join from while its hotspot is up.
+pub trait CredentialSource {
+    /// Waits up to `timeout` for credentials. Returns `Ok(None)` if none
+    /// arrived in time.
+    fn wait_for_credentials(&mut self, timeout: Duration) -> Result<Option<NetworkCredentials>>;
+
+    /// Called when joining the network failed, right before the hotspot is
+    /// started again.
+    fn connection_failed(&mut self, _credentials: &NetworkCredentials, _error: &Error) {}
+}
+
+/// Receives credentials sent from another thread, e.g. a web server.
+impl CredentialSource for Receiver<NetworkCredentials> {
+    fn wait_for_credentials(&mut self, timeout: Duration) -> Result<Option<NetworkCredentials>> {
+        match self.recv_timeout(timeout) {
+            Ok(credentials) => Ok(Some(credentials)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => bail!(ErrorKind::NetworkManager(
+                "Credential source disconnected".into()
+            )),
+        }
+    }
+}
+
+pub struct OnboardingConfig {
+    /// The Wi-Fi interface used for both the hotspot and the client
+    /// connection.
+    pub interface: String,
+    pub hotspot_ssid: String,
+    pub hotspot: HotspotConfig,
+    /// How long to wait for credentials before checking connectivity again,
+    /// e.g. in case an Ethernet cable was plugged in.
+    pub credentials_timeout: Duration,
+    /// How long to scan for the network to join.
+    pub scan_timeout: Duration,
+}
+
+impl OnboardingConfig {
+    pub fn new(interface: &str, hotspot_ssid: &str) -> Self {
+        OnboardingConfig {
+            interface: interface.to_string(),
+            hotspot_ssid: hotspot_ssid.to_string(),
+            hotspot: HotspotConfig::new(),
+            credentials_timeout: Duration::from_secs(DEFAULT_CREDENTIALS_TIMEOUT),
+            scan_timeout: Duration::from_secs(DEFAULT_SCAN_TIMEOUT),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OnboardingState {
+    /// Connectivity is checked on the next step.
+    Offline,
+    /// The hotspot is up and credentials are awaited.
+    Hotspot,
+    /// Credentials arrived and the network is joined on the next step.
+    Connecting(Box<NetworkCredentials>),
+    /// Connectivity is available.
+    Online,
+}
+
+/// Brings a device online: a hotspot is started while there is no
+/// connectivity, and once credentials arrive through the `CredentialSource`
+/// the hotspot is torn down and the network is joined. The hotspot is
+/// started again if that fails.
+///
+/// The controller is driven by calling `step` for single transitions or
+/// `run` to go on until online.
+pub struct OnboardingController<'a, S> {
+    manager: &'a NetworkManager,
+    source: S,
+    config: OnboardingConfig,
+    state: OnboardingState,
+    hotspot: Option<Connection>,
+}
+
+impl<'a, S> OnboardingController<'a, S>
+where
+    S: CredentialSource,
+{
+    pub fn new(manager: &'a NetworkManager, source: S, config: OnboardingConfig) -> Self {
+        OnboardingController {
+            manager,
+            source,
+            config,
+            state: OnboardingState::Offline,
+            hotspot: None,
+        }
+    }
+
+    pub fn state(&self) -> &OnboardingState {
+        &self.state
+    }
+
+    /// Performs the transition out of the current state. Blocks for up to
+    /// the credentials timeout in the `Hotspot` state.
+    pub fn step(&mut self) -> Result<&OnboardingState> {
+        let state = match self.state.clone() {
+            OnboardingState::Offline => {
+                if self.is_online()? {
+                    OnboardingState::Online
+                } else {
+                    self.start_hotspot()?;
+                    OnboardingState::Hotspot
+                }
+            }
+            OnboardingState::Hotspot => {
+                let timeout = self.config.credentials_timeout;
+
+                match self.source.wait_for_credentials(timeout)? {
+                    Some(credentials) => {
+                        self.stop_hotspot()?;
+                        OnboardingState::Connecting(Box::new(credentials))
+                    }
+                    None => {
+                        if self.is_online()? {
+                            self.stop_hotspot()?;
+                            OnboardingState::Online
+                        } else {
+                            OnboardingState::Hotspot
+                        }
+                    }
+                }
+            }
+            OnboardingState::Connecting(credentials) => match self.connect(&credentials) {
+                Ok(()) => OnboardingState::Online,
+                Err(error) => {
+                    warn!("Joining {:?} failed: {}", credentials.ssid, error);
+
+                    self.source.connection_failed(&credentials, &error);
+                    self.start_hotspot()?;
+                    OnboardingState::Hotspot
+                }
+            },
+            OnboardingState::Online => {
+                if self.is_online()? {
+                    OnboardingState::Online
+                } else {
+                    OnboardingState::Offline
+                }
+            }
+        };
+
+        debug!("Onboarding state: {:?}", state);
+
+        self.state = state;
+
+        Ok(&self.state)
+    }
+
+    /// Steps until online.
+    pub fn run(&mut self) -> Result<()> {
+        while *self.step()? != OnboardingState::Online {}
+
+        Ok(())
+    }
+
+    fn is_online(&self) -> Result<bool> {
+        Ok(self.manager.get_connectivity()? == Connectivity::Full)
+    }
+
+    fn device(&self) -> Result<Device> {
+        self.manager.get_device_by_interface(&self.config.interface)
+    }
+
+    /// Activates the hotspot, reusing the profile created the first time.
+    fn start_hotspot(&mut self) -> Result<()> {
+        let state = match self.hotspot {
+            Some(ref hotspot) => hotspot.activate()?,
+            None => {
+                let device = self.device()?;
+
+                let (hotspot, state) = as_wifi_device(&device)?
+                    .create_hotspot(&self.config.hotspot_ssid as &str, &self.config.hotspot)?;
+
+                self.hotspot = Some(hotspot);
+
+                state
+            }
+        };
+
+        if state != ConnectionState::Activated {
+            bail!(ErrorKind::NetworkManager(format!(
+                "Hotspot not activated: {:?}",
+                state
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn stop_hotspot(&self) -> Result<()> {
+        if let Some(ref hotspot) = self.hotspot {
+            if hotspot.get_state()? != ConnectionState::Deactivated {
+                hotspot.deactivate()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Joins the network, probing for it directly if a scan does not find it.
+    fn connect(&self, credentials: &NetworkCredentials) -> Result<()> {
+        let device = self.device()?;
+        let wifi_device = as_wifi_device(&device)?;
+
+        let access_points = match wifi_device.scan_and_wait(self.config.scan_timeout) {
+            Ok(access_points) => access_points,
+            Err(error) => {
+                warn!("Scan failed, using the known access points: {}", error);
+
+                wifi_device.get_access_points()?
+            }
+        };
+
+        let access_point = access_points
+            .iter()
+            .find(|access_point| access_point.ssid == credentials.ssid);
+
+        let (_, state) = match access_point {
+            Some(access_point) => wifi_device.connect(access_point, &credentials.credentials)?,
+            None => {
+                wifi_device.connect_hidden(credentials.ssid.as_bytes(), &credentials.credentials)?
+            }
+        };
+
+        if state != ConnectionState::Activated {
+            bail!(ErrorKind::NetworkManager(format!(
+                "Connection not activated: {:?}",
+                state
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn as_wifi_device(device: &Device) -> Result<WiFiDevice<'_>> {
+    device.as_wifi_device().ok_or_else(|| {
+        ErrorKind::NetworkManager(format!("{} is not a Wi-Fi device", device.interface())).into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use fake::{fixtures, FakeNetworkManager};
+    use manager::NetworkManagerState;
+
+    fn credentials(passphrase: &str) -> NetworkCredentials {
+        NetworkCredentials {
+            ssid: Ssid::from_bytes("office").unwrap(),
+            credentials: AccessPointCredentials::Wpa {
+                passphrase: passphrase.into(),
+            },
+        }
+    }
+
+    fn setup() -> (Rc<FakeNetworkManager>, NetworkManager, OnboardingConfig) {
+        let (fake, manager, _) = fixtures::wifi();
+
+        let mut config = OnboardingConfig::new("wlan0", "onboarding");
+        config.credentials_timeout = Duration::from_secs(0);
+
+        (fake, manager, config)
+    }
+
+    #[test]
+    fn test_onboarding_fallback() {
+        let (_, manager, config) = setup();
+
+        let (sender, receiver) = mpsc::channel();
+        let mut controller = OnboardingController::new(&manager, receiver, config);
+
+        assert_eq!(*controller.step().unwrap(), OnboardingState::Hotspot);
+        assert_eq!(
+            manager.get_state().unwrap(),
+            NetworkManagerState::ConnectedLocal
+        );
+
+        assert_eq!(*controller.step().unwrap(), OnboardingState::Hotspot);
+
+        sender.send(credentials("wrong password")).unwrap();
+
+        assert_eq!(
+            *controller.step().unwrap(),
+            OnboardingState::Connecting(Box::new(credentials("wrong password")))
+        );
+        assert_eq!(*controller.step().unwrap(), OnboardingState::Hotspot);
+        assert_eq!(
+            manager.get_state().unwrap(),
+            NetworkManagerState::ConnectedLocal
+        );
+
+        sender.send(credentials("password")).unwrap();
+
+        controller.run().unwrap();
+
+        assert_eq!(*controller.state(), OnboardingState::Online);
+        assert_eq!(manager.get_active_connections().unwrap().len(), 1);
+        assert_eq!(manager.get_connectivity().unwrap(), Connectivity::Full);
+    }
+
+    #[test]
+    fn test_onboarding_connectivity_restored() {
+        let (fake, manager, config) = setup();
+
+        let (_sender, receiver) = mpsc::channel();
+        let mut controller = OnboardingController::new(&manager, receiver, config);
+
+        assert_eq!(*controller.step().unwrap(), OnboardingState::Hotspot);
+
+        fake.set_connectivity(Connectivity::Full);
+
+        assert_eq!(*controller.step().unwrap(), OnboardingState::Online);
+        assert!(manager.get_active_connections().unwrap().is_empty());
+    }
+}
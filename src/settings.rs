@@ -0,0 +1,1064 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ssid::Ssid;
+
+pub const CONNECTION_SETTING: &str = "connection";
+pub const WIRELESS_SETTING: &str = "802-11-wireless";
+pub const WIRELESS_SECURITY_SETTING: &str = "802-11-wireless-security";
+pub const IEEE8021X_SETTING: &str = "802-1x";
+pub const ETHERNET_SETTING: &str = "802-3-ethernet";
+pub const GSM_SETTING: &str = "gsm";
+pub const VPN_SETTING: &str = "vpn";
+pub const IPV4_SETTING: &str = "ipv4";
+pub const IPV6_SETTING: &str = "ipv6";
+
+/// The keys of a setting group and their values.
+pub type SettingGroup = BTreeMap<String, SettingValue>;
+
+/// A setting value with its exact D-Bus type.
+///
+/// Arrays keep the signature of their elements, so that empty arrays can be
+/// sent back to Network Manager unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingValue {
+    Bool(bool),
+    Byte(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Double(f64),
+    String(String),
+    ObjectPath(String),
+    Array(Vec<SettingValue>, String),
+    Struct(Vec<SettingValue>),
+    DictEntry(Box<SettingValue>, Box<SettingValue>),
+    Variant(Box<SettingValue>),
+}
+
+impl SettingValue {
+    /// Creates a byte array (`ay`).
+    pub fn bytes(bytes: &[u8]) -> Self {
+        SettingValue::Array(
+            bytes.iter().map(|b| SettingValue::Byte(*b)).collect(),
+            "y".into(),
+        )
+    }
+
+    /// Creates a string array (`as`).
+    pub fn strings<S: AsRef<str>>(strings: &[S]) -> Self {
+        SettingValue::Array(
+            strings
+                .iter()
+                .map(|s| SettingValue::String(s.as_ref().to_string()))
+                .collect(),
+            "s".into(),
+        )
+    }
+
+    /// Creates a string to variant dictionary (`a{sv}`).
+    pub fn dict(group: &SettingGroup) -> Self {
+        SettingValue::Array(
+            group
+                .iter()
+                .map(|(key, value)| {
+                    SettingValue::DictEntry(
+                        Box::new(SettingValue::String(key.clone())),
+                        Box::new(SettingValue::Variant(Box::new(value.clone()))),
+                    )
+                })
+                .collect(),
+            "{sv}".into(),
+        )
+    }
+
+    /// Returns the D-Bus signature of the value.
+    pub fn signature(&self) -> String {
+        match *self {
+            SettingValue::Bool(_) => "b".into(),
+            SettingValue::Byte(_) => "y".into(),
+            SettingValue::Int16(_) => "n".into(),
+            SettingValue::UInt16(_) => "q".into(),
+            SettingValue::Int32(_) => "i".into(),
+            SettingValue::UInt32(_) => "u".into(),
+            SettingValue::Int64(_) => "x".into(),
+            SettingValue::UInt64(_) => "t".into(),
+            SettingValue::Double(_) => "d".into(),
+            SettingValue::String(_) => "s".into(),
+            SettingValue::ObjectPath(_) => "o".into(),
+            SettingValue::Array(_, ref signature) => format!("a{}", signature),
+            SettingValue::Struct(ref fields) => format!(
+                "({})",
+                fields.iter().map(|f| f.signature()).collect::<String>()
+            ),
+            SettingValue::DictEntry(ref key, ref value) => {
+                format!("{{{}{}}}", key.signature(), value.signature())
+            }
+            SettingValue::Variant(_) => "v".into(),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            SettingValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of any integer type.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            SettingValue::Byte(value) => Some(i64::from(value)),
+            SettingValue::Int16(value) => Some(i64::from(value)),
+            SettingValue::UInt16(value) => Some(i64::from(value)),
+            SettingValue::Int32(value) => Some(i64::from(value)),
+            SettingValue::UInt32(value) => Some(i64::from(value)),
+            SettingValue::Int64(value) => Some(value),
+            SettingValue::UInt64(value) => Some(value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            SettingValue::String(ref value) | SettingValue::ObjectPath(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the contents of a byte array.
+    pub fn as_bytes(&self) -> Option<Vec<u8>> {
+        match *self {
+            SettingValue::Array(ref items, ref signature) if signature == "y" => items
+                .iter()
+                .map(|item| match *item {
+                    SettingValue::Byte(byte) => Some(byte),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries of a string keyed dictionary, unwrapping variants.
+    pub fn as_dict(&self) -> Option<SettingGroup> {
+        match *self {
+            SettingValue::Array(ref items, _) => items
+                .iter()
+                .map(|item| match *item {
+                    SettingValue::DictEntry(ref key, ref value) => {
+                        let value = match **value {
+                            SettingValue::Variant(ref inner) => (**inner).clone(),
+                            ref value => value.clone(),
+                        };
+
+                        key.as_str().map(|key| (key.to_string(), value))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Conversion between typed setting fields and their D-Bus values.
+pub trait SettingType: Sized {
+    /// The D-Bus signature of the value.
+    const SIGNATURE: &'static str;
+
+    fn from_setting(value: &SettingValue) -> Option<Self>;
+
+    fn to_setting(&self) -> SettingValue;
+}
+
+impl SettingType for bool {
+    const SIGNATURE: &'static str = "b";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        value.as_bool()
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Bool(*self)
+    }
+}
+
+impl SettingType for i32 {
+    const SIGNATURE: &'static str = "i";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Int32(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Int32(*self)
+    }
+}
+
+impl SettingType for u32 {
+    const SIGNATURE: &'static str = "u";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::UInt32(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::UInt32(*self)
+    }
+}
+
+impl SettingType for i64 {
+    const SIGNATURE: &'static str = "x";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Int64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Int64(*self)
+    }
+}
+
+impl SettingType for u64 {
+    const SIGNATURE: &'static str = "t";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::UInt64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::UInt64(*self)
+    }
+}
+
+impl SettingType for String {
+    const SIGNATURE: &'static str = "s";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::String(ref value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::String(self.clone())
+    }
+}
+
+impl SettingType for Vec<u8> {
+    const SIGNATURE: &'static str = "ay";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        value.as_bytes()
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::bytes(self)
+    }
+}
+
+impl SettingType for Vec<String> {
+    const SIGNATURE: &'static str = "as";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Array(ref items, ref signature) if signature == "s" => {
+                items.iter().map(String::from_setting).collect()
+            }
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::strings(self)
+    }
+}
+
+impl SettingType for BTreeMap<String, String> {
+    const SIGNATURE: &'static str = "a{ss}";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Array(ref items, ref signature) if signature == "{ss}" => items
+                .iter()
+                .map(|item| match *item {
+                    SettingValue::DictEntry(ref key, ref value) => {
+                        Some((String::from_setting(key)?, String::from_setting(value)?))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Array(
+            self.iter()
+                .map(|(key, value)| {
+                    SettingValue::DictEntry(
+                        Box::new(key.to_setting()),
+                        Box::new(value.to_setting()),
+                    )
+                })
+                .collect(),
+            "{ss}".into(),
+        )
+    }
+}
+
+impl SettingType for Ssid {
+    const SIGNATURE: &'static str = "ay";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        Ssid::from_bytes(value.as_bytes()?).ok()
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::bytes(self.as_bytes())
+    }
+}
+
+impl SettingType for Ipv4Addr {
+    const SIGNATURE: &'static str = "s";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        value.as_str()?.parse().ok()
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::String(self.to_string())
+    }
+}
+
+impl SettingType for Ipv6Addr {
+    const SIGNATURE: &'static str = "s";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        value.as_str()?.parse().ok()
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::String(self.to_string())
+    }
+}
+
+/// IPv4 DNS servers are sent as `u32` values in network byte order.
+impl SettingType for Vec<Ipv4Addr> {
+    const SIGNATURE: &'static str = "au";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Array(ref items, ref signature) if signature == "u" => items
+                .iter()
+                .map(|item| u32::from_setting(item).map(|a| Ipv4Addr::from(a.to_ne_bytes())))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Array(
+            self.iter()
+                .map(|a| SettingValue::UInt32(u32::from_ne_bytes(a.octets())))
+                .collect(),
+            "u".into(),
+        )
+    }
+}
+
+/// IPv6 DNS servers are sent as byte arrays.
+impl SettingType for Vec<Ipv6Addr> {
+    const SIGNATURE: &'static str = "aay";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Array(ref items, ref signature) if signature == "ay" => items
+                .iter()
+                .map(|item| {
+                    let bytes = item.as_bytes()?;
+
+                    if bytes.len() != 16 {
+                        return None;
+                    }
+
+                    let mut octets = [0; 16];
+                    octets.copy_from_slice(&bytes);
+
+                    Some(Ipv6Addr::from(octets))
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Array(
+            self.iter()
+                .map(|a| SettingValue::bytes(&a.octets()))
+                .collect(),
+            "ay".into(),
+        )
+    }
+}
+
+/// An entry of the `address-data` IP setting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpAddress {
+    pub address: IpAddr,
+    pub prefix: u32,
+    /// Additional attributes, e.g. `label`.
+    pub attributes: SettingGroup,
+}
+
+impl SettingType for Vec<IpAddress> {
+    const SIGNATURE: &'static str = "aa{sv}";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Array(ref items, ref signature) if signature == "a{sv}" => items
+                .iter()
+                .map(|item| {
+                    let mut attributes = item.as_dict()?;
+
+                    let address = attributes.remove("address")?.as_str()?.parse().ok()?;
+                    let prefix = u32::from_setting(&attributes.remove("prefix")?)?;
+
+                    Some(IpAddress {
+                        address,
+                        prefix,
+                        attributes,
+                    })
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Array(
+            self.iter()
+                .map(|address| {
+                    let mut group = address.attributes.clone();
+                    group.insert(
+                        "address".into(),
+                        SettingValue::String(address.address.to_string()),
+                    );
+                    group.insert("prefix".into(), SettingValue::UInt32(address.prefix));
+                    SettingValue::dict(&group)
+                })
+                .collect(),
+            "a{sv}".into(),
+        )
+    }
+}
+
+/// An entry of the `route-data` IP setting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpRoute {
+    pub dest: IpAddr,
+    pub prefix: u32,
+    pub next_hop: Option<IpAddr>,
+    pub metric: Option<u32>,
+    /// Additional attributes, e.g. `table` or `mtu`.
+    pub attributes: SettingGroup,
+}
+
+impl SettingType for Vec<IpRoute> {
+    const SIGNATURE: &'static str = "aa{sv}";
+
+    fn from_setting(value: &SettingValue) -> Option<Self> {
+        match *value {
+            SettingValue::Array(ref items, ref signature) if signature == "a{sv}" => items
+                .iter()
+                .map(|item| {
+                    let mut attributes = item.as_dict()?;
+
+                    let dest = attributes.remove("dest")?.as_str()?.parse().ok()?;
+                    let prefix = u32::from_setting(&attributes.remove("prefix")?)?;
+
+                    let next_hop = match attributes.remove("next-hop") {
+                        Some(next_hop) => Some(next_hop.as_str()?.parse().ok()?),
+                        None => None,
+                    };
+
+                    let metric = match attributes.remove("metric") {
+                        Some(metric) => Some(u32::from_setting(&metric)?),
+                        None => None,
+                    };
+
+                    Some(IpRoute {
+                        dest,
+                        prefix,
+                        next_hop,
+                        metric,
+                        attributes,
+                    })
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn to_setting(&self) -> SettingValue {
+        SettingValue::Array(
+            self.iter()
+                .map(|route| {
+                    let mut group = route.attributes.clone();
+                    group.insert("dest".into(), SettingValue::String(route.dest.to_string()));
+                    group.insert("prefix".into(), SettingValue::UInt32(route.prefix));
+                    if let Some(next_hop) = route.next_hop {
+                        group.insert(
+                            "next-hop".into(),
+                            SettingValue::String(next_hop.to_string()),
+                        );
+                    }
+                    if let Some(metric) = route.metric {
+                        group.insert("metric".into(), SettingValue::UInt32(metric));
+                    }
+                    SettingValue::dict(&group)
+                })
+                .collect(),
+            "a{sv}".into(),
+        )
+    }
+}
+
+/// Moves a key out of `group` if its value has the expected type.
+fn take<T: SettingType>(group: &mut SettingGroup, key: &str) -> Option<T> {
+    let value = group.get(key).and_then(T::from_setting)?;
+
+    group.remove(key);
+
+    Some(value)
+}
+
+fn put<T: SettingType>(group: &mut SettingGroup, key: &str, value: &Option<T>) {
+    if let Some(ref value) = *value {
+        group.insert(key.to_string(), value.to_setting());
+    }
+}
+
+/// Defines a setting group with a typed field for each of the given keys.
+/// Keys that are missing or have an unexpected type end up in `other`.
+macro_rules! setting_group {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident: $ty:ty = $key:expr,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct $name {
+            $(
+                $(#[$field_meta])*
+                pub $field: Option<$ty>,
+            )*
+            /// Keys without a typed field.
+            pub other: SettingGroup,
+        }
+
+        impl $name {
+            pub fn from_group(mut group: SettingGroup) -> Self {
+                $name {
+                    $($field: take(&mut group, $key),)*
+                    other: group,
+                }
+            }
+
+            pub fn to_group(&self) -> SettingGroup {
+                let mut group = self.other.clone();
+                $(put(&mut group, $key, &self.$field);)*
+                group
+            }
+
+            /// Returns the D-Bus signature of a key with a typed field.
+            pub fn signature(key: &str) -> Option<&'static str> {
+                $(
+                    if key == $key {
+                        return Some(<$ty as SettingType>::SIGNATURE);
+                    }
+                )*
+                None
+            }
+        }
+    };
+}
+
+setting_group! {
+    /// The `connection` setting group.
+    pub struct ConnectionSetting {
+        id: String = "id",
+        uuid: String = "uuid",
+        /// The connection type, e.g. `802-11-wireless`.
+        kind: String = "type",
+        interface_name: String = "interface-name",
+        autoconnect: bool = "autoconnect",
+        autoconnect_priority: i32 = "autoconnect-priority",
+        autoconnect_retries: i32 = "autoconnect-retries",
+        /// Seconds since the Unix epoch of the last successful activation.
+        timestamp: u64 = "timestamp",
+        read_only: bool = "read-only",
+        permissions: Vec<String> = "permissions",
+        zone: String = "zone",
+        master: String = "master",
+        slave_type: String = "slave-type",
+        metered: i32 = "metered",
+    }
+}
+
+setting_group! {
+    /// The `802-11-wireless` setting group.
+    pub struct WirelessSetting {
+        ssid: Ssid = "ssid",
+        /// `infrastructure`, `adhoc`, `ap` or `mesh`.
+        mode: String = "mode",
+        band: String = "band",
+        channel: u32 = "channel",
+        bssid: Vec<u8> = "bssid",
+        mac_address: Vec<u8> = "mac-address",
+        cloned_mac_address: Vec<u8> = "cloned-mac-address",
+        mtu: u32 = "mtu",
+        hidden: bool = "hidden",
+        powersave: u32 = "powersave",
+        seen_bssids: Vec<String> = "seen-bssids",
+    }
+}
+
+setting_group! {
+    /// The `802-11-wireless-security` setting group.
+    pub struct WirelessSecuritySetting {
+        /// `none`, `ieee8021x`, `wpa-psk`, `wpa-eap`, `sae` or `owe` among others.
+        key_mgmt: String = "key-mgmt",
+        auth_alg: String = "auth-alg",
+        proto: Vec<String> = "proto",
+        pairwise: Vec<String> = "pairwise",
+        group: Vec<String> = "group",
+        psk: String = "psk",
+        psk_flags: u32 = "psk-flags",
+        wep_key0: String = "wep-key0",
+        wep_key1: String = "wep-key1",
+        wep_key2: String = "wep-key2",
+        wep_key3: String = "wep-key3",
+        wep_key_flags: u32 = "wep-key-flags",
+        wep_key_type: u32 = "wep-key-type",
+        wep_tx_keyidx: u32 = "wep-tx-keyidx",
+        leap_username: String = "leap-username",
+        leap_password: String = "leap-password",
+        pmf: i32 = "pmf",
+    }
+}
+
+setting_group! {
+    /// The `802-1x` setting group.
+    pub struct Ieee8021xSetting {
+        eap: Vec<String> = "eap",
+        identity: String = "identity",
+        anonymous_identity: String = "anonymous-identity",
+        password: String = "password",
+        password_flags: u32 = "password-flags",
+        phase1_peaplabel: String = "phase1-peaplabel",
+        phase1_peapver: String = "phase1-peapver",
+        phase2_auth: String = "phase2-auth",
+        phase2_autheap: String = "phase2-autheap",
+        ca_cert: Vec<u8> = "ca-cert",
+        ca_path: String = "ca-path",
+        client_cert: Vec<u8> = "client-cert",
+        private_key: Vec<u8> = "private-key",
+        private_key_password: String = "private-key-password",
+        private_key_password_flags: u32 = "private-key-password-flags",
+        domain_suffix_match: String = "domain-suffix-match",
+        system_ca_certs: bool = "system-ca-certs",
+    }
+}
+
+setting_group! {
+    /// The `802-3-ethernet` setting group.
+    pub struct EthernetSetting {
+        mac_address: Vec<u8> = "mac-address",
+        cloned_mac_address: Vec<u8> = "cloned-mac-address",
+        mtu: u32 = "mtu",
+        auto_negotiate: bool = "auto-negotiate",
+        speed: u32 = "speed",
+        duplex: String = "duplex",
+        port: String = "port",
+        wake_on_lan: u32 = "wake-on-lan",
+        s390_subchannels: Vec<String> = "s390-subchannels",
+        s390_nettype: String = "s390-nettype",
+        s390_options: BTreeMap<String, String> = "s390-options",
+    }
+}
+
+setting_group! {
+    /// The `gsm` setting group.
+    pub struct GsmSetting {
+        apn: String = "apn",
+        number: String = "number",
+        username: String = "username",
+        password: String = "password",
+        password_flags: u32 = "password-flags",
+        pin: String = "pin",
+        pin_flags: u32 = "pin-flags",
+        network_id: String = "network-id",
+        home_only: bool = "home-only",
+        auto_config: bool = "auto-config",
+        device_id: String = "device-id",
+        sim_id: String = "sim-id",
+        sim_operator_id: String = "sim-operator-id",
+        mtu: u32 = "mtu",
+    }
+}
+
+setting_group! {
+    /// The `vpn` setting group.
+    pub struct VpnSetting {
+        service_type: String = "service-type",
+        user_name: String = "user-name",
+        persistent: bool = "persistent",
+        timeout: u32 = "timeout",
+        /// Plugin specific options.
+        data: BTreeMap<String, String> = "data",
+        /// Plugin specific secrets.
+        secrets: BTreeMap<String, String> = "secrets",
+    }
+}
+
+setting_group! {
+    /// The `ipv4` setting group.
+    ///
+    /// The deprecated `addresses` and `routes` keys are left in `other`.
+    pub struct Ipv4Setting {
+        /// `auto`, `link-local`, `manual`, `shared` or `disabled`.
+        method: String = "method",
+        address_data: Vec<IpAddress> = "address-data",
+        gateway: Ipv4Addr = "gateway",
+        route_data: Vec<IpRoute> = "route-data",
+        dns: Vec<Ipv4Addr> = "dns",
+        dns_search: Vec<String> = "dns-search",
+        dns_priority: i32 = "dns-priority",
+        ignore_auto_dns: bool = "ignore-auto-dns",
+        ignore_auto_routes: bool = "ignore-auto-routes",
+        never_default: bool = "never-default",
+        may_fail: bool = "may-fail",
+        route_metric: i64 = "route-metric",
+        dhcp_client_id: String = "dhcp-client-id",
+        dhcp_hostname: String = "dhcp-hostname",
+        dhcp_send_hostname: bool = "dhcp-send-hostname",
+        dhcp_timeout: i32 = "dhcp-timeout",
+    }
+}
+
+setting_group! {
+    /// The `ipv6` setting group.
+    ///
+    /// The deprecated `addresses` and `routes` keys are left in `other`.
+    pub struct Ipv6Setting {
+        /// `auto`, `dhcp`, `link-local`, `manual`, `shared`, `ignore` or `disabled`.
+        method: String = "method",
+        address_data: Vec<IpAddress> = "address-data",
+        gateway: Ipv6Addr = "gateway",
+        route_data: Vec<IpRoute> = "route-data",
+        dns: Vec<Ipv6Addr> = "dns",
+        dns_search: Vec<String> = "dns-search",
+        dns_priority: i32 = "dns-priority",
+        ignore_auto_dns: bool = "ignore-auto-dns",
+        ignore_auto_routes: bool = "ignore-auto-routes",
+        never_default: bool = "never-default",
+        may_fail: bool = "may-fail",
+        route_metric: i64 = "route-metric",
+        addr_gen_mode: i32 = "addr-gen-mode",
+        ip6_privacy: i32 = "ip6-privacy",
+        dhcp_hostname: String = "dhcp-hostname",
+        dhcp_send_hostname: bool = "dhcp-send-hostname",
+    }
+}
+
+/// Returns the D-Bus signature of a key with a typed field.
+pub fn setting_signature(setting: &str, key: &str) -> Option<&'static str> {
+    match setting {
+        CONNECTION_SETTING => ConnectionSetting::signature(key),
+        WIRELESS_SETTING => WirelessSetting::signature(key),
+        WIRELESS_SECURITY_SETTING => WirelessSecuritySetting::signature(key),
+        IEEE8021X_SETTING => Ieee8021xSetting::signature(key),
+        ETHERNET_SETTING => EthernetSetting::signature(key),
+        GSM_SETTING => GsmSetting::signature(key),
+        VPN_SETTING => VpnSetting::signature(key),
+        IPV4_SETTING => Ipv4Setting::signature(key),
+        IPV6_SETTING => Ipv6Setting::signature(key),
+        _ => None,
+    }
+}
+
+/// The settings of a Network Manager connection profile.
+///
+/// Known setting groups and keys are available as typed fields. Everything
+/// else is kept as is in the `other` maps, so converting the settings back
+/// with `to_groups` does not lose any information.
+///
+/// The `kind`, `id`, `uuid`, `ssid` and `mode` fields of earlier versions are
+/// replaced by accessors of the same names. `ssid` and `mode` return `None`
+/// for connections without a `802-11-wireless` setting.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionSettings {
+    pub connection: ConnectionSetting,
+    pub wireless: Option<WirelessSetting>,
+    pub wireless_security: Option<WirelessSecuritySetting>,
+    pub ieee8021x: Option<Ieee8021xSetting>,
+    pub ethernet: Option<EthernetSetting>,
+    pub gsm: Option<GsmSetting>,
+    pub vpn: Option<VpnSetting>,
+    pub ipv4: Option<Ipv4Setting>,
+    pub ipv6: Option<Ipv6Setting>,
+    /// Setting groups without a typed representation, e.g. `proxy`.
+    pub other: BTreeMap<String, SettingGroup>,
+}
+
+impl ConnectionSettings {
+    pub fn from_groups(mut groups: BTreeMap<String, SettingGroup>) -> Self {
+        ConnectionSettings {
+            connection: ConnectionSetting::from_group(
+                groups.remove(CONNECTION_SETTING).unwrap_or_default(),
+            ),
+            wireless: groups
+                .remove(WIRELESS_SETTING)
+                .map(WirelessSetting::from_group),
+            wireless_security: groups
+                .remove(WIRELESS_SECURITY_SETTING)
+                .map(WirelessSecuritySetting::from_group),
+            ieee8021x: groups
+                .remove(IEEE8021X_SETTING)
+                .map(Ieee8021xSetting::from_group),
+            ethernet: groups
+                .remove(ETHERNET_SETTING)
+                .map(EthernetSetting::from_group),
+            gsm: groups.remove(GSM_SETTING).map(GsmSetting::from_group),
+            vpn: groups.remove(VPN_SETTING).map(VpnSetting::from_group),
+            ipv4: groups.remove(IPV4_SETTING).map(Ipv4Setting::from_group),
+            ipv6: groups.remove(IPV6_SETTING).map(Ipv6Setting::from_group),
+            other: groups,
+        }
+    }
+
+    pub fn to_groups(&self) -> BTreeMap<String, SettingGroup> {
+        let mut groups = self.other.clone();
+
+        groups.insert(CONNECTION_SETTING.into(), self.connection.to_group());
+
+        let mut insert = |name: &str, group: Option<SettingGroup>| {
+            if let Some(group) = group {
+                groups.insert(name.into(), group);
+            }
+        };
+
+        insert(
+            WIRELESS_SETTING,
+            self.wireless.as_ref().map(|s| s.to_group()),
+        );
+        insert(
+            WIRELESS_SECURITY_SETTING,
+            self.wireless_security.as_ref().map(|s| s.to_group()),
+        );
+        insert(
+            IEEE8021X_SETTING,
+            self.ieee8021x.as_ref().map(|s| s.to_group()),
+        );
+        insert(
+            ETHERNET_SETTING,
+            self.ethernet.as_ref().map(|s| s.to_group()),
+        );
+        insert(GSM_SETTING, self.gsm.as_ref().map(|s| s.to_group()));
+        insert(VPN_SETTING, self.vpn.as_ref().map(|s| s.to_group()));
+        insert(IPV4_SETTING, self.ipv4.as_ref().map(|s| s.to_group()));
+        insert(IPV6_SETTING, self.ipv6.as_ref().map(|s| s.to_group()));
+
+        groups
+    }
+
+    pub fn id(&self) -> &str {
+        self.connection.id.as_ref().map_or("", |id| id)
+    }
+
+    pub fn uuid(&self) -> &str {
+        self.connection.uuid.as_ref().map_or("", |uuid| uuid)
+    }
+
+    /// The connection type, e.g. `802-11-wireless`.
+    pub fn kind(&self) -> &str {
+        self.connection.kind.as_ref().map_or("", |kind| kind)
+    }
+
+    /// The SSID of a Wi-Fi connection.
+    pub fn ssid(&self) -> Option<&Ssid> {
+        self.wireless.as_ref().and_then(|w| w.ssid.as_ref())
+    }
+
+    /// The mode of a Wi-Fi connection, e.g. `infrastructure` or `ap`.
+    pub fn mode(&self) -> Option<&str> {
+        self.wireless
+            .as_ref()
+            .and_then(|w| w.mode.as_ref())
+            .map(|mode| mode as &str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(entries: Vec<(&str, SettingValue)>) -> SettingGroup {
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+
+    fn wifi_groups() -> BTreeMap<String, SettingGroup> {
+        let mut groups = BTreeMap::new();
+
+        groups.insert(
+            "connection".into(),
+            group(vec![
+                ("id", SettingValue::String("office".into())),
+                (
+                    "uuid",
+                    SettingValue::String("6a4b6b4e-5d43-4e4f-a6d5-1f3b2c0e7a11".into()),
+                ),
+                ("type", SettingValue::String("802-11-wireless".into())),
+                ("interface-name", SettingValue::String("wlan0".into())),
+                ("autoconnect-priority", SettingValue::Int32(10)),
+                ("timestamp", SettingValue::UInt64(1_600_000_000)),
+                ("permissions", SettingValue::strings::<&str>(&[])),
+                ("llmnr", SettingValue::Int32(-1)),
+            ]),
+        );
+        groups.insert(
+            "802-11-wireless".into(),
+            group(vec![
+                ("ssid", SettingValue::bytes(b"office")),
+                ("mode", SettingValue::String("infrastructure".into())),
+                ("seen-bssids", SettingValue::strings(&["00:11:22:33:44:55"])),
+            ]),
+        );
+        groups.insert(
+            "ipv4".into(),
+            group(vec![
+                ("method", SettingValue::String("manual".into())),
+                (
+                    "address-data",
+                    SettingValue::Array(
+                        vec![SettingValue::dict(&group(vec![
+                            ("address", SettingValue::String("192.168.1.10".into())),
+                            ("prefix", SettingValue::UInt32(24)),
+                        ]))],
+                        "a{sv}".into(),
+                    ),
+                ),
+                ("gateway", SettingValue::String("192.168.1.1".into())),
+                (
+                    "dns",
+                    SettingValue::Array(
+                        vec![SettingValue::UInt32(u32::from_ne_bytes([8, 8, 8, 8]))],
+                        "u".into(),
+                    ),
+                ),
+                (
+                    "addresses",
+                    SettingValue::Array(
+                        vec![SettingValue::Array(
+                            vec![
+                                SettingValue::UInt32(u32::from_ne_bytes([192, 168, 1, 10])),
+                                SettingValue::UInt32(24),
+                                SettingValue::UInt32(u32::from_ne_bytes([192, 168, 1, 1])),
+                            ],
+                            "u".into(),
+                        )],
+                        "au".into(),
+                    ),
+                ),
+            ]),
+        );
+        groups.insert(
+            "proxy".into(),
+            group(vec![("method", SettingValue::Int32(0))]),
+        );
+
+        groups
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let settings = ConnectionSettings::from_groups(wifi_groups());
+
+        assert_eq!(settings.id(), "office");
+        assert_eq!(settings.kind(), "802-11-wireless");
+        assert_eq!(
+            settings.connection.interface_name,
+            Some("wlan0".to_string())
+        );
+        assert_eq!(settings.connection.autoconnect_priority, Some(10));
+        assert_eq!(settings.ssid().unwrap().as_str().unwrap(), "office");
+        assert_eq!(settings.mode(), Some("infrastructure"));
+
+        let ipv4 = settings.ipv4.as_ref().unwrap();
+        assert_eq!(ipv4.method, Some("manual".to_string()));
+        assert_eq!(
+            ipv4.address_data,
+            Some(vec![IpAddress {
+                address: "192.168.1.10".parse().unwrap(),
+                prefix: 24,
+                attributes: SettingGroup::new(),
+            }])
+        );
+        assert_eq!(ipv4.gateway, Some(Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(ipv4.dns, Some(vec![Ipv4Addr::new(8, 8, 8, 8)]));
+        assert!(ipv4.other.contains_key("addresses"));
+
+        assert!(settings.connection.other.contains_key("llmnr"));
+        assert!(settings.other.contains_key("proxy"));
+        assert!(settings.ethernet.is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let groups = wifi_groups();
+
+        let settings = ConnectionSettings::from_groups(groups.clone());
+
+        assert_eq!(settings.to_groups(), groups);
+    }
+
+    #[test]
+    fn test_unexpected_type_kept_raw() {
+        let mut groups = BTreeMap::new();
+        groups.insert(
+            "connection".into(),
+            group(vec![("autoconnect", SettingValue::Int32(1))]),
+        );
+
+        let settings = ConnectionSettings::from_groups(groups.clone());
+
+        assert_eq!(settings.connection.autoconnect, None);
+        assert_eq!(settings.to_groups(), groups);
+    }
+}
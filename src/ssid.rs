@@ -93,7 +93,7 @@ impl AsSsidSlice for [u8] {
                 self.len()
             )))
         } else {
-            Ok(unsafe { mem::transmute(self) })
+            Ok(unsafe { mem::transmute::<&[u8], &SsidSlice>(self) })
         }
     }
 }
@@ -1,15 +1,38 @@
-use std::net::Ipv4Addr;
+use std::cmp;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use dbus_nm::DBusNetworkManager;
+use ascii::AsciiStr;
+
+use backend::Backend;
 use errors::*;
 
-use connection::{connect_to_access_point, create_hotspot, Connection, ConnectionState};
+use connection::{
+    connect_to_access_point, connect_to_hidden_network, create_hotspot,
+    request_access_point_connection, request_hotspot, Connection, ConnectionState,
+    ConnectionStateWatch,
+};
 use device::{Device, PathGetter};
+use eap::Ieee8021xCredentials;
+use events::EventStream;
+use hotspot::HotspotConfig;
+use settings::{
+    ConnectionSetting, ConnectionSettings, WirelessSecuritySetting, WirelessSetting,
+    WIRELESS_SETTING,
+};
 use ssid::{AsSsidSlice, Ssid, SsidSlice};
 
+/// How often `LastScan` is polled while waiting for a scan to complete.
+const SCAN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait before repeating a refused scan request.
+const SCAN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The `wep-key-type` of WEP keys given as passphrases.
+pub const WEP_KEY_TYPE_PASSPHRASE: u32 = 2;
+
 pub struct WiFiDevice<'a> {
-    dbus_manager: Rc<DBusNetworkManager>,
+    backend: Rc<dyn Backend>,
     device: &'a Device,
 }
 
@@ -18,12 +41,10 @@ impl<'a> WiFiDevice<'a> {
     pub fn get_access_points(&self) -> Result<Vec<AccessPoint>> {
         let mut access_points = Vec::new();
 
-        let paths = self
-            .dbus_manager
-            .get_device_access_points(self.device.path())?;
+        let paths = self.backend.get_device_access_points(self.device.path())?;
 
         for path in paths {
-            if let Some(access_point) = get_access_point(&self.dbus_manager, &path)? {
+            if let Some(access_point) = get_access_point(&*self.backend, &path)? {
                 access_points.push(access_point);
             }
         }
@@ -35,40 +56,116 @@ impl<'a> WiFiDevice<'a> {
     }
 
     pub fn request_scan(&self) -> Result<()> {
-        self.dbus_manager
-            .request_access_point_scan(self.device.path())?;
+        self.backend
+            .request_access_point_scan(self.device.path(), &[])?;
         Ok(())
     }
 
+    /// Requests a scan that probes for the given SSIDs, which finds networks
+    /// that do not broadcast their SSID.
+    pub fn request_scan_for<T>(&self, ssids: &[&T]) -> Result<()>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        let ssids = as_ssid_slices(ssids)?;
+
+        self.backend
+            .request_access_point_scan(self.device.path(), &ssids)
+    }
+
+    /// The time of the last completed scan in milliseconds since boot, or
+    /// `None` if the device has never scanned.
+    pub fn last_scan(&self) -> Result<Option<i64>> {
+        let last_scan = self.backend.get_device_last_scan(self.device.path())?;
+
+        Ok(if last_scan < 0 { None } else { Some(last_scan) })
+    }
+
+    /// Scans and returns the access points found once the scan completes.
+    ///
+    /// Requests refused because a scan is already running or because of rate
+    /// limiting are retried every second until `timeout` expires.
+    pub fn scan_and_wait(&self, timeout: Duration) -> Result<Vec<AccessPoint>> {
+        self.scan_ssids_and_wait(&[], timeout)
+    }
+
+    /// Like `scan_and_wait`, but probes for the given SSIDs.
+    pub fn scan_for_and_wait<T>(&self, ssids: &[&T], timeout: Duration) -> Result<Vec<AccessPoint>>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        self.scan_ssids_and_wait(&as_ssid_slices(ssids)?, timeout)
+    }
+
+    fn scan_ssids_and_wait(
+        &self,
+        ssids: &[&SsidSlice],
+        timeout: Duration,
+    ) -> Result<Vec<AccessPoint>> {
+        let mut watch = request_scan_watch(self, ssids)?;
+
+        let start = Instant::now();
+
+        loop {
+            let remaining = match timeout.checked_sub(start.elapsed()) {
+                Some(remaining) if remaining > Duration::from_secs(0) => remaining,
+                _ => bail!(ErrorKind::NetworkManager(format!(
+                    "Scan did not complete within {:?}",
+                    timeout
+                ))),
+            };
+
+            if watch.next(cmp::min(remaining, SCAN_POLL_INTERVAL))? {
+                return self.get_access_points();
+            }
+        }
+    }
+
     pub fn connect(
         &self,
         access_point: &AccessPoint,
         credentials: &AccessPointCredentials,
     ) -> Result<(Connection, ConnectionState)> {
-        connect_to_access_point(
-            &self.dbus_manager,
+        connect_to_access_point(&self.backend, self.device.path(), access_point, credentials)
+    }
+
+    /// Connects to a network that does not broadcast its SSID.
+    ///
+    /// A profile marked as hidden is saved and bound to this device, the
+    /// network is probed for with a targeted scan and the profile is then
+    /// activated.
+    pub fn connect_hidden<T>(
+        &self,
+        ssid: &T,
+        credentials: &AccessPointCredentials,
+    ) -> Result<(Connection, ConnectionState)>
+    where
+        T: AsSsidSlice + ?Sized,
+    {
+        connect_to_hidden_network(
+            &self.backend,
             self.device.path(),
-            access_point,
+            self.device.interface(),
+            ssid,
             credentials,
         )
     }
 
+    /// Creates and activates an access point connection on this device.
     pub fn create_hotspot<T>(
         &self,
         ssid: &T,
-        password: Option<&str>,
-        address: Option<Ipv4Addr>,
+        config: &HotspotConfig,
     ) -> Result<(Connection, ConnectionState)>
     where
         T: AsSsidSlice + ?Sized,
     {
         create_hotspot(
-            &self.dbus_manager,
+            &self.backend,
             self.device.path(),
             self.device.interface(),
             ssid,
-            password,
-            address,
+            config,
         )
     }
 }
@@ -79,12 +176,82 @@ pub struct AccessPoint {
     pub ssid: Ssid,
     pub strength: u32,
     pub security: Security,
+    /// The hardware address of the access point.
+    pub bssid: String,
+    /// The radio channel frequency in MHz.
+    pub frequency: u32,
+    /// The maximum bitrate in kbit/s.
+    pub max_bitrate: u32,
+    pub mode: NM80211Mode,
+    /// When the access point was last found in a scan, in seconds since
+    /// boot. `None` if it has never been found.
+    pub last_seen: Option<i32>,
+    pub flags: NM80211ApFlags,
+    pub wpa_flags: NM80211ApSecurityFlags,
+    pub rsn_flags: NM80211ApSecurityFlags,
 }
 
 impl AccessPoint {
     pub fn ssid(&self) -> &SsidSlice {
         &self.ssid
     }
+
+    /// The band of the radio channel the access point operates on.
+    pub fn band(&self) -> Option<WiFiBand> {
+        match self.frequency {
+            2400..=2500 => Some(WiFiBand::TwoPointFourGhz),
+            4900..=5899 => Some(WiFiBand::FiveGhz),
+            5925..=7125 => Some(WiFiBand::SixGhz),
+            _ => None,
+        }
+    }
+
+    /// The number of the radio channel the access point operates on.
+    pub fn channel(&self) -> Option<u32> {
+        match (self.band()?, self.frequency) {
+            (WiFiBand::TwoPointFourGhz, 2484) => Some(14),
+            (WiFiBand::TwoPointFourGhz, frequency) if frequency >= 2412 => {
+                Some((frequency - 2407) / 5)
+            }
+            (WiFiBand::FiveGhz, frequency) if frequency >= 5000 => Some((frequency - 5000) / 5),
+            (WiFiBand::FiveGhz, frequency) => Some((frequency - 4000) / 5),
+            (WiFiBand::SixGhz, 5935) => Some(2),
+            (WiFiBand::SixGhz, frequency) if frequency >= 5955 => Some((frequency - 5950) / 5),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WiFiBand {
+    TwoPointFourGhz,
+    FiveGhz,
+    SixGhz,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NM80211Mode {
+    Unknown,
+    Adhoc,
+    Infra,
+    Ap,
+    Mesh,
+}
+
+impl From<i64> for NM80211Mode {
+    fn from(mode: i64) -> Self {
+        match mode {
+            0 => NM80211Mode::Unknown,
+            1 => NM80211Mode::Adhoc,
+            2 => NM80211Mode::Infra,
+            3 => NM80211Mode::Ap,
+            4 => NM80211Mode::Mesh,
+            _ => {
+                warn!("Undefined Wi-Fi mode: {}", mode);
+                NM80211Mode::Unknown
+            }
+        }
+    }
 }
 
 bitflags! {
@@ -94,22 +261,36 @@ bitflags! {
         const WPA          = 0b0000_0010;
         const WPA2         = 0b0000_0100;
         const ENTERPRISE   = 0b0000_1000;
+        // WPA3-Personal (SAE)
+        const WPA3         = 0b0001_0000;
+        // Enhanced Open (OWE), including transition mode
+        const OWE          = 0b0010_0000;
+        // WPA3-Enterprise 192-bit mode
+        const SUITE_B_192  = 0b0100_0000;
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum AccessPointCredentials {
     None,
     Wep {
         passphrase: String,
     },
+    /// WPA/WPA2-Personal. WPA3-only access points are joined with SAE.
     Wpa {
         passphrase: String,
     },
-    Enterprise {
-        identity: String,
+    /// WPA3-Personal (SAE).
+    Sae {
         passphrase: String,
     },
+    /// Enhanced Open (OWE).
+    Owe,
+    /// WPA/WPA2-Enterprise. Access points only offering the 192-bit mode are
+    /// joined with Suite-B.
+    Enterprise(Ieee8021xCredentials),
+    /// WPA3-Enterprise 192-bit mode (Suite-B).
+    EnterpriseSuiteB192(Ieee8021xCredentials),
 }
 
 bitflags! {
@@ -162,27 +343,149 @@ bitflags! {
     }
 }
 
-pub fn new_wifi_device<'a>(
-    dbus_manager: &Rc<DBusNetworkManager>,
-    device: &'a Device,
-) -> WiFiDevice<'a> {
+fn as_ssid_slices<'a, T>(ssids: &[&'a T]) -> Result<Vec<&'a SsidSlice>>
+where
+    T: AsSsidSlice + ?Sized,
+{
+    ssids.iter().map(|ssid| ssid.as_ssid_slice()).collect()
+}
+
+/// Follows a scan of a Wi-Fi device until it completes, repeating the scan
+/// request while it is refused.
+pub struct ScanWatch {
+    backend: Rc<dyn Backend>,
+    path: String,
+    ssids: Vec<Ssid>,
+    events: EventStream,
+    // The `LastScan` value from before the accepted scan request
+    requested_at: Option<i64>,
+    retry_at: Instant,
+    checked_at: Instant,
+}
+
+impl ScanWatch {
+    /// Requests the scan once it is due and handles the events arriving
+    /// within `timeout`. Returns `true` once the scan has completed. A zero
+    /// timeout only handles the events already received.
+    pub fn next(&mut self, timeout: Duration) -> Result<bool> {
+        if self.requested_at.is_none() && Instant::now() >= self.retry_at {
+            let last_scan = self.backend.get_device_last_scan(&self.path)?;
+
+            let ssids = self.ssids.iter().map(|ssid| &**ssid).collect::<Vec<_>>();
+
+            match self.backend.request_access_point_scan(&self.path, &ssids) {
+                Ok(()) => self.requested_at = Some(last_scan),
+                Err(Error(ErrorKind::ScanNotAllowed(ref message), _)) => {
+                    debug!("Scan request refused, retrying: {}", message);
+
+                    self.retry_at = Instant::now() + SCAN_RETRY_INTERVAL;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // `LastScan` is checked on every device property change, and
+        // periodically in case the change signal is missed
+        let changed = self.events.next_timeout(timeout)?.is_some();
+
+        while self.events.next_timeout(Duration::from_secs(0))?.is_some() {}
+
+        let last_scan = match self.requested_at {
+            Some(last_scan) if changed || self.checked_at.elapsed() >= SCAN_POLL_INTERVAL => {
+                last_scan
+            }
+            _ => return Ok(false),
+        };
+
+        self.checked_at = Instant::now();
+
+        Ok(self.backend.get_device_last_scan(&self.path)? != last_scan)
+    }
+}
+
+/// Starts following a scan of `device` for `ssids`. The scan is requested by
+/// the first call to `ScanWatch::next`.
+pub fn request_scan_watch(device: &WiFiDevice, ssids: &[&SsidSlice]) -> Result<ScanWatch> {
+    let ssids = ssids
+        .iter()
+        .map(|ssid| Ssid::from_bytes(ssid.as_bytes()))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ScanWatch {
+        backend: Rc::clone(&device.backend),
+        path: device.device.path().to_string(),
+        ssids,
+        events: device.backend.subscribe()?,
+        requested_at: None,
+        retry_at: Instant::now(),
+        checked_at: Instant::now(),
+    })
+}
+
+/// Like `WiFiDevice::connect`, but returns as soon as the activation is
+/// started.
+pub fn request_wifi_connect(
+    device: &WiFiDevice,
+    access_point: &AccessPoint,
+    credentials: &AccessPointCredentials,
+) -> Result<(Connection, ConnectionStateWatch)> {
+    request_access_point_connection(
+        &device.backend,
+        device.device.path(),
+        access_point,
+        credentials,
+    )
+}
+
+/// Like `WiFiDevice::create_hotspot`, but returns as soon as the activation
+/// is started.
+pub fn request_wifi_hotspot<T>(
+    device: &WiFiDevice,
+    ssid: &T,
+    config: &HotspotConfig,
+) -> Result<(Connection, ConnectionStateWatch)>
+where
+    T: AsSsidSlice + ?Sized,
+{
+    request_hotspot(
+        &device.backend,
+        device.device.path(),
+        device.device.interface(),
+        ssid,
+        config,
+    )
+}
+
+pub fn new_wifi_device<'a>(backend: &Rc<dyn Backend>, device: &'a Device) -> WiFiDevice<'a> {
     WiFiDevice {
-        dbus_manager: Rc::clone(dbus_manager),
+        backend: Rc::clone(backend),
         device,
     }
 }
 
-fn get_access_point(manager: &DBusNetworkManager, path: &str) -> Result<Option<AccessPoint>> {
+fn get_access_point(manager: &dyn Backend, path: &str) -> Result<Option<AccessPoint>> {
     if let Some(ssid) = manager.get_access_point_ssid(path) {
-        let strength = manager.get_access_point_strength(path)?;
+        let flags = manager.get_access_point_flags(path)?;
+
+        let wpa_flags = manager.get_access_point_wpa_flags(path)?;
+
+        let rsn_flags = manager.get_access_point_rsn_flags(path)?;
 
-        let security = get_access_point_security(manager, path)?;
+        let last_seen = manager.get_access_point_last_seen(path)?;
 
         let access_point = AccessPoint {
             path: path.to_string(),
             ssid,
-            strength,
-            security,
+            strength: manager.get_access_point_strength(path)?,
+            security: get_access_point_security(flags, wpa_flags, rsn_flags),
+            bssid: manager.get_access_point_bssid(path)?,
+            frequency: manager.get_access_point_frequency(path)?,
+            max_bitrate: manager.get_access_point_max_bitrate(path)?,
+            mode: manager.get_access_point_mode(path)?,
+            last_seen: if last_seen < 0 { None } else { Some(last_seen) },
+            flags,
+            wpa_flags,
+            rsn_flags,
         };
 
         Ok(Some(access_point))
@@ -191,13 +494,11 @@ fn get_access_point(manager: &DBusNetworkManager, path: &str) -> Result<Option<A
     }
 }
 
-fn get_access_point_security(manager: &DBusNetworkManager, path: &str) -> Result<Security> {
-    let flags = manager.get_access_point_flags(path)?;
-
-    let wpa_flags = manager.get_access_point_wpa_flags(path)?;
-
-    let rsn_flags = manager.get_access_point_rsn_flags(path)?;
-
+fn get_access_point_security(
+    flags: NM80211ApFlags,
+    wpa_flags: NM80211ApSecurityFlags,
+    rsn_flags: NM80211ApSecurityFlags,
+) -> Security {
     let mut security = Security::NONE;
 
     if flags.contains(NM80211ApFlags::AP_FLAGS_PRIVACY)
@@ -211,15 +512,391 @@ fn get_access_point_security(manager: &DBusNetworkManager, path: &str) -> Result
         security |= Security::WPA;
     }
 
-    if rsn_flags != NM80211ApSecurityFlags::AP_SEC_NONE {
+    // Access points offering only WPA3 or OWE key management do not accept
+    // WPA2 clients
+    let wpa3_key_mgmt = NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE
+        | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_OWE
+        | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_OWE_TM
+        | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_EAP_SUITE_B_192;
+
+    if rsn_flags != NM80211ApSecurityFlags::AP_SEC_NONE
+        && (!rsn_flags.intersects(wpa3_key_mgmt)
+            || rsn_flags.intersects(
+                NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_PSK
+                    | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_802_1X,
+            ))
+    {
         security |= Security::WPA2;
     }
 
+    if rsn_flags.contains(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE) {
+        security |= Security::WPA3;
+    }
+
+    if rsn_flags.intersects(
+        NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_OWE
+            | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_OWE_TM,
+    ) {
+        security |= Security::OWE;
+    }
+
     if wpa_flags.contains(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_802_1X)
         || rsn_flags.contains(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_802_1X)
     {
         security |= Security::ENTERPRISE;
     }
 
-    Ok(security)
+    if rsn_flags.contains(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_EAP_SUITE_B_192) {
+        security |= Security::ENTERPRISE | Security::SUITE_B_192;
+    }
+
+    security
+}
+
+/// Chooses the `key-mgmt` wireless security setting for connecting to
+/// `access_point` with `credentials`. Returns `None` for open and WEP
+/// networks, which do not need one.
+///
+/// WPA2 credentials fall back to the WPA3 equivalent for access points that
+/// do not offer WPA2, while no credentials pick OWE on Enhanced Open and
+/// transition mode access points.
+pub fn key_mgmt(
+    access_point: &AccessPoint,
+    credentials: &AccessPointCredentials,
+) -> Option<&'static str> {
+    let offers = |key_mgmt: NM80211ApSecurityFlags| {
+        access_point.wpa_flags.contains(key_mgmt) || access_point.rsn_flags.contains(key_mgmt)
+    };
+
+    match *credentials {
+        AccessPointCredentials::None => {
+            if access_point.security.contains(Security::OWE) {
+                Some("owe")
+            } else {
+                None
+            }
+        }
+        AccessPointCredentials::Wep { .. } => None,
+        AccessPointCredentials::Wpa { .. } => {
+            if offers(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE)
+                && !offers(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_PSK)
+            {
+                Some("sae")
+            } else {
+                Some("wpa-psk")
+            }
+        }
+        AccessPointCredentials::Sae { .. } => Some("sae"),
+        AccessPointCredentials::Owe => Some("owe"),
+        AccessPointCredentials::Enterprise(_) => {
+            if offers(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_EAP_SUITE_B_192)
+                && !offers(NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_802_1X)
+            {
+                Some("wpa-eap-suite-b-192")
+            } else {
+                Some("wpa-eap")
+            }
+        }
+        AccessPointCredentials::EnterpriseSuiteB192(_) => Some("wpa-eap-suite-b-192"),
+    }
+}
+
+/// Builds a Wi-Fi client profile for a network that does not broadcast its
+/// SSID, bound to `interface`.
+///
+/// Unlike with visible access points, the supported key management cannot be
+/// looked up, so it is derived from the credentials alone.
+pub fn hidden_network_settings(
+    ssid: &SsidSlice,
+    interface: &str,
+    credentials: &AccessPointCredentials,
+) -> Result<ConnectionSettings> {
+    let security = |key_mgmt: &str| WirelessSecuritySetting {
+        key_mgmt: Some(key_mgmt.into()),
+        ..Default::default()
+    };
+
+    let mut settings = ConnectionSettings {
+        connection: ConnectionSetting {
+            id: Some(String::from_utf8_lossy(ssid.as_bytes()).into_owned()),
+            kind: Some(WIRELESS_SETTING.into()),
+            interface_name: Some(interface.into()),
+            ..Default::default()
+        },
+        wireless: Some(WirelessSetting {
+            ssid: Some(Ssid::from_bytes(ssid.as_bytes().to_vec())?),
+            mode: Some("infrastructure".into()),
+            hidden: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    match *credentials {
+        AccessPointCredentials::None => {}
+        AccessPointCredentials::Wep { ref passphrase } => {
+            settings.wireless_security = Some(WirelessSecuritySetting {
+                wep_key_type: Some(WEP_KEY_TYPE_PASSPHRASE),
+                wep_key0: Some(verify_ascii_password(passphrase)?.into()),
+                ..security("none")
+            });
+        }
+        AccessPointCredentials::Wpa { ref passphrase } => {
+            settings.wireless_security = Some(WirelessSecuritySetting {
+                psk: Some(verify_ascii_password(passphrase)?.into()),
+                ..security("wpa-psk")
+            });
+        }
+        AccessPointCredentials::Sae { ref passphrase } => {
+            if passphrase.is_empty() {
+                bail!(ErrorKind::PreSharedKey("Empty password".into()));
+            }
+
+            settings.wireless_security = Some(WirelessSecuritySetting {
+                psk: Some(passphrase.clone()),
+                ..security("sae")
+            });
+        }
+        AccessPointCredentials::Owe => {
+            settings.wireless_security = Some(security("owe"));
+        }
+        AccessPointCredentials::Enterprise(ref ieee8021x) => {
+            settings.wireless_security = Some(security("wpa-eap"));
+            settings.ieee8021x = Some(ieee8021x.to_setting()?);
+        }
+        AccessPointCredentials::EnterpriseSuiteB192(ref ieee8021x) => {
+            settings.wireless_security = Some(security("wpa-eap-suite-b-192"));
+            settings.ieee8021x = Some(ieee8021x.to_setting()?);
+        }
+    }
+
+    Ok(settings)
+}
+
+pub fn verify_ascii_password(password: &str) -> Result<&str> {
+    match AsciiStr::from_ascii(password) {
+        Err(e) => Err(e).chain_err(|| ErrorKind::PreSharedKey("Not an ASCII password".into())),
+        Ok(p) => {
+            if p.len() < 8 {
+                bail!(ErrorKind::PreSharedKey(format!(
+                    "Password length should be at least 8 characters: {} len",
+                    p.len()
+                )))
+            } else if p.len() > 64 {
+                bail!(ErrorKind::PreSharedKey(format!(
+                    "Password length should not exceed 64: {} len",
+                    p.len()
+                )))
+            } else {
+                Ok(password)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use connection::ConnectionState;
+    use fake::fixtures;
+
+    fn access_point(frequency: u32) -> AccessPoint {
+        AccessPoint {
+            path: "/org/freedesktop/NetworkManager/AccessPoint/1".into(),
+            ssid: Ssid::from_bytes("office").unwrap(),
+            strength: 70,
+            security: Security::WPA2,
+            bssid: "00:11:22:33:44:55".into(),
+            frequency,
+            max_bitrate: 130_000,
+            mode: NM80211Mode::Infra,
+            last_seen: Some(1200),
+            flags: NM80211ApFlags::AP_FLAGS_PRIVACY,
+            wpa_flags: NM80211ApSecurityFlags::AP_SEC_NONE,
+            rsn_flags: NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_PSK,
+        }
+    }
+
+    #[test]
+    fn test_wpa3_security() {
+        let sae = NM80211ApSecurityFlags::AP_SEC_PAIR_CCMP
+            | NM80211ApSecurityFlags::AP_SEC_GROUP_CCMP
+            | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE;
+        let transition = sae | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_PSK;
+        let owe =
+            NM80211ApSecurityFlags::AP_SEC_PAIR_CCMP | NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_OWE;
+        let suite_b = NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_EAP_SUITE_B_192;
+
+        let privacy = NM80211ApFlags::AP_FLAGS_PRIVACY;
+        let none = NM80211ApSecurityFlags::AP_SEC_NONE;
+
+        assert_eq!(
+            get_access_point_security(privacy, none, sae),
+            Security::WPA3
+        );
+        assert_eq!(
+            get_access_point_security(privacy, none, transition),
+            Security::WPA2 | Security::WPA3
+        );
+        assert_eq!(get_access_point_security(privacy, none, owe), Security::OWE);
+        assert_eq!(
+            get_access_point_security(privacy, none, suite_b),
+            Security::ENTERPRISE | Security::SUITE_B_192
+        );
+    }
+
+    #[test]
+    fn test_key_mgmt() {
+        let wpa = AccessPointCredentials::Wpa {
+            passphrase: "password".into(),
+        };
+
+        let mut access_point = access_point(2412);
+        assert_eq!(key_mgmt(&access_point, &wpa), Some("wpa-psk"));
+
+        access_point.rsn_flags |= NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE;
+        assert_eq!(key_mgmt(&access_point, &wpa), Some("wpa-psk"));
+
+        access_point.rsn_flags = NM80211ApSecurityFlags::AP_SEC_KEY_MGMT_SAE;
+        assert_eq!(key_mgmt(&access_point, &wpa), Some("sae"));
+
+        access_point.security = Security::OWE;
+        assert_eq!(
+            key_mgmt(&access_point, &AccessPointCredentials::None),
+            Some("owe")
+        );
+    }
+
+    #[test]
+    fn test_band_and_channel() {
+        let cases = [
+            (2412, Some(WiFiBand::TwoPointFourGhz), Some(1)),
+            (2484, Some(WiFiBand::TwoPointFourGhz), Some(14)),
+            (5180, Some(WiFiBand::FiveGhz), Some(36)),
+            (5825, Some(WiFiBand::FiveGhz), Some(165)),
+            (5955, Some(WiFiBand::SixGhz), Some(1)),
+            (6115, Some(WiFiBand::SixGhz), Some(33)),
+            (60480, None, None),
+        ];
+
+        for &(frequency, band, channel) in &cases {
+            let access_point = access_point(frequency);
+
+            assert_eq!(access_point.band(), band, "{} MHz", frequency);
+            assert_eq!(access_point.channel(), channel, "{} MHz", frequency);
+        }
+    }
+
+    #[test]
+    fn test_connect_wpa3() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        fake.add_access_point(&device_path, "wpa3", 90, Security::WPA3, Some("password"));
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+        let access_points = wifi_device.get_access_points().unwrap();
+
+        assert_eq!(access_points[0].security, Security::WPA3);
+
+        let credentials = AccessPointCredentials::Sae {
+            passphrase: "password".into(),
+        };
+        let (_, state) = wifi_device
+            .connect(&access_points[0], &credentials)
+            .unwrap();
+
+        assert_eq!(state, ConnectionState::Activated);
+    }
+
+    #[test]
+    fn test_connect_hidden() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        let hidden = fake.add_access_point(
+            &device_path,
+            "factory",
+            60,
+            Security::WPA2,
+            Some("password"),
+        );
+        fake.set_access_point_hidden(&hidden, true);
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+
+        assert!(wifi_device
+            .get_access_points()
+            .unwrap()
+            .iter()
+            .all(|ap| ap.ssid().as_bytes() != b"factory"));
+
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "password".into(),
+        };
+        let (connection, state) = wifi_device.connect_hidden("factory", &credentials).unwrap();
+
+        assert_eq!(state, ConnectionState::Activated);
+
+        let wireless = connection.settings().wireless.as_ref().unwrap();
+
+        assert_eq!(wireless.hidden, Some(true));
+        assert_eq!(
+            connection.settings().connection.interface_name,
+            Some("wlan0".to_string())
+        );
+
+        let credentials = AccessPointCredentials::Wpa {
+            passphrase: "wrong password".into(),
+        };
+
+        assert!(wifi_device.connect_hidden("factory", &credentials).is_err());
+    }
+
+    #[test]
+    fn test_scan_and_wait() {
+        let (fake, manager, device_path) = fixtures::wifi();
+
+        let device = manager.get_device_by_interface("wlan0").unwrap();
+        let wifi_device = device.as_wifi_device().unwrap();
+
+        assert_eq!(wifi_device.last_scan().unwrap(), None);
+
+        let access_points = wifi_device.scan_and_wait(Duration::from_secs(5)).unwrap();
+
+        assert_eq!(access_points.len(), 2);
+        assert_eq!(wifi_device.last_scan().unwrap(), Some(1000));
+
+        fake.refuse_scans(&device_path, 1);
+
+        wifi_device
+            .scan_for_and_wait(&["office"], Duration::from_secs(5))
+            .unwrap();
+
+        assert_eq!(wifi_device.last_scan().unwrap(), Some(2000));
+
+        fake.refuse_scans(&device_path, 1);
+
+        match wifi_device.request_scan() {
+            Err(Error(ErrorKind::ScanNotAllowed(_), _)) => {}
+            result => panic!("Unexpected scan result: {:?}", result),
+        }
+
+        // Refused requests are not repeated before the retry interval
+        fake.refuse_scans(&device_path, 2);
+
+        assert!(wifi_device
+            .scan_and_wait(Duration::from_millis(600))
+            .is_err());
+        assert!(wifi_device.request_scan().is_err());
+
+        fake.refuse_scans(&device_path, u32::MAX);
+
+        assert!(wifi_device
+            .scan_and_wait(Duration::from_millis(50))
+            .is_err());
+    }
 }